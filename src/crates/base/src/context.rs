@@ -8,6 +8,7 @@ use std::future::Future;
 use std::marker::PhantomData;
 use std::pin::Pin;
 use std::sync::Arc;
+use tokio::sync::watch;
 
 pub struct CardinalContext {
     pub config: Arc<CardinalConfig>,
@@ -181,6 +182,35 @@ impl CardinalContext {
     }
 }
 
+/// Shared slot holding the active context. Replacing it makes new requests resolve the
+/// new context while requests already in flight keep the `Arc` they resolved.
+#[derive(Clone)]
+pub struct ContextHandle {
+    current: Arc<watch::Sender<Arc<CardinalContext>>>,
+}
+
+impl ContextHandle {
+    pub fn new(context: Arc<CardinalContext>) -> Self {
+        Self {
+            current: Arc::new(watch::Sender::new(context)),
+        }
+    }
+
+    pub fn current(&self) -> Arc<CardinalContext> {
+        self.current.borrow().clone()
+    }
+
+    /// Swaps in `context`, returning the one it replaced.
+    pub fn replace(&self, context: Arc<CardinalContext>) -> Arc<CardinalContext> {
+        self.current.send_replace(context)
+    }
+
+    /// Receiver notified every time the context is replaced.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<CardinalContext>> {
+        self.current.subscribe()
+    }
+}
+
 // RAII guard for the constructing set, to ensure cleanup on early returns
 struct ConstructGuard<'a> {
     ctx: &'a CardinalContext,
@@ -227,6 +257,7 @@ mod tests {
     use cardinal_errors::CardinalError;
 
     #[derive(Debug)]
+    #[allow(dead_code)]
     struct Db {
         dsn: String,
     }
@@ -376,8 +407,10 @@ mod tests {
     }
 
     #[derive(Debug)]
+    #[allow(dead_code)]
     struct A(Arc<B>);
     #[derive(Debug)]
+    #[allow(dead_code)]
     struct B(Arc<A>);
 
     #[async_trait]
@@ -408,4 +441,18 @@ mod tests {
             ))
         ));
     }
+
+    #[tokio::test]
+    async fn context_handle_swaps_and_notifies() {
+        let first = Arc::new(get_context());
+        let handle = ContextHandle::new(first.clone());
+        let changes = handle.subscribe();
+
+        let second = Arc::new(get_context());
+        let previous = handle.replace(second.clone());
+
+        assert!(Arc::ptr_eq(&previous, &first));
+        assert!(Arc::ptr_eq(&handle.current(), &second));
+        assert!(changes.has_changed().unwrap());
+    }
 }
@@ -1,2 +1,6 @@
+pub mod balancer;
+pub mod circuit_breaker;
 pub mod container;
 pub mod matcher;
+pub mod mirror;
+pub mod upstream_tls;
@@ -0,0 +1,600 @@
+use cardinal_config::{
+    Destination, DestinationLoadBalancer, LoadBalancerHashKey, LoadBalancingStrategy,
+};
+use pingora::http::RequestHeader;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A single upstream origin a destination can forward traffic to.
+#[derive(Debug)]
+pub struct UpstreamEndpoint {
+    pub url: String,
+    pub weight: u32,
+    active_connections: AtomicUsize,
+    healthy: AtomicBool,
+}
+
+impl UpstreamEndpoint {
+    pub fn new(url: impl Into<String>, weight: u32) -> Self {
+        Self {
+            url: url.into(),
+            weight,
+            active_connections: AtomicUsize::new(0),
+            healthy: AtomicBool::new(true),
+        }
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    /// Updates the health flag, returning `true` when the state actually changed.
+    pub fn set_healthy(&self, healthy: bool) -> bool {
+        self.healthy.swap(healthy, Ordering::Relaxed) != healthy
+    }
+
+    /// Marks the endpoint as in use until the returned lease is dropped.
+    pub fn acquire(self: &Arc<Self>) -> UpstreamLease {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        UpstreamLease {
+            endpoint: Arc::clone(self),
+        }
+    }
+}
+
+/// Tracks an in-flight request against an endpoint, used by least-connections balancing.
+#[derive(Debug)]
+pub struct UpstreamLease {
+    endpoint: Arc<UpstreamEndpoint>,
+}
+
+impl UpstreamLease {
+    pub fn endpoint(&self) -> &Arc<UpstreamEndpoint> {
+        &self.endpoint
+    }
+}
+
+impl Drop for UpstreamLease {
+    fn drop(&mut self) {
+        self.endpoint
+            .active_connections
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Strategy used to pick one endpoint out of a set of candidates.
+///
+/// `hash` is only provided when the destination is configured with a hash key.
+pub trait LoadBalancer: Send + Sync {
+    fn select(
+        &self,
+        candidates: &[Arc<UpstreamEndpoint>],
+        hash: Option<u64>,
+    ) -> Option<Arc<UpstreamEndpoint>>;
+}
+
+#[derive(Default)]
+pub struct RoundRobinBalancer {
+    counter: AtomicUsize,
+}
+
+impl LoadBalancer for RoundRobinBalancer {
+    fn select(
+        &self,
+        candidates: &[Arc<UpstreamEndpoint>],
+        _hash: Option<u64>,
+    ) -> Option<Arc<UpstreamEndpoint>> {
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let next = self.counter.fetch_add(1, Ordering::Relaxed);
+        candidates.get(next % candidates.len()).cloned()
+    }
+}
+
+#[derive(Default)]
+pub struct WeightedBalancer {
+    counter: AtomicUsize,
+}
+
+impl LoadBalancer for WeightedBalancer {
+    fn select(
+        &self,
+        candidates: &[Arc<UpstreamEndpoint>],
+        _hash: Option<u64>,
+    ) -> Option<Arc<UpstreamEndpoint>> {
+        let total_weight: usize = candidates.iter().map(|e| e.weight as usize).sum();
+        if total_weight == 0 {
+            return None;
+        }
+
+        let mut slot = self.counter.fetch_add(1, Ordering::Relaxed) % total_weight;
+        for endpoint in candidates {
+            let weight = endpoint.weight as usize;
+            if slot < weight {
+                return Some(endpoint.clone());
+            }
+            slot -= weight;
+        }
+
+        None
+    }
+}
+
+#[derive(Default)]
+pub struct LeastConnectionsBalancer {
+    counter: AtomicUsize,
+}
+
+impl LoadBalancer for LeastConnectionsBalancer {
+    fn select(
+        &self,
+        candidates: &[Arc<UpstreamEndpoint>],
+        _hash: Option<u64>,
+    ) -> Option<Arc<UpstreamEndpoint>> {
+        if candidates.is_empty() {
+            return None;
+        }
+
+        // Start scanning from a rotating offset so ties are spread across endpoints.
+        let offset = self.counter.fetch_add(1, Ordering::Relaxed) % candidates.len();
+        candidates
+            .iter()
+            .cycle()
+            .skip(offset)
+            .take(candidates.len())
+            .min_by(|a, b| {
+                let left = a.active_connections() as u64 * b.weight as u64;
+                let right = b.active_connections() as u64 * a.weight as u64;
+                left.cmp(&right)
+            })
+            .cloned()
+    }
+}
+
+/// Weighted rendezvous hashing. Removing a candidate only remaps the keys that were
+/// assigned to it, which keeps affinity stable when the candidate set changes.
+#[derive(Default)]
+pub struct ConsistentHashBalancer {
+    fallback: RoundRobinBalancer,
+}
+
+impl LoadBalancer for ConsistentHashBalancer {
+    fn select(
+        &self,
+        candidates: &[Arc<UpstreamEndpoint>],
+        hash: Option<u64>,
+    ) -> Option<Arc<UpstreamEndpoint>> {
+        let Some(hash) = hash else {
+            return self.fallback.select(candidates, None);
+        };
+
+        candidates
+            .iter()
+            .map(|endpoint| {
+                let point = fnv1a(&hash.to_be_bytes(), endpoint.url.as_bytes());
+                let unit = (point as f64 + 1.0) / (u64::MAX as f64 + 2.0);
+                (endpoint, endpoint.weight as f64 / -unit.ln())
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(endpoint, _)| endpoint.clone())
+    }
+}
+
+/// Point-in-time health of a single endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpstreamHealth {
+    pub url: String,
+    pub healthy: bool,
+}
+
+/// The set of endpoints behind a destination together with the strategy used to pick one.
+pub struct UpstreamPool {
+    endpoints: Vec<Arc<UpstreamEndpoint>>,
+    balancer: Arc<dyn LoadBalancer>,
+    hash_key: Option<LoadBalancerHashKey>,
+}
+
+impl UpstreamPool {
+    pub fn new(
+        endpoints: Vec<Arc<UpstreamEndpoint>>,
+        balancer: Arc<dyn LoadBalancer>,
+        hash_key: Option<LoadBalancerHashKey>,
+    ) -> Self {
+        Self {
+            endpoints,
+            balancer,
+            hash_key,
+        }
+    }
+
+    pub fn from_destination(destination: &Destination) -> Self {
+        let endpoints = if destination.upstreams.is_empty() {
+            vec![Arc::new(UpstreamEndpoint::new(destination.url.clone(), 1))]
+        } else {
+            destination
+                .upstreams
+                .iter()
+                .map(|u| Arc::new(UpstreamEndpoint::new(u.url.clone(), u.weight.unwrap_or(1))))
+                .collect()
+        };
+
+        let lb = destination.load_balancer.clone().unwrap_or_default();
+        let hash_key = match lb.strategy {
+            LoadBalancingStrategy::ConsistentHash => Some(lb.hash_key.clone().unwrap_or_default()),
+            _ => None,
+        };
+
+        Self::new(endpoints, balancer_for(&lb), hash_key)
+    }
+
+    /// Replaces the balancing strategy, e.g. with a custom [`LoadBalancer`] implementation.
+    pub fn with_balancer(mut self, balancer: Arc<dyn LoadBalancer>) -> Self {
+        self.balancer = balancer;
+        self
+    }
+
+    pub fn endpoints(&self) -> &[Arc<UpstreamEndpoint>] {
+        &self.endpoints
+    }
+
+    pub fn hash_key(&self) -> Option<&LoadBalancerHashKey> {
+        self.hash_key.as_ref()
+    }
+
+    /// Computes the affinity hash for a request according to the configured hash key.
+    pub fn request_hash(&self, req: &RequestHeader, client_ip: Option<IpAddr>) -> Option<u64> {
+        request_hash(self.hash_key.as_ref()?, req, client_ip)
+    }
+
+    pub fn health(&self) -> Vec<UpstreamHealth> {
+        self.endpoints
+            .iter()
+            .map(|e| UpstreamHealth {
+                url: e.url.clone(),
+                healthy: e.is_healthy(),
+            })
+            .collect()
+    }
+
+    /// Takes over the health of endpoints with the same URL in `previous`, the pool of the
+    /// same destination before a reload.
+    pub fn carry_health_from(&self, previous: &UpstreamPool) {
+        for endpoint in &self.endpoints {
+            if let Some(old) = previous
+                .endpoints
+                .iter()
+                .find(|old| old.url == endpoint.url)
+            {
+                endpoint.set_healthy(old.is_healthy());
+            }
+        }
+    }
+
+    /// Selects a healthy endpoint. Returns `None` when every endpoint is marked unhealthy.
+    pub fn select(&self, hash: Option<u64>) -> Option<Arc<UpstreamEndpoint>> {
+        if self.endpoints.iter().all(|e| e.is_healthy()) {
+            return self.balancer.select(&self.endpoints, hash);
+        }
+
+        self.select_from(hash, |_| true)
+    }
+
+    /// Selects a healthy endpoint while avoiding `excluded` ones, unless nothing else is left.
+    pub fn select_excluding(
+        &self,
+        hash: Option<u64>,
+        excluded: &[Arc<UpstreamEndpoint>],
+    ) -> Option<Arc<UpstreamEndpoint>> {
+        self.select_from(hash, |e| !excluded.iter().any(|x| Arc::ptr_eq(x, e)))
+            .or_else(|| self.select(hash))
+    }
+
+    fn select_from<F>(&self, hash: Option<u64>, filter: F) -> Option<Arc<UpstreamEndpoint>>
+    where
+        F: Fn(&Arc<UpstreamEndpoint>) -> bool,
+    {
+        let candidates: Vec<Arc<UpstreamEndpoint>> = self
+            .endpoints
+            .iter()
+            .filter(|e| e.is_healthy() && filter(e))
+            .cloned()
+            .collect();
+
+        self.balancer.select(&candidates, hash)
+    }
+}
+
+fn balancer_for(config: &DestinationLoadBalancer) -> Arc<dyn LoadBalancer> {
+    match config.strategy {
+        LoadBalancingStrategy::RoundRobin => Arc::new(RoundRobinBalancer::default()),
+        LoadBalancingStrategy::Weighted => Arc::new(WeightedBalancer::default()),
+        LoadBalancingStrategy::LeastConnections => Arc::new(LeastConnectionsBalancer::default()),
+        LoadBalancingStrategy::ConsistentHash => Arc::new(ConsistentHashBalancer::default()),
+    }
+}
+
+/// Hash of the request attribute selected by `key`, `None` when the request lacks it.
+pub fn request_hash(
+    key: &LoadBalancerHashKey,
+    req: &RequestHeader,
+    client_ip: Option<IpAddr>,
+) -> Option<u64> {
+    match key {
+        LoadBalancerHashKey::ClientIp => client_ip.map(|ip| fnv1a(ip.to_string().as_bytes(), &[])),
+        LoadBalancerHashKey::Path => Some(fnv1a(req.uri.path().as_bytes(), &[])),
+        LoadBalancerHashKey::Header(name) => req
+            .headers
+            .get(name.as_str())
+            .map(|value| fnv1a(value.as_bytes(), &[])),
+        LoadBalancerHashKey::Cookie(name) => {
+            cookie_value(req, name).map(|value| fnv1a(value.as_bytes(), &[]))
+        }
+    }
+}
+
+pub(crate) fn cookie_value(req: &RequestHeader, name: &str) -> Option<String> {
+    req.headers
+        .get_all("cookie")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
+
+fn fnv1a(seed: &[u8], data: &[u8]) -> u64 {
+    seed.iter()
+        .chain(data.iter())
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cardinal_config::DestinationUpstream;
+    use http::Method;
+    use std::collections::HashMap;
+
+    fn endpoints(weights: &[u32]) -> Vec<Arc<UpstreamEndpoint>> {
+        weights
+            .iter()
+            .enumerate()
+            .map(|(i, w)| Arc::new(UpstreamEndpoint::new(format!("http://10.0.0.{i}:80"), *w)))
+            .collect()
+    }
+
+    fn count_selections(
+        balancer: &dyn LoadBalancer,
+        candidates: &[Arc<UpstreamEndpoint>],
+        rounds: usize,
+    ) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for _ in 0..rounds {
+            let picked = balancer.select(candidates, None).unwrap();
+            *counts.entry(picked.url.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    fn destination(upstreams: Vec<DestinationUpstream>) -> Destination {
+        Destination {
+            name: "posts".into(),
+            url: String::new(),
+            upstreams,
+            load_balancer: None,
+            health_check: None,
+            default: false,
+            r#match: None,
+            routes: Vec::new(),
+            middleware: Vec::new(),
+            timeout: None,
+            retry: None,
+            circuit_breaker: None,
+            preserve_host: false,
+            host_header: None,
+            sni: None,
+            tls: None,
+            mirror: None,
+            cache: None,
+            compression: None,
+            limits: None,
+            websocket: None,
+            grpc: None,
+        }
+    }
+
+    #[test]
+    fn round_robin_cycles_through_endpoints() {
+        let candidates = endpoints(&[1, 1, 1]);
+        let balancer = RoundRobinBalancer::default();
+
+        let picked: Vec<String> = (0..6)
+            .map(|_| balancer.select(&candidates, None).unwrap().url.clone())
+            .collect();
+
+        assert_eq!(picked[0..3], picked[3..6]);
+        assert_ne!(picked[0], picked[1]);
+        assert_ne!(picked[1], picked[2]);
+    }
+
+    #[test]
+    fn weighted_respects_weights() {
+        let candidates = endpoints(&[3, 1]);
+        let counts = count_selections(&WeightedBalancer::default(), &candidates, 400);
+
+        assert_eq!(counts[&candidates[0].url], 300);
+        assert_eq!(counts[&candidates[1].url], 100);
+    }
+
+    #[test]
+    fn least_connections_prefers_idle_endpoint() {
+        let candidates = endpoints(&[1, 1]);
+        let _busy = candidates[0].acquire();
+        let balancer = LeastConnectionsBalancer::default();
+
+        for _ in 0..4 {
+            let picked = balancer.select(&candidates, None).unwrap();
+            assert_eq!(picked.url, candidates[1].url);
+        }
+    }
+
+    #[test]
+    fn lease_releases_connection_on_drop() {
+        let candidates = endpoints(&[1]);
+        let lease = candidates[0].acquire();
+        assert_eq!(candidates[0].active_connections(), 1);
+        drop(lease);
+        assert_eq!(candidates[0].active_connections(), 0);
+    }
+
+    #[test]
+    fn consistent_hash_is_stable_and_minimal_on_removal() {
+        let candidates = endpoints(&[1, 1, 1, 1]);
+        let balancer = ConsistentHashBalancer::default();
+
+        let before: Vec<String> = (0..200u64)
+            .map(|k| balancer.select(&candidates, Some(k)).unwrap().url.clone())
+            .collect();
+        let again: Vec<String> = (0..200u64)
+            .map(|k| balancer.select(&candidates, Some(k)).unwrap().url.clone())
+            .collect();
+        assert_eq!(before, again);
+
+        let removed = candidates[1].url.clone();
+        let remaining: Vec<_> = candidates
+            .iter()
+            .filter(|e| e.url != removed)
+            .cloned()
+            .collect();
+        for (k, previous) in before.iter().enumerate() {
+            let now = balancer.select(&remaining, Some(k as u64)).unwrap();
+            if *previous != removed {
+                assert_eq!(&now.url, previous);
+            }
+        }
+    }
+
+    #[test]
+    fn pool_falls_back_to_destination_url() {
+        let mut config = destination(Vec::new());
+        config.url = "http://single.internal".into();
+
+        let pool = UpstreamPool::from_destination(&config);
+        assert_eq!(pool.endpoints().len(), 1);
+        assert_eq!(pool.select(None).unwrap().url, "http://single.internal");
+    }
+
+    #[test]
+    fn pool_select_excluding_skips_failed_endpoint() {
+        let pool = UpstreamPool::from_destination(&destination(vec![
+            DestinationUpstream {
+                url: "http://a.internal".into(),
+                weight: None,
+            },
+            DestinationUpstream {
+                url: "http://b.internal".into(),
+                weight: None,
+            },
+        ]));
+        let failed = vec![pool.endpoints()[0].clone()];
+
+        for _ in 0..4 {
+            let picked = pool.select_excluding(None, &failed).unwrap();
+            assert_eq!(picked.url, "http://b.internal");
+        }
+    }
+
+    #[test]
+    fn pool_skips_unhealthy_endpoints() {
+        let pool = UpstreamPool::from_destination(&destination(vec![
+            DestinationUpstream {
+                url: "http://a.internal".into(),
+                weight: None,
+            },
+            DestinationUpstream {
+                url: "http://b.internal".into(),
+                weight: None,
+            },
+        ]));
+        assert!(pool.endpoints()[0].set_healthy(false));
+        assert!(!pool.endpoints()[0].set_healthy(false));
+
+        for _ in 0..4 {
+            assert_eq!(pool.select(None).unwrap().url, "http://b.internal");
+        }
+        assert_eq!(
+            pool.health(),
+            vec![
+                UpstreamHealth {
+                    url: "http://a.internal".into(),
+                    healthy: false,
+                },
+                UpstreamHealth {
+                    url: "http://b.internal".into(),
+                    healthy: true,
+                },
+            ]
+        );
+
+        pool.endpoints()[1].set_healthy(false);
+        assert!(pool.select(None).is_none());
+        assert!(pool.select_excluding(None, &[]).is_none());
+    }
+
+    #[test]
+    fn pool_hashes_header_and_cookie_keys() {
+        let mut config = destination(vec![DestinationUpstream {
+            url: "http://a.internal".into(),
+            weight: None,
+        }]);
+        config.load_balancer = Some(DestinationLoadBalancer {
+            strategy: LoadBalancingStrategy::ConsistentHash,
+            hash_key: Some(LoadBalancerHashKey::Cookie("session".into())),
+        });
+        let pool = UpstreamPool::from_destination(&config);
+
+        let mut req = RequestHeader::build(Method::GET, b"/", None).unwrap();
+        assert_eq!(pool.request_hash(&req, None), None);
+
+        req.insert_header("cookie", "theme=dark; session=abc123")
+            .unwrap();
+        let with_cookie = pool.request_hash(&req, None);
+        assert_eq!(with_cookie, Some(fnv1a(b"abc123", &[])));
+
+        config.load_balancer = Some(DestinationLoadBalancer {
+            strategy: LoadBalancingStrategy::ConsistentHash,
+            hash_key: Some(LoadBalancerHashKey::Header("x-user".into())),
+        });
+        let pool = UpstreamPool::from_destination(&config);
+        req.insert_header("x-user", "abc123").unwrap();
+        assert_eq!(pool.request_hash(&req, None), with_cookie);
+    }
+
+    #[test]
+    fn pool_without_hash_strategy_ignores_request() {
+        let pool = UpstreamPool::from_destination(&destination(vec![DestinationUpstream {
+            url: "http://a.internal".into(),
+            weight: None,
+        }]));
+        let req = RequestHeader::build(Method::GET, b"/", None).unwrap();
+
+        assert!(pool.hash_key().is_none());
+        assert_eq!(
+            pool.request_hash(&req, Some("127.0.0.1".parse().unwrap())),
+            None
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use cardinal_config::DestinationCircuitBreaker;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    half_open_in_flight: u32,
+}
+
+/// Per-destination circuit breaker fed by request outcomes.
+///
+/// Closed until `failure_threshold` consecutive failures, then open for `open_duration`.
+/// Once that elapses, a limited number of trial requests decide whether it closes again.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    half_open_max_requests: u32,
+    failure_status_codes: Vec<u16>,
+    fallback_destination: Option<String>,
+    inner: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &DestinationCircuitBreaker) -> Self {
+        Self {
+            failure_threshold: config.failure_threshold.max(1),
+            open_duration: Duration::from_millis(config.open_duration_ms),
+            half_open_max_requests: config.half_open_max_requests.unwrap_or(1).max(1),
+            failure_status_codes: config.failure_status_codes.clone(),
+            fallback_destination: config.fallback_destination.clone(),
+            inner: Mutex::new(BreakerState {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                half_open_in_flight: 0,
+            }),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.inner.lock().state
+    }
+
+    pub fn fallback_destination(&self) -> Option<&str> {
+        self.fallback_destination.as_deref()
+    }
+
+    pub fn is_failure_status(&self, status: u16) -> bool {
+        self.failure_status_codes.contains(&status)
+    }
+
+    /// Takes over the state of `previous`, the breaker of the same destination before a reload.
+    /// Trial requests still in flight keep reporting to `previous`.
+    pub fn carry_state_from(&self, previous: &CircuitBreaker) {
+        let previous = previous.inner.lock();
+        let mut inner = self.inner.lock();
+        inner.state = previous.state;
+        inner.consecutive_failures = previous.consecutive_failures;
+        inner.opened_at = previous.opened_at;
+        inner.half_open_in_flight = 0;
+    }
+
+    /// Admits a request, returning `None` while the breaker is open.
+    pub fn try_acquire(self: &Arc<Self>) -> Option<CircuitPermit> {
+        let mut inner = self.inner.lock();
+
+        if inner.state == CircuitState::Open {
+            let elapsed = inner
+                .opened_at
+                .map(|opened| opened.elapsed() >= self.open_duration)
+                .unwrap_or(true);
+            if !elapsed {
+                return None;
+            }
+            inner.state = CircuitState::HalfOpen;
+            inner.half_open_in_flight = 0;
+        }
+
+        let trial = inner.state == CircuitState::HalfOpen;
+        if trial {
+            if inner.half_open_in_flight >= self.half_open_max_requests {
+                return None;
+            }
+            inner.half_open_in_flight += 1;
+        }
+
+        Some(CircuitPermit {
+            breaker: Arc::clone(self),
+            trial,
+            recorded: false,
+        })
+    }
+
+    fn on_success(&self, trial: bool) {
+        let mut inner = self.inner.lock();
+        match inner.state {
+            CircuitState::Closed => inner.consecutive_failures = 0,
+            CircuitState::HalfOpen if trial => {
+                inner.state = CircuitState::Closed;
+                inner.consecutive_failures = 0;
+                inner.opened_at = None;
+                inner.half_open_in_flight = 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn on_failure(&self, trial: bool) -> bool {
+        let mut inner = self.inner.lock();
+        match inner.state {
+            CircuitState::Closed => {
+                inner.consecutive_failures += 1;
+                if inner.consecutive_failures >= self.failure_threshold {
+                    inner.state = CircuitState::Open;
+                    inner.opened_at = Some(Instant::now());
+                    return true;
+                }
+                false
+            }
+            CircuitState::HalfOpen if trial => {
+                inner.state = CircuitState::Open;
+                inner.opened_at = Some(Instant::now());
+                inner.half_open_in_flight = 0;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn on_abandoned(&self, trial: bool) {
+        let mut inner = self.inner.lock();
+        if trial && inner.state == CircuitState::HalfOpen {
+            inner.half_open_in_flight = inner.half_open_in_flight.saturating_sub(1);
+        }
+    }
+}
+
+/// Admission granted by [`CircuitBreaker::try_acquire`]. The request outcome is reported
+/// through [`CircuitPermit::success`] or [`CircuitPermit::failure`]; dropping it without
+/// reporting releases the slot without affecting the breaker.
+#[derive(Debug)]
+pub struct CircuitPermit {
+    breaker: Arc<CircuitBreaker>,
+    trial: bool,
+    recorded: bool,
+}
+
+impl CircuitPermit {
+    pub fn breaker(&self) -> &Arc<CircuitBreaker> {
+        &self.breaker
+    }
+
+    pub fn success(mut self) {
+        self.recorded = true;
+        self.breaker.on_success(self.trial);
+    }
+
+    /// Records a failure, returning `true` when it tripped the breaker open.
+    pub fn failure(mut self) -> bool {
+        self.recorded = true;
+        self.breaker.on_failure(self.trial)
+    }
+}
+
+impl Drop for CircuitPermit {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.breaker.on_abandoned(self.trial);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(threshold: u32, open_ms: u64) -> Arc<CircuitBreaker> {
+        Arc::new(CircuitBreaker::new(&DestinationCircuitBreaker {
+            failure_threshold: threshold,
+            open_duration_ms: open_ms,
+            half_open_max_requests: None,
+            failure_status_codes: vec![502, 503, 504],
+            fallback_destination: None,
+        }))
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = breaker(2, 60_000);
+
+        assert!(!breaker.try_acquire().unwrap().failure());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.try_acquire().unwrap().failure());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.try_acquire().is_none());
+    }
+
+    #[test]
+    fn carries_open_state_over() {
+        let previous = breaker(1, 60_000);
+        assert!(previous.try_acquire().unwrap().failure());
+
+        let next = breaker(1, 60_000);
+        next.carry_state_from(&previous);
+        assert_eq!(next.state(), CircuitState::Open);
+        assert!(next.try_acquire().is_none());
+    }
+
+    #[test]
+    fn success_resets_failure_count() {
+        let breaker = breaker(2, 60_000);
+
+        breaker.try_acquire().unwrap().failure();
+        breaker.try_acquire().unwrap().success();
+        breaker.try_acquire().unwrap().failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn half_open_admits_single_trial_and_closes_on_success() {
+        let breaker = breaker(1, 10);
+        breaker.try_acquire().unwrap().failure();
+        std::thread::sleep(Duration::from_millis(20));
+
+        let trial = breaker.try_acquire().expect("trial request admitted");
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.try_acquire().is_none());
+
+        trial.success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.try_acquire().is_some());
+    }
+
+    #[test]
+    fn half_open_failure_reopens() {
+        let breaker = breaker(1, 10);
+        breaker.try_acquire().unwrap().failure();
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert!(breaker.try_acquire().unwrap().failure());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.try_acquire().is_none());
+    }
+
+    #[test]
+    fn abandoned_trial_releases_slot() {
+        let breaker = breaker(1, 10);
+        breaker.try_acquire().unwrap().failure();
+        std::thread::sleep(Duration::from_millis(20));
+
+        drop(breaker.try_acquire().unwrap());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.try_acquire().is_some());
+    }
+
+    #[test]
+    fn failure_status_codes_are_configurable() {
+        let breaker = breaker(1, 10);
+        assert!(breaker.is_failure_status(503));
+        assert!(!breaker.is_failure_status(500));
+    }
+}
@@ -1,18 +1,29 @@
 use crate::context::CardinalContext;
+use crate::destinations::balancer::{UpstreamHealth, UpstreamPool};
+use crate::destinations::circuit_breaker::CircuitBreaker;
 use crate::destinations::matcher::DestinationMatcherIndex;
+use crate::destinations::mirror::TrafficMirror;
+use crate::destinations::upstream_tls::UpstreamTls;
 use crate::provider::Provider;
 use crate::router::CardinalRouter;
 use async_trait::async_trait;
 use cardinal_config::{Destination, Middleware, MiddlewareType};
 use cardinal_errors::CardinalError;
+use http::header::CONTENT_TYPE;
 use pingora::http::RequestHeader;
 use std::collections::BTreeMap;
+use std::net::IpAddr;
 use std::sync::Arc;
 
 pub struct DestinationWrapper {
     pub destination: Destination,
     pub router: CardinalRouter,
     pub has_routes: bool,
+    pub upstreams: UpstreamPool,
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// Loaded from `destination.tls` when the destination container is built.
+    pub upstream_tls: Option<Arc<UpstreamTls>>,
+    pub mirror: Option<Arc<TrafficMirror>>,
     inbound_middleware: Vec<Middleware>,
     outbound_middleware: Vec<Middleware>,
 }
@@ -34,6 +45,16 @@ impl DestinationWrapper {
 
         Self {
             has_routes: !destination.routes.is_empty(),
+            upstreams: UpstreamPool::from_destination(&destination),
+            circuit_breaker: destination
+                .circuit_breaker
+                .as_ref()
+                .map(|config| Arc::new(CircuitBreaker::new(config))),
+            upstream_tls: None,
+            mirror: destination
+                .mirror
+                .as_ref()
+                .map(|config| Arc::new(TrafficMirror::new(config))),
             destination,
             router: router.unwrap_or_default(),
             inbound_middleware,
@@ -54,18 +75,69 @@ pub struct DestinationContainer {
     destinations: BTreeMap<String, Arc<DestinationWrapper>>,
     default_destination: Option<Arc<DestinationWrapper>>,
     matcher: DestinationMatcherIndex,
+    all_destinations: Vec<Arc<DestinationWrapper>>,
+    grpc_services: BTreeMap<String, Arc<DestinationWrapper>>,
 }
 
 impl DestinationContainer {
+    /// Every configured destination, including the ones only reachable through the matcher.
+    pub fn destinations(&self) -> &[Arc<DestinationWrapper>] {
+        &self.all_destinations
+    }
+
+    pub fn default_destination(&self) -> Option<&Arc<DestinationWrapper>> {
+        self.default_destination.as_ref()
+    }
+
+    pub fn matcher(&self) -> &DestinationMatcherIndex {
+        &self.matcher
+    }
+
+    pub fn get_destination(&self, name: &str) -> Option<Arc<DestinationWrapper>> {
+        self.all_destinations
+            .iter()
+            .find(|d| d.destination.name == name)
+            .cloned()
+    }
+
+    /// Current health of every upstream endpoint, keyed by destination name.
+    pub fn upstream_health(&self) -> BTreeMap<String, Vec<UpstreamHealth>> {
+        self.all_destinations
+            .iter()
+            .map(|d| (d.destination.name.clone(), d.upstreams.health()))
+            .collect()
+    }
+
+    /// Takes over upstream health and circuit breaker state from `previous`, the container
+    /// replaced by a reload, for destinations and upstreams that still exist.
+    pub fn carry_state_from(&self, previous: &DestinationContainer) {
+        for wrapper in &self.all_destinations {
+            let Some(old) = previous.get_destination(&wrapper.destination.name) else {
+                continue;
+            };
+            wrapper.upstreams.carry_health_from(&old.upstreams);
+            if let (Some(breaker), Some(old)) = (&wrapper.circuit_breaker, &old.circuit_breaker) {
+                breaker.carry_state_from(old);
+            }
+        }
+    }
+
+    /// Destination serving `req`. `client_ip` feeds `ClientIp` sticky traffic splits.
     pub fn get_backend_for_request(
         &self,
         req: &RequestHeader,
         force_parameter: bool,
+        client_ip: Option<IpAddr>,
     ) -> Option<Arc<DestinationWrapper>> {
+        let grpc_hit = grpc_call(req).and_then(|(service, _)| self.grpc_services.get(service));
+        if let Some(backend) = grpc_hit {
+            return Some(backend.clone());
+        }
+
         let matcher_hit = if force_parameter {
             None
         } else {
-            self.matcher.resolve(req)
+            self.matcher.resolve(req, client_ip)
         };
 
         matcher_hit.or_else(|| {
@@ -99,11 +171,22 @@ impl Provider for DestinationContainer {
                 .routes
                 .iter()
                 .fold(CardinalRouter::new(), |mut r, route| {
-                    let _ = r.add(route.method.as_str(), route.path.as_str());
+                    let _ = r.add_with_body_limit(
+                        route.method.as_str(),
+                        route.path.as_str(),
+                        route.max_request_body_bytes,
+                    );
                     r
                 });
 
-            let wrapper = Arc::new(DestinationWrapper::new(destination, Some(router)));
+            let upstream_tls = destination
+                .tls
+                .as_ref()
+                .map(UpstreamTls::from_config)
+                .transpose()?;
+            let mut wrapper = DestinationWrapper::new(destination, Some(router));
+            wrapper.upstream_tls = upstream_tls.map(Arc::new);
+            let wrapper = Arc::new(wrapper);
 
             if wrapper.destination.default {
                 default_destination = Some(wrapper.clone());
@@ -117,16 +200,53 @@ impl Provider for DestinationContainer {
             wrappers.push(wrapper);
         }
 
-        let matcher = DestinationMatcherIndex::new(wrappers.into_iter())?;
+        let matcher = DestinationMatcherIndex::new(wrappers.clone().into_iter())?;
 
         Ok(Self {
             destinations,
             default_destination,
             matcher,
+            grpc_services: index_grpc_services(&wrappers),
+            all_destinations: wrappers,
         })
     }
 }
 
+/// Service and method of a gRPC call, taken from its `/package.Service/Method` path. gRPC-Web
+/// content types share the `application/grpc` prefix and count as well.
+pub fn grpc_call(req: &RequestHeader) -> Option<(&str, &str)> {
+    const GRPC: &str = "application/grpc";
+
+    let is_grpc = req
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.get(..GRPC.len()))
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(GRPC));
+    if !is_grpc {
+        return None;
+    }
+    let (service, method) = req.uri.path().strip_prefix('/')?.split_once('/')?;
+    (!service.is_empty() && !method.is_empty() && !method.contains('/'))
+        .then_some((service, method))
+}
+
+fn index_grpc_services(
+    wrappers: &[Arc<DestinationWrapper>],
+) -> BTreeMap<String, Arc<DestinationWrapper>> {
+    wrappers
+        .iter()
+        .flat_map(|wrapper| {
+            let services = wrapper
+                .destination
+                .grpc
+                .iter()
+                .flat_map(|grpc| &grpc.services);
+            services.map(|service| (service.clone(), Arc::clone(wrapper)))
+        })
+        .collect()
+}
+
 fn first_path_segment(req: &RequestHeader) -> Option<String> {
     let path = req.uri.path();
     path.strip_prefix('/')
@@ -162,7 +282,7 @@ fn extract_subdomain(req: &RequestHeader) -> Option<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cardinal_config::{Destination, DestinationMatch, DestinationMatchValue};
+    use cardinal_config::{Destination, DestinationGrpc, DestinationMatch, DestinationMatchValue};
     use http::{Method, Uri};
     use std::collections::BTreeMap;
     use std::sync::Arc;
@@ -249,17 +369,35 @@ mod tests {
         Destination {
             name: name.to_string(),
             url: format!("https://{name}.internal"),
+            upstreams: Vec::new(),
+            load_balancer: None,
             health_check: None,
             default,
             r#match: Some(vec![DestinationMatch {
                 host,
                 path_prefix,
                 path_exact: path_exact.map(|s| s.to_string()),
+                methods: vec![],
+                headers: vec![],
+                query: vec![],
+                cookies: vec![],
+                split: None,
             }]),
             routes: Vec::new(),
             middleware: Vec::new(),
             timeout: None,
             retry: None,
+            circuit_breaker: None,
+            preserve_host: false,
+            host_header: None,
+            sni: None,
+            tls: None,
+            mirror: None,
+            cache: None,
+            compression: None,
+            limits: None,
+            websocket: None,
+            grpc: None,
         }
     }
 
@@ -285,12 +423,14 @@ mod tests {
             wrappers.push(wrapper);
         }
 
-        let matcher = DestinationMatcherIndex::new(wrappers.into_iter()).unwrap();
+        let matcher = DestinationMatcherIndex::new(wrappers.clone().into_iter()).unwrap();
 
         DestinationContainer {
             destinations,
             default_destination,
             matcher,
+            grpc_services: index_grpc_services(&wrappers),
+            all_destinations: wrappers,
         }
     }
 
@@ -308,7 +448,9 @@ mod tests {
         )]);
 
         let req = req_with_host_header("support.example.com", "/any");
-        let resolved = container.get_backend_for_request(&req, false).unwrap();
+        let resolved = container
+            .get_backend_for_request(&req, false, None)
+            .unwrap();
         assert_eq!(resolved.destination.name, "customer");
     }
 
@@ -328,7 +470,9 @@ mod tests {
         )]);
 
         let req = req_with_host_header("api.eu.example.com", "/billing/pay");
-        let resolved = container.get_backend_for_request(&req, false).unwrap();
+        let resolved = container
+            .get_backend_for_request(&req, false, None)
+            .unwrap();
         assert_eq!(resolved.destination.name, "billing");
     }
 
@@ -346,7 +490,9 @@ mod tests {
         )]);
 
         let req = req_with_host_header("any.example.com", "/helpdesk/ticket");
-        let resolved = container.get_backend_for_request(&req, false).unwrap();
+        let resolved = container
+            .get_backend_for_request(&req, false, None)
+            .unwrap();
         assert_eq!(resolved.destination.name, "helpdesk");
     }
 
@@ -364,7 +510,9 @@ mod tests {
         )]);
 
         let req = req_with_host_header("unknown.example.com", "/unknown");
-        let resolved = container.get_backend_for_request(&req, false).unwrap();
+        let resolved = container
+            .get_backend_for_request(&req, false, None)
+            .unwrap();
         assert_eq!(resolved.destination.name, "primary");
     }
 
@@ -385,6 +533,8 @@ mod tests {
         let default_destination = Destination {
             name: "fallback".into(),
             url: "https://fallback.internal".into(),
+            upstreams: Vec::new(),
+            load_balancer: None,
             health_check: None,
             default: true,
             r#match: None,
@@ -392,6 +542,17 @@ mod tests {
             middleware: Vec::new(),
             timeout: None,
             retry: None,
+            circuit_breaker: None,
+            preserve_host: false,
+            host_header: None,
+            sni: None,
+            tls: None,
+            mirror: None,
+            cache: None,
+            compression: None,
+            limits: None,
+            websocket: None,
+            grpc: None,
         };
 
         entries.push(("fallback", default_destination));
@@ -399,7 +560,9 @@ mod tests {
         let container = build_container(entries);
         let req = req_with_host_header("billing.example.com", "/other");
 
-        let resolved = container.get_backend_for_request(&req, false).unwrap();
+        let resolved = container
+            .get_backend_for_request(&req, false, None)
+            .unwrap();
         assert_eq!(resolved.destination.name, "fallback");
     }
 
@@ -431,6 +594,8 @@ mod tests {
                 Destination {
                     name: "fallback".into(),
                     url: "https://fallback.internal".into(),
+                    upstreams: Vec::new(),
+                    load_balancer: None,
                     health_check: None,
                     default: true,
                     r#match: None,
@@ -438,12 +603,25 @@ mod tests {
                     middleware: Vec::new(),
                     timeout: None,
                     retry: None,
+                    circuit_breaker: None,
+                    preserve_host: false,
+                    host_header: None,
+                    sni: None,
+                    tls: None,
+                    mirror: None,
+                    cache: None,
+                    compression: None,
+                    limits: None,
+                    websocket: None,
+                    grpc: None,
                 },
             ),
         ]);
 
         let req = req_with_host_header("api.example.com", "/support/ticket");
-        let resolved = container.get_backend_for_request(&req, false).unwrap();
+        let resolved = container
+            .get_backend_for_request(&req, false, None)
+            .unwrap();
         assert_eq!(resolved.destination.name, "support");
     }
 
@@ -465,6 +643,8 @@ mod tests {
                 Destination {
                     name: "fallback".into(),
                     url: "https://fallback.internal".into(),
+                    upstreams: Vec::new(),
+                    load_balancer: None,
                     health_check: None,
                     default: true,
                     r#match: None,
@@ -472,12 +652,25 @@ mod tests {
                     middleware: Vec::new(),
                     timeout: None,
                     retry: None,
+                    circuit_breaker: None,
+                    preserve_host: false,
+                    host_header: None,
+                    sni: None,
+                    tls: None,
+                    mirror: None,
+                    cache: None,
+                    compression: None,
+                    limits: None,
+                    websocket: None,
+                    grpc: None,
                 },
             ),
         ]);
 
         let req = req_with_host_header("api.example.com", "/reports");
-        let resolved = container.get_backend_for_request(&req, false).unwrap();
+        let resolved = container
+            .get_backend_for_request(&req, false, None)
+            .unwrap();
         assert_eq!(resolved.destination.name, "fallback");
     }
 
@@ -513,6 +706,8 @@ mod tests {
                 Destination {
                     name: "fallback".into(),
                     url: "https://fallback.internal".into(),
+                    upstreams: Vec::new(),
+                    load_balancer: None,
                     health_check: None,
                     default: true,
                     r#match: None,
@@ -520,12 +715,25 @@ mod tests {
                     middleware: Vec::new(),
                     timeout: None,
                     retry: None,
+                    circuit_breaker: None,
+                    preserve_host: false,
+                    host_header: None,
+                    sni: None,
+                    tls: None,
+                    mirror: None,
+                    cache: None,
+                    compression: None,
+                    limits: None,
+                    websocket: None,
+                    grpc: None,
                 },
             ),
         ]);
 
         let req = req_with_host_header("api.eu.example.com", "/support/chat");
-        let resolved = container.get_backend_for_request(&req, false).unwrap();
+        let resolved = container
+            .get_backend_for_request(&req, false, None)
+            .unwrap();
         assert_eq!(resolved.destination.name, "support");
     }
 
@@ -559,6 +767,8 @@ mod tests {
                 Destination {
                     name: "fallback".into(),
                     url: "https://fallback.internal".into(),
+                    upstreams: Vec::new(),
+                    load_balancer: None,
                     health_check: None,
                     default: true,
                     r#match: None,
@@ -566,25 +776,36 @@ mod tests {
                     middleware: Vec::new(),
                     timeout: None,
                     retry: None,
+                    circuit_breaker: None,
+                    preserve_host: false,
+                    host_header: None,
+                    sni: None,
+                    tls: None,
+                    mirror: None,
+                    cache: None,
+                    compression: None,
+                    limits: None,
+                    websocket: None,
+                    grpc: None,
                 },
             ),
         ]);
 
         let req_reports = req_with_host_header("any.example.com", "/reports/daily/summary");
         let resolved_reports = container
-            .get_backend_for_request(&req_reports, false)
+            .get_backend_for_request(&req_reports, false, None)
             .unwrap();
         assert_eq!(resolved_reports.destination.name, "reports");
 
         let req_billing = req_with_host_header("any.example.com", "/billing/invoice");
         let resolved_billing = container
-            .get_backend_for_request(&req_billing, false)
+            .get_backend_for_request(&req_billing, false, None)
             .unwrap();
         assert_eq!(resolved_billing.destination.name, "billing");
 
         let req_fallback = req_with_host_header("any.example.com", "/unknown");
         let resolved_fallback = container
-            .get_backend_for_request(&req_fallback, false)
+            .get_backend_for_request(&req_fallback, false, None)
             .unwrap();
         assert_eq!(resolved_fallback.destination.name, "fallback");
     }
@@ -607,6 +828,8 @@ mod tests {
                 Destination {
                     name: "fallback".into(),
                     url: "https://fallback.internal".into(),
+                    upstreams: Vec::new(),
+                    load_balancer: None,
                     health_check: None,
                     default: true,
                     r#match: None,
@@ -614,12 +837,23 @@ mod tests {
                     middleware: Vec::new(),
                     timeout: None,
                     retry: None,
+                    circuit_breaker: None,
+                    preserve_host: false,
+                    host_header: None,
+                    sni: None,
+                    tls: None,
+                    mirror: None,
+                    cache: None,
+                    compression: None,
+                    limits: None,
+                    websocket: None,
+                    grpc: None,
                 },
             ),
         ]);
 
         let req = req_with_path("/matched/orders");
-        let resolved = container.get_backend_for_request(&req, true).unwrap();
+        let resolved = container.get_backend_for_request(&req, true, None).unwrap();
         assert_eq!(resolved.destination.name, "fallback");
     }
 
@@ -649,7 +883,9 @@ mod tests {
         ]);
 
         let req = req_with_host_header("status.example.com", "/status");
-        let resolved = container.get_backend_for_request(&req, false).unwrap();
+        let resolved = container
+            .get_backend_for_request(&req, false, None)
+            .unwrap();
         assert_eq!(resolved.destination.name, "status_exact");
     }
 
@@ -685,6 +921,8 @@ mod tests {
                 Destination {
                     name: "fallback".into(),
                     url: "https://fallback.internal".into(),
+                    upstreams: Vec::new(),
+                    load_balancer: None,
                     health_check: None,
                     default: true,
                     r#match: None,
@@ -692,16 +930,31 @@ mod tests {
                     middleware: Vec::new(),
                     timeout: None,
                     retry: None,
+                    circuit_breaker: None,
+                    preserve_host: false,
+                    host_header: None,
+                    sni: None,
+                    tls: None,
+                    mirror: None,
+                    cache: None,
+                    compression: None,
+                    limits: None,
+                    websocket: None,
+                    grpc: None,
                 },
             ),
         ]);
 
         let req_v2 = req_with_host_header("api.eu.example.com", "/v2/items");
-        let resolved_v2 = container.get_backend_for_request(&req_v2, false).unwrap();
+        let resolved_v2 = container
+            .get_backend_for_request(&req_v2, false, None)
+            .unwrap();
         assert_eq!(resolved_v2.destination.name, "v2");
 
         let req_none = req_with_host_header("api.eu.example.com", "/v3/unknown");
-        let resolved_none = container.get_backend_for_request(&req_none, false).unwrap();
+        let resolved_none = container
+            .get_backend_for_request(&req_none, false, None)
+            .unwrap();
         assert_eq!(resolved_none.destination.name, "fallback");
     }
 
@@ -733,7 +986,9 @@ mod tests {
         ]);
 
         let req = req_with_host_header("any.example.com", "/reports/daily");
-        let resolved = container.get_backend_for_request(&req, false).unwrap();
+        let resolved = container
+            .get_backend_for_request(&req, false, None)
+            .unwrap();
         assert_eq!(resolved.destination.name, "reports_regex");
     }
 
@@ -744,6 +999,8 @@ mod tests {
             Destination {
                 name: "fallback".into(),
                 url: "https://fallback.internal".into(),
+                upstreams: Vec::new(),
+                load_balancer: None,
                 health_check: None,
                 default: true,
                 r#match: None,
@@ -751,11 +1008,22 @@ mod tests {
                 middleware: Vec::new(),
                 timeout: None,
                 retry: None,
+                circuit_breaker: None,
+                preserve_host: false,
+                host_header: None,
+                sni: None,
+                tls: None,
+                mirror: None,
+                cache: None,
+                compression: None,
+                limits: None,
+                websocket: None,
+                grpc: None,
             },
         )]);
 
         let req = req_with_path("/unknown/path");
-        let resolved = container.get_backend_for_request(&req, true).unwrap();
+        let resolved = container.get_backend_for_request(&req, true, None).unwrap();
         assert_eq!(resolved.destination.name, "fallback");
     }
 
@@ -773,7 +1041,7 @@ mod tests {
         )]);
 
         let req = req_with_host_header("api.example.com", "/unknown");
-        let resolved = container.get_backend_for_request(&req, false);
+        let resolved = container.get_backend_for_request(&req, false, None);
         assert!(resolved.is_none());
     }
 
@@ -782,6 +1050,8 @@ mod tests {
         let destination = Destination {
             name: "shared".into(),
             url: "https://shared.internal".into(),
+            upstreams: Vec::new(),
+            load_balancer: None,
             health_check: None,
             default: false,
             r#match: Some(vec![
@@ -789,6 +1059,11 @@ mod tests {
                     host: Some(DestinationMatchValue::String("api.example.com".into())),
                     path_prefix: Some(DestinationMatchValue::String("/billing".into())),
                     path_exact: None,
+                    methods: vec![],
+                    headers: vec![],
+                    query: vec![],
+                    cookies: vec![],
+                    split: None,
                 },
                 DestinationMatch {
                     host: Some(DestinationMatchValue::Regex {
@@ -796,27 +1071,43 @@ mod tests {
                     }),
                     path_prefix: Some(DestinationMatchValue::String("/regex".into())),
                     path_exact: None,
+                    methods: vec![],
+                    headers: vec![],
+                    query: vec![],
+                    cookies: vec![],
+                    split: None,
                 },
             ]),
             routes: Vec::new(),
             middleware: Vec::new(),
             timeout: None,
             retry: None,
+            circuit_breaker: None,
+            preserve_host: false,
+            host_header: None,
+            sni: None,
+            tls: None,
+            mirror: None,
+            cache: None,
+            compression: None,
+            limits: None,
+            websocket: None,
+            grpc: None,
         };
 
         let container = build_container(vec![("shared", destination)]);
 
-        assert!(container.destinations.get("shared").is_none());
+        assert!(!container.destinations.contains_key("shared"));
 
         let exact_req = req_with_host_header("api.example.com", "/billing/invoices");
         let exact_resolved = container
-            .get_backend_for_request(&exact_req, false)
+            .get_backend_for_request(&exact_req, false, None)
             .unwrap();
         assert_eq!(exact_resolved.destination.name, "shared");
 
         let regex_req = req_with_host_header("api.example.com", "/regex/search");
         let regex_resolved = container
-            .get_backend_for_request(&regex_req, false)
+            .get_backend_for_request(&regex_req, false, None)
             .unwrap();
         assert_eq!(regex_resolved.destination.name, "shared");
     }
@@ -826,6 +1117,8 @@ mod tests {
         let destination = Destination {
             name: "segment".into(),
             url: "https://segment.internal".into(),
+            upstreams: Vec::new(),
+            load_balancer: None,
             health_check: None,
             default: false,
             r#match: None,
@@ -833,12 +1126,23 @@ mod tests {
             middleware: Vec::new(),
             timeout: None,
             retry: None,
+            circuit_breaker: None,
+            preserve_host: false,
+            host_header: None,
+            sni: None,
+            tls: None,
+            mirror: None,
+            cache: None,
+            compression: None,
+            limits: None,
+            websocket: None,
+            grpc: None,
         };
 
         let container = build_container(vec![("segment", destination)]);
         let req = req_with_path("/segment/orders");
 
-        let resolved = container.get_backend_for_request(&req, true).unwrap();
+        let resolved = container.get_backend_for_request(&req, true, None).unwrap();
         assert_eq!(resolved.destination.name, "segment");
     }
 
@@ -847,6 +1151,8 @@ mod tests {
         let destination = Destination {
             name: "api".into(),
             url: "https://api.internal".into(),
+            upstreams: Vec::new(),
+            load_balancer: None,
             health_check: None,
             default: false,
             r#match: None,
@@ -854,12 +1160,63 @@ mod tests {
             middleware: Vec::new(),
             timeout: None,
             retry: None,
+            circuit_breaker: None,
+            preserve_host: false,
+            host_header: None,
+            sni: None,
+            tls: None,
+            mirror: None,
+            cache: None,
+            compression: None,
+            limits: None,
+            websocket: None,
+            grpc: None,
         };
 
         let container = build_container(vec![("api", destination)]);
         let req = req_with_host_header("api.mygateway.com", "/any");
 
-        let resolved = container.get_backend_for_request(&req, false).unwrap();
+        let resolved = container
+            .get_backend_for_request(&req, false, None)
+            .unwrap();
         assert_eq!(resolved.destination.name, "api");
     }
+
+    #[test]
+    fn routes_grpc_calls_by_service() {
+        let mut greeter = destination_config("greeter", None, None, Some("/never"), false);
+        greeter.grpc = Some(DestinationGrpc {
+            services: vec!["helloworld.Greeter".into()],
+        });
+        let fallback = destination_config("fallback", None, None, Some("/never"), true);
+        let container = build_container(vec![("greeter", greeter), ("fallback", fallback)]);
+        let call = |content_type: &str, path: &str| {
+            let mut req = RequestHeader::build(Method::POST, path.as_bytes(), None).unwrap();
+            req.insert_header("Content-Type", content_type).unwrap();
+            container
+                .get_backend_for_request(&req, true, None)
+                .map(|backend| backend.destination.name.clone())
+        };
+
+        assert_eq!(
+            call("application/grpc", "/helloworld.Greeter/SayHello").as_deref(),
+            Some("greeter")
+        );
+        assert_eq!(
+            call(
+                "application/grpc-web-text+proto",
+                "/helloworld.Greeter/SayHello"
+            )
+            .as_deref(),
+            Some("greeter")
+        );
+        assert_eq!(
+            call("application/json", "/helloworld.Greeter/SayHello").as_deref(),
+            Some("fallback")
+        );
+        assert_eq!(
+            call("application/grpc", "/helloworld.Other/SayHello").as_deref(),
+            Some("fallback")
+        );
+    }
 }
@@ -1,15 +1,40 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
-use cardinal_config::{DestinationMatch, DestinationMatchValue};
+use cardinal_config::{
+    DestinationMatch, DestinationMatchField, DestinationMatchValue, DestinationSplit,
+    LoadBalancerHashKey,
+};
 use cardinal_errors::CardinalError;
 use pingora::http::RequestHeader;
 use regex::Regex;
 
+use crate::destinations::balancer::{cookie_value, request_hash};
 use crate::destinations::container::DestinationWrapper;
 
+/// Bucket a matcher entry is evaluated in: exact hosts first, then wildcard hosts (most
+/// specific suffix first), then host regexes, then entries without a host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatcherTier {
+    ExactHost,
+    WildcardHost,
+    RegexHost,
+    Hostless,
+}
+
+/// A compiled matcher entry, in evaluation order within its tier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatcherEntry {
+    pub tier: MatcherTier,
+    pub destination: String,
+    pub rule: DestinationMatch,
+}
+
 pub struct DestinationMatcherIndex {
     exact_host: HashMap<String, Vec<CompiledDestination>>,
+    wildcard_host: HostSuffixTrie,
     regex_host: Vec<RegexHostEntry>,
     hostless: Vec<CompiledDestination>,
 }
@@ -19,10 +44,17 @@ impl DestinationMatcherIndex {
         destinations: impl Iterator<Item = Arc<DestinationWrapper>>,
     ) -> Result<Self, CardinalError> {
         let mut exact_host: HashMap<String, Vec<CompiledDestination>> = HashMap::new();
+        let mut wildcard_host = HostSuffixTrie::default();
         let mut regex_host: Vec<RegexHostEntry> = Vec::new();
         let mut hostless: Vec<CompiledDestination> = Vec::new();
 
-        for wrapper in destinations {
+        let destinations = destinations.collect::<Vec<_>>();
+        let by_name = destinations
+            .iter()
+            .map(|d| (d.destination.name.as_str(), d.clone()))
+            .collect::<HashMap<_, _>>();
+
+        for wrapper in &destinations {
             let Some(matchers) = wrapper.destination.r#match.as_ref() else {
                 continue;
             };
@@ -32,7 +64,7 @@ impl DestinationMatcherIndex {
             }
 
             for matcher in matchers {
-                let compiled = CompiledEntry::try_from(wrapper.clone(), matcher)?;
+                let compiled = CompiledEntry::try_from(wrapper.clone(), matcher, &by_name)?;
                 match compiled.host_matcher {
                     Some(CompiledHostMatcher::Exact(host)) => {
                         exact_host
@@ -40,6 +72,9 @@ impl DestinationMatcherIndex {
                             .or_default()
                             .push(compiled.destination);
                     }
+                    Some(CompiledHostMatcher::Wildcard(suffix)) => {
+                        wildcard_host.insert(&suffix, compiled.destination);
+                    }
                     Some(CompiledHostMatcher::Regex(regex)) => {
                         regex_host.push(RegexHostEntry {
                             matcher: regex,
@@ -53,12 +88,42 @@ impl DestinationMatcherIndex {
 
         Ok(Self {
             exact_host,
+            wildcard_host,
             regex_host,
             hostless,
         })
     }
 
-    pub fn resolve(&self, req: &RequestHeader) -> Option<Arc<DestinationWrapper>> {
+    /// Lists the index entries for introspection, exact hosts sorted by host name and wildcard
+    /// hosts by their reversed labels.
+    pub fn entries(&self) -> Vec<MatcherEntry> {
+        let mut hosts = self.exact_host.keys().collect::<Vec<_>>();
+        hosts.sort();
+
+        let exact = hosts
+            .into_iter()
+            .flat_map(|host| &self.exact_host[host])
+            .map(|d| d.entry(MatcherTier::ExactHost));
+        let mut wildcard = Vec::new();
+        self.wildcard_host.collect(&mut wildcard);
+        let wildcard = wildcard
+            .into_iter()
+            .map(|d| d.entry(MatcherTier::WildcardHost));
+        let regex = self
+            .regex_host
+            .iter()
+            .map(|e| e.destination.entry(MatcherTier::RegexHost));
+        let hostless = self.hostless.iter().map(|d| d.entry(MatcherTier::Hostless));
+
+        exact.chain(wildcard).chain(regex).chain(hostless).collect()
+    }
+
+    /// Destination for `req`. `client_ip` feeds `ClientIp` sticky traffic splits.
+    pub fn resolve(
+        &self,
+        req: &RequestHeader,
+        client_ip: Option<IpAddr>,
+    ) -> Option<Arc<DestinationWrapper>> {
         let host = request_host(req);
         let path = req.uri.path();
 
@@ -68,15 +133,24 @@ impl DestinationMatcherIndex {
                 // Walk the candidates and keep the first whose path rules apply.
                 if let Some(wrapper) = entries
                     .iter()
-                    .find_map(|destination| destination.matches(path))
+                    .find_map(|destination| destination.matches(req, path, client_ip))
                 {
                     return Some(wrapper);
                 }
             }
 
+            if let Some(wrapper) = self
+                .wildcard_host
+                .candidates(host)
+                .into_iter()
+                .find_map(|destination| destination.matches(req, path, client_ip))
+            {
+                return Some(wrapper);
+            }
+
             for entry in &self.regex_host {
                 if entry.matcher.is_match(host) {
-                    if let Some(wrapper) = entry.destination.matches(path) {
+                    if let Some(wrapper) = entry.destination.matches(req, path, client_ip) {
                         return Some(wrapper);
                     }
                 }
@@ -84,7 +158,7 @@ impl DestinationMatcherIndex {
         }
 
         for destination in &self.hostless {
-            if let Some(wrapper) = destination.matches(path) {
+            if let Some(wrapper) = destination.matches(req, path, client_ip) {
                 return Some(wrapper);
             }
         }
@@ -93,6 +167,56 @@ impl DestinationMatcherIndex {
     }
 }
 
+/// Wildcard hosts keyed by their labels in reverse order, so `*.eu.tenant.com` lives under
+/// `com` → `tenant` → `eu`. A lookup walks the request host once, whatever the number of
+/// wildcards.
+#[derive(Default)]
+struct HostSuffixTrie {
+    children: HashMap<String, HostSuffixTrie>,
+    destinations: Vec<CompiledDestination>,
+}
+
+impl HostSuffixTrie {
+    fn insert(&mut self, suffix: &str, destination: CompiledDestination) {
+        let node = suffix.rsplit('.').fold(self, |node, label| {
+            node.children.entry(label.to_string()).or_default()
+        });
+        node.destinations.push(destination);
+    }
+
+    /// Destinations whose wildcard covers `host`, most specific suffix first. A wildcard
+    /// needs at least one extra label: `*.tenant.com` does not match `tenant.com`.
+    fn candidates(&self, host: &str) -> Vec<&CompiledDestination> {
+        let mut matched = Vec::new();
+        let mut node = self;
+        let mut labels = host.rsplit('.').peekable();
+        while let Some(label) = labels.next() {
+            let Some(child) = node.children.get(label) else {
+                break;
+            };
+            node = child;
+            if labels.peek().is_some() && !node.destinations.is_empty() {
+                matched.push(node);
+            }
+        }
+
+        matched
+            .into_iter()
+            .rev()
+            .flat_map(|node| &node.destinations)
+            .collect()
+    }
+
+    fn collect<'a>(&'a self, out: &mut Vec<&'a CompiledDestination>) {
+        out.extend(&self.destinations);
+        let mut labels = self.children.keys().collect::<Vec<_>>();
+        labels.sort();
+        for label in labels {
+            self.children[label].collect(out);
+        }
+    }
+}
+
 struct RegexHostEntry {
     matcher: Regex,
     destination: CompiledDestination,
@@ -107,15 +231,25 @@ impl CompiledEntry {
     fn try_from(
         wrapper: Arc<DestinationWrapper>,
         matcher: &DestinationMatch,
+        destinations: &HashMap<&str, Arc<DestinationWrapper>>,
     ) -> Result<Self, CardinalError> {
         let host_matcher = compile_host_matcher(matcher.host.as_ref())?;
         let path_prefix = compile_path_prefix(matcher.path_prefix.as_ref())?;
         let path_exact = matcher.path_exact.clone();
+        let predicates = RequestPredicates::compile(matcher)?;
+        let split = matcher
+            .split
+            .as_ref()
+            .map(|split| TrafficSplit::compile(split, destinations))
+            .transpose()?;
 
         let destination = CompiledDestination {
             wrapper,
             path_prefix,
             path_exact,
+            predicates,
+            split,
+            rule: matcher.clone(),
         };
 
         Ok(Self {
@@ -127,6 +261,8 @@ impl CompiledEntry {
 
 enum CompiledHostMatcher {
     Exact(String),
+    /// Suffix after `*.`, lowercased.
+    Wildcard(String),
     Regex(Regex),
 }
 
@@ -134,14 +270,33 @@ struct CompiledDestination {
     wrapper: Arc<DestinationWrapper>,
     path_prefix: Option<CompiledPathMatcher>,
     path_exact: Option<String>,
+    predicates: RequestPredicates,
+    split: Option<TrafficSplit>,
+    rule: DestinationMatch,
 }
 
 impl CompiledDestination {
-    fn matches(&self, path: &str) -> Option<Arc<DestinationWrapper>> {
-        if self.matches_path(path) {
-            Some(self.wrapper.clone())
-        } else {
-            None
+    fn entry(&self, tier: MatcherTier) -> MatcherEntry {
+        MatcherEntry {
+            tier,
+            destination: self.wrapper.destination.name.clone(),
+            rule: self.rule.clone(),
+        }
+    }
+
+    fn matches(
+        &self,
+        req: &RequestHeader,
+        path: &str,
+        client_ip: Option<IpAddr>,
+    ) -> Option<Arc<DestinationWrapper>> {
+        if !self.matches_path(path) || !self.predicates.matches(req) {
+            return None;
+        }
+
+        match &self.split {
+            Some(split) => Some(split.select(req, client_ip)),
+            None => Some(self.wrapper.clone()),
         }
     }
 
@@ -174,12 +329,207 @@ impl CompiledPathMatcher {
     }
 }
 
+/// Weighted choice between destinations, e.g. a stable release and its canary.
+struct TrafficSplit {
+    targets: Vec<(Arc<DestinationWrapper>, u64)>,
+    total_weight: u64,
+    sticky: Option<LoadBalancerHashKey>,
+    counter: AtomicUsize,
+}
+
+impl TrafficSplit {
+    fn compile(
+        split: &DestinationSplit,
+        destinations: &HashMap<&str, Arc<DestinationWrapper>>,
+    ) -> Result<Self, CardinalError> {
+        let targets = split
+            .targets
+            .iter()
+            .filter(|target| target.weight > 0)
+            .map(|target| {
+                destinations
+                    .get(target.destination.as_str())
+                    .map(|wrapper| (wrapper.clone(), target.weight as u64))
+                    .ok_or_else(|| {
+                        CardinalError::Other(format!(
+                            "unknown split target '{}'",
+                            target.destination
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let total_weight = targets.iter().map(|(_, weight)| weight).sum();
+        if total_weight == 0 {
+            return Err(CardinalError::Other(
+                "traffic split needs a target with a positive weight".into(),
+            ));
+        }
+
+        Ok(Self {
+            targets,
+            total_weight,
+            sticky: split.sticky.clone(),
+            counter: AtomicUsize::new(0),
+        })
+    }
+
+    /// Sticky splits map the request hash onto the weights, so a client keeps its target as
+    /// long as the weights do not change. Requests without the sticky attribute, and
+    /// non-sticky splits, go weighted round-robin.
+    fn select(&self, req: &RequestHeader, client_ip: Option<IpAddr>) -> Arc<DestinationWrapper> {
+        let hash = self
+            .sticky
+            .as_ref()
+            .and_then(|key| request_hash(key, req, client_ip));
+        let mut slot = match hash {
+            Some(hash) => hash % self.total_weight,
+            None => self.counter.fetch_add(1, Ordering::Relaxed) as u64 % self.total_weight,
+        };
+
+        for (wrapper, weight) in &self.targets {
+            if slot < *weight {
+                return wrapper.clone();
+            }
+            slot -= weight;
+        }
+        self.targets[0].0.clone()
+    }
+}
+
+/// Method, header, query and cookie conditions of a match entry, all of which must hold.
+struct RequestPredicates {
+    methods: Vec<String>,
+    headers: Vec<CompiledField>,
+    query: Vec<CompiledField>,
+    cookies: Vec<CompiledField>,
+}
+
+impl RequestPredicates {
+    fn compile(matcher: &DestinationMatch) -> Result<Self, CardinalError> {
+        let fields = |fields: &[DestinationMatchField], lowercase: bool| {
+            fields
+                .iter()
+                .map(|field| CompiledField::compile(field, lowercase))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(Self {
+            methods: matcher
+                .methods
+                .iter()
+                .map(|m| m.to_ascii_uppercase())
+                .collect(),
+            headers: fields(&matcher.headers, true)?,
+            query: fields(&matcher.query, false)?,
+            cookies: fields(&matcher.cookies, false)?,
+        })
+    }
+
+    fn matches(&self, req: &RequestHeader) -> bool {
+        if !self.methods.is_empty() && !self.methods.iter().any(|m| m == req.method.as_str()) {
+            return false;
+        }
+
+        let headers_match = self.headers.iter().all(|field| {
+            field.matches_any(
+                req.headers
+                    .get_all(field.name.as_str())
+                    .iter()
+                    .filter_map(|value| value.to_str().ok()),
+            )
+        });
+        if !headers_match {
+            return false;
+        }
+
+        if !self.query.is_empty() {
+            let query = req
+                .uri
+                .query()
+                .map(|q| form_urlencoded::parse(q.as_bytes()).collect::<Vec<_>>())
+                .unwrap_or_default();
+            let query_matches = self.query.iter().all(|field| {
+                field.matches_any(
+                    query
+                        .iter()
+                        .filter(|(key, _)| key == &field.name)
+                        .map(|(_, value)| value.as_ref()),
+                )
+            });
+            if !query_matches {
+                return false;
+            }
+        }
+
+        self.cookies
+            .iter()
+            .all(|field| field.matches_any(cookie_value(req, &field.name).as_deref()))
+    }
+}
+
+struct CompiledField {
+    name: String,
+    value: Option<CompiledValue>,
+}
+
+enum CompiledValue {
+    Exact(String),
+    Regex(Regex),
+}
+
+impl CompiledField {
+    /// Header names are case-insensitive and compared lowercased; query and cookie names are
+    /// case-sensitive.
+    fn compile(field: &DestinationMatchField, lowercase_name: bool) -> Result<Self, CardinalError> {
+        let value = match &field.value {
+            Some(DestinationMatchValue::String(value)) => Some(CompiledValue::Exact(value.clone())),
+            Some(DestinationMatchValue::Regex { regex }) => {
+                Some(CompiledValue::Regex(Regex::new(regex).map_err(|err| {
+                    CardinalError::Other(format!(
+                        "invalid regex '{regex}' for '{}': {err}",
+                        field.name
+                    ))
+                })?))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            name: if lowercase_name {
+                field.name.to_ascii_lowercase()
+            } else {
+                field.name.clone()
+            },
+            value,
+        })
+    }
+
+    /// True when one of the values is present and, if a value is expected, matches it.
+    fn matches_any<'a>(&self, values: impl IntoIterator<Item = &'a str>) -> bool {
+        values.into_iter().any(|value| match &self.value {
+            None => true,
+            Some(CompiledValue::Exact(expected)) => value == expected,
+            Some(CompiledValue::Regex(regex)) => regex.is_match(value),
+        })
+    }
+}
+
 fn compile_host_matcher(
     value: Option<&DestinationMatchValue>,
 ) -> Result<Option<CompiledHostMatcher>, CardinalError> {
     match value {
         Some(DestinationMatchValue::String(host)) => {
-            Ok(Some(CompiledHostMatcher::Exact(host.to_ascii_lowercase())))
+            let host = host.to_ascii_lowercase();
+            match host.strip_prefix("*.") {
+                Some(suffix) if suffix.is_empty() || suffix.contains('*') => Err(
+                    CardinalError::Other(format!("invalid wildcard host '{host}'")),
+                ),
+                Some(suffix) => Ok(Some(CompiledHostMatcher::Wildcard(suffix.to_string()))),
+                None if host.contains('*') => Err(CardinalError::Other(format!(
+                    "wildcard host '{host}' must start with '*.'"
+                ))),
+                None => Ok(Some(CompiledHostMatcher::Exact(host))),
+            }
         }
         Some(DestinationMatchValue::Regex { regex }) => {
             let compiled = Regex::new(regex).map_err(|err| {
@@ -223,7 +573,7 @@ fn request_host(req: &RequestHeader) -> Option<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cardinal_config::{Destination, DestinationMatch};
+    use cardinal_config::{Destination, DestinationMatch, DestinationSplitTarget};
     use http::Method;
     use pingora::http::RequestHeader;
 
@@ -239,6 +589,11 @@ mod tests {
                 host,
                 path_prefix,
                 path_exact: path_exact.map(|s| s.to_string()),
+                methods: vec![],
+                headers: vec![],
+                query: vec![],
+                cookies: vec![],
+                split: None,
             }]),
         )
     }
@@ -250,6 +605,8 @@ mod tests {
         let destination = Destination {
             name: name.to_string(),
             url: "https://example.com".to_string(),
+            upstreams: Vec::new(),
+            load_balancer: None,
             health_check: None,
             default: false,
             r#match: matchers,
@@ -257,6 +614,17 @@ mod tests {
             middleware: Vec::new(),
             timeout: None,
             retry: None,
+            circuit_breaker: None,
+            preserve_host: false,
+            host_header: None,
+            sni: None,
+            tls: None,
+            mirror: None,
+            cache: None,
+            compression: None,
+            limits: None,
+            websocket: None,
+            grpc: None,
         };
 
         Arc::new(DestinationWrapper::new(destination, None))
@@ -280,7 +648,7 @@ mod tests {
         let matcher = DestinationMatcherIndex::new(vec![destination.clone()].into_iter()).unwrap();
         let req = build_request("API.EXAMPLE.com", "/v1/customers");
 
-        let resolved = matcher.resolve(&req).unwrap();
+        let resolved = matcher.resolve(&req, None).unwrap();
         assert_eq!(resolved.destination.name, "customer_service");
     }
 
@@ -298,10 +666,348 @@ mod tests {
         let matcher = DestinationMatcherIndex::new(vec![destination.clone()].into_iter()).unwrap();
         let req = build_request("api.eu.example.com", "/billing");
 
-        let resolved = matcher.resolve(&req).unwrap();
+        let resolved = matcher.resolve(&req, None).unwrap();
         assert_eq!(resolved.destination.name, "billing");
     }
 
+    #[test]
+    fn matches_wildcard_host_most_specific_first() {
+        let tenants = build_destination(
+            "tenants",
+            Some(DestinationMatchValue::String("*.Tenant.com".into())),
+            None,
+            None,
+        );
+        let eu_billing = build_destination(
+            "eu_billing",
+            Some(DestinationMatchValue::String("*.eu.tenant.com".into())),
+            Some(DestinationMatchValue::String("/billing".into())),
+            None,
+        );
+        let exact = build_destination(
+            "exact",
+            Some(DestinationMatchValue::String("acme.eu.tenant.com".into())),
+            Some(DestinationMatchValue::String("/billing".into())),
+            None,
+        );
+
+        let matcher =
+            DestinationMatcherIndex::new(vec![tenants, eu_billing, exact].into_iter()).unwrap();
+        let resolve = |host: &str, path: &str| {
+            matcher
+                .resolve(&build_request(host, path), None)
+                .map(|d| d.destination.name.clone())
+        };
+
+        assert_eq!(
+            resolve("acme.eu.tenant.com", "/billing").as_deref(),
+            Some("exact")
+        );
+        assert_eq!(
+            resolve("other.eu.tenant.com:8443", "/billing").as_deref(),
+            Some("eu_billing")
+        );
+        // Falls back to the broader wildcard when the specific one rejects the path.
+        assert_eq!(
+            resolve("other.eu.tenant.com", "/support").as_deref(),
+            Some("tenants")
+        );
+        assert_eq!(
+            resolve("acme.tenant.com", "/billing").as_deref(),
+            Some("tenants")
+        );
+        assert_eq!(resolve("tenant.com", "/"), None);
+        assert_eq!(resolve("eviltenant.com", "/"), None);
+    }
+
+    #[test]
+    fn rejects_misplaced_wildcards() {
+        for host in ["api.*.tenant.com", "*.", "*.*.tenant.com"] {
+            let destination = build_destination(
+                "broken",
+                Some(DestinationMatchValue::String(host.into())),
+                None,
+                None,
+            );
+            assert!(DestinationMatcherIndex::new(vec![destination].into_iter()).is_err());
+        }
+    }
+
+    #[test]
+    fn entries_list_wildcards_between_exact_and_regex() {
+        let regex = build_destination(
+            "regex",
+            Some(DestinationMatchValue::Regex {
+                regex: "^.*\\.example\\.com$".into(),
+            }),
+            None,
+            None,
+        );
+        let wildcard = build_destination(
+            "wildcard",
+            Some(DestinationMatchValue::String("*.example.com".into())),
+            None,
+            None,
+        );
+        let exact = build_destination(
+            "exact",
+            Some(DestinationMatchValue::String("api.example.com".into())),
+            None,
+            None,
+        );
+
+        let matcher =
+            DestinationMatcherIndex::new(vec![regex, wildcard, exact].into_iter()).unwrap();
+        let tiers = matcher
+            .entries()
+            .into_iter()
+            .map(|e| e.tier)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tiers,
+            vec![
+                MatcherTier::ExactHost,
+                MatcherTier::WildcardHost,
+                MatcherTier::RegexHost
+            ]
+        );
+    }
+
+    fn field(name: &str, value: Option<DestinationMatchValue>) -> DestinationMatchField {
+        DestinationMatchField {
+            name: name.into(),
+            value,
+        }
+    }
+
+    #[test]
+    fn request_predicates_compose_with_host_rules() {
+        let host = || Some(DestinationMatchValue::String("api.example.com".into()));
+        let v2 = build_destination_with_matchers(
+            "v2",
+            Some(vec![DestinationMatch {
+                host: host(),
+                path_prefix: None,
+                path_exact: None,
+                methods: vec!["get".into(), "POST".into()],
+                headers: vec![field(
+                    "X-Api-Version",
+                    Some(DestinationMatchValue::String("2".into())),
+                )],
+                query: vec![],
+                cookies: vec![],
+                split: None,
+            }]),
+        );
+        let canary = build_destination_with_matchers(
+            "canary",
+            Some(vec![DestinationMatch {
+                host: host(),
+                path_prefix: None,
+                path_exact: None,
+                methods: vec![],
+                headers: vec![],
+                query: vec![field("debug", None)],
+                cookies: vec![field(
+                    "beta",
+                    Some(DestinationMatchValue::Regex {
+                        regex: "^(1|true)$".into(),
+                    }),
+                )],
+                split: None,
+            }]),
+        );
+        let stable = build_destination("stable", host(), None, None);
+
+        let matcher = DestinationMatcherIndex::new(vec![v2, canary, stable].into_iter()).unwrap();
+        let resolve = |req: RequestHeader| {
+            matcher
+                .resolve(&req, None)
+                .unwrap()
+                .destination
+                .name
+                .clone()
+        };
+
+        let mut req = build_request("api.example.com", "/orders");
+        req.insert_header("x-api-version", "2").unwrap();
+        assert_eq!(resolve(req), "v2");
+
+        let mut req = build_request("api.example.com", "/orders");
+        req.set_method(Method::DELETE);
+        req.insert_header("x-api-version", "2").unwrap();
+        assert_eq!(resolve(req), "stable");
+
+        let mut req = build_request("api.example.com", "/orders?debug&page=2");
+        req.insert_header("cookie", "theme=dark; beta=true")
+            .unwrap();
+        assert_eq!(resolve(req), "canary");
+
+        let mut req = build_request("api.example.com", "/orders?page=2");
+        req.insert_header("cookie", "beta=true").unwrap();
+        assert_eq!(resolve(req), "stable");
+    }
+
+    #[test]
+    fn rejects_invalid_predicate_regex() {
+        let destination = build_destination_with_matchers(
+            "broken",
+            Some(vec![DestinationMatch {
+                host: None,
+                path_prefix: None,
+                path_exact: None,
+                methods: vec![],
+                headers: vec![field(
+                    "x-tenant",
+                    Some(DestinationMatchValue::Regex { regex: "(".into() }),
+                )],
+                query: vec![],
+                cookies: vec![],
+                split: None,
+            }]),
+        );
+
+        assert!(DestinationMatcherIndex::new(vec![destination].into_iter()).is_err());
+    }
+
+    fn split_matcher(sticky: Option<LoadBalancerHashKey>) -> DestinationMatcherIndex {
+        let target = |destination: &str, weight| DestinationSplitTarget {
+            destination: destination.into(),
+            weight,
+        };
+        let router = build_destination_with_matchers(
+            "router",
+            Some(vec![DestinationMatch {
+                host: Some(DestinationMatchValue::String("api.example.com".into())),
+                path_prefix: None,
+                path_exact: None,
+                methods: vec![],
+                headers: vec![],
+                query: vec![],
+                cookies: vec![],
+                split: Some(DestinationSplit {
+                    targets: vec![target("stable", 95), target("canary", 5)],
+                    sticky,
+                }),
+            }]),
+        );
+        let destinations = vec![
+            router,
+            build_destination_with_matchers("stable", None),
+            build_destination_with_matchers("canary", None),
+        ];
+
+        DestinationMatcherIndex::new(destinations.into_iter()).unwrap()
+    }
+
+    #[test]
+    fn split_spreads_requests_by_weight() {
+        let matcher = split_matcher(None);
+
+        let mut counts = HashMap::new();
+        for _ in 0..200 {
+            let req = build_request("api.example.com", "/orders");
+            let wrapper = matcher.resolve(&req, None).unwrap();
+            *counts.entry(wrapper.destination.name.clone()).or_insert(0) += 1;
+        }
+
+        assert_eq!(counts["stable"], 190);
+        assert_eq!(counts["canary"], 10);
+    }
+
+    #[test]
+    fn sticky_split_keeps_clients_on_their_target() {
+        let matcher = split_matcher(Some(LoadBalancerHashKey::Cookie("session".into())));
+        let resolve = |session: &str| {
+            let mut req = build_request("api.example.com", "/orders");
+            req.insert_header("cookie", format!("session={session}"))
+                .unwrap();
+            matcher
+                .resolve(&req, None)
+                .unwrap()
+                .destination
+                .name
+                .clone()
+        };
+
+        let mut seen = std::collections::HashSet::new();
+        for client in 0..200 {
+            let session = format!("client-{client}");
+            let first = resolve(&session);
+            assert_eq!(resolve(&session), first);
+            assert_eq!(resolve(&session), first);
+            seen.insert(first);
+        }
+        assert_eq!(seen.len(), 2);
+    }
+
+    #[test]
+    fn split_rejects_unknown_targets() {
+        let router = build_destination_with_matchers(
+            "router",
+            Some(vec![DestinationMatch {
+                host: None,
+                path_prefix: None,
+                path_exact: None,
+                methods: vec![],
+                headers: vec![],
+                query: vec![],
+                cookies: vec![],
+                split: Some(DestinationSplit {
+                    targets: vec![DestinationSplitTarget {
+                        destination: "missing".into(),
+                        weight: 1,
+                    }],
+                    sticky: None,
+                }),
+            }]),
+        );
+
+        assert!(DestinationMatcherIndex::new(vec![router].into_iter()).is_err());
+    }
+
+    #[test]
+    fn entries_list_tiers_in_evaluation_order() {
+        let hostless = build_destination(
+            "hostless",
+            None,
+            Some(DestinationMatchValue::String("/static".into())),
+            None,
+        );
+        let regex = build_destination(
+            "regex",
+            Some(DestinationMatchValue::Regex {
+                regex: "^.*\\.example\\.com$".into(),
+            }),
+            None,
+            None,
+        );
+        let exact = build_destination(
+            "exact",
+            Some(DestinationMatchValue::String("api.example.com".into())),
+            None,
+            None,
+        );
+
+        let matcher =
+            DestinationMatcherIndex::new(vec![hostless, regex, exact].into_iter()).unwrap();
+        let entries = matcher
+            .entries()
+            .into_iter()
+            .map(|e| (e.tier, e.destination))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            entries,
+            vec![
+                (MatcherTier::ExactHost, "exact".to_string()),
+                (MatcherTier::RegexHost, "regex".to_string()),
+                (MatcherTier::Hostless, "hostless".to_string()),
+            ]
+        );
+    }
+
     #[test]
     fn supports_multiple_match_entries_per_destination() {
         let destination = build_destination_with_matchers(
@@ -311,11 +1017,21 @@ mod tests {
                     host: Some(DestinationMatchValue::String("api.example.com".into())),
                     path_prefix: Some(DestinationMatchValue::String("/billing".into())),
                     path_exact: None,
+                    methods: vec![],
+                    headers: vec![],
+                    query: vec![],
+                    cookies: vec![],
+                    split: None,
                 },
                 DestinationMatch {
                     host: Some(DestinationMatchValue::String("api.example.com".into())),
                     path_prefix: Some(DestinationMatchValue::String("/support".into())),
                     path_exact: None,
+                    methods: vec![],
+                    headers: vec![],
+                    query: vec![],
+                    cookies: vec![],
+                    split: None,
                 },
             ]),
         );
@@ -323,15 +1039,15 @@ mod tests {
         let matcher = DestinationMatcherIndex::new(vec![destination.clone()].into_iter()).unwrap();
 
         let billing_req = build_request("api.example.com", "/billing/payments");
-        let billing_destination = matcher.resolve(&billing_req).unwrap();
+        let billing_destination = matcher.resolve(&billing_req, None).unwrap();
         assert_eq!(billing_destination.destination.name, "api");
 
         let support_req = build_request("api.example.com", "/support/chat");
-        let support_destination = matcher.resolve(&support_req).unwrap();
+        let support_destination = matcher.resolve(&support_req, None).unwrap();
         assert_eq!(support_destination.destination.name, "api");
 
         let missing_req = build_request("api.example.com", "/reports");
-        assert!(matcher.resolve(&missing_req).is_none());
+        assert!(matcher.resolve(&missing_req, None).is_none());
     }
 
     #[test]
@@ -343,6 +1059,11 @@ mod tests {
                     host: Some(DestinationMatchValue::String("api.example.com".into())),
                     path_prefix: Some(DestinationMatchValue::String("/billing".into())),
                     path_exact: None,
+                    methods: vec![],
+                    headers: vec![],
+                    query: vec![],
+                    cookies: vec![],
+                    split: None,
                 },
                 DestinationMatch {
                     host: Some(DestinationMatchValue::Regex {
@@ -350,6 +1071,11 @@ mod tests {
                     }),
                     path_prefix: Some(DestinationMatchValue::String("/regex".into())),
                     path_exact: None,
+                    methods: vec![],
+                    headers: vec![],
+                    query: vec![],
+                    cookies: vec![],
+                    split: None,
                 },
             ]),
         );
@@ -357,11 +1083,11 @@ mod tests {
         let matcher = DestinationMatcherIndex::new(vec![destination.clone()].into_iter()).unwrap();
 
         let exact_req = build_request("api.example.com", "/billing/invoices");
-        let exact_destination = matcher.resolve(&exact_req).unwrap();
+        let exact_destination = matcher.resolve(&exact_req, None).unwrap();
         assert_eq!(exact_destination.destination.name, "api");
 
         let regex_req = build_request("api.example.com", "/regex/search");
-        let regex_destination = matcher.resolve(&regex_req).unwrap();
+        let regex_destination = matcher.resolve(&regex_req, None).unwrap();
         assert_eq!(regex_destination.destination.name, "api");
     }
 
@@ -377,7 +1103,7 @@ mod tests {
         let matcher = DestinationMatcherIndex::new(vec![hostless.clone()].into_iter()).unwrap();
         let req = build_request("any.example.com", "/helpdesk/ticket");
 
-        let resolved = matcher.resolve(&req).unwrap();
+        let resolved = matcher.resolve(&req, None).unwrap();
         assert_eq!(resolved.destination.name, "helpdesk");
     }
 
@@ -395,7 +1121,7 @@ mod tests {
         let matcher = DestinationMatcherIndex::new(vec![hostless.clone()].into_iter()).unwrap();
         let req = build_request("other.example.com", "/reports/daily/summary");
 
-        let resolved = matcher.resolve(&req).unwrap();
+        let resolved = matcher.resolve(&req, None).unwrap();
         assert_eq!(resolved.destination.name, "reports");
     }
 
@@ -411,10 +1137,10 @@ mod tests {
         let matcher = DestinationMatcherIndex::new(vec![host.clone()].into_iter()).unwrap();
         let req = build_request("status.example.com", "/healthz");
 
-        assert!(matcher.resolve(&req).is_some());
+        assert!(matcher.resolve(&req, None).is_some());
 
         let req_non_matching = build_request("status.example.com", "/healthz/extra");
-        assert!(matcher.resolve(&req_non_matching).is_none());
+        assert!(matcher.resolve(&req_non_matching, None).is_none());
     }
 
     #[test]
@@ -433,7 +1159,7 @@ mod tests {
         .unwrap();
         let req = build_request("api.example.com", "/anything");
 
-        let resolved = matcher.resolve(&req).unwrap();
+        let resolved = matcher.resolve(&req, None).unwrap();
         assert_eq!(resolved.destination.name, "api");
     }
 }
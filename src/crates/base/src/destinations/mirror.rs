@@ -0,0 +1,71 @@
+use cardinal_config::DestinationMirror;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Mirror settings of a destination with the counter deciding which requests are copied.
+#[derive(Debug)]
+pub struct TrafficMirror {
+    destination: String,
+    percentage: u64,
+    max_body_bytes: usize,
+    counter: AtomicU64,
+}
+
+impl TrafficMirror {
+    pub fn new(config: &DestinationMirror) -> Self {
+        Self {
+            destination: config.destination.clone(),
+            percentage: u64::from(config.percentage.min(100)),
+            max_body_bytes: config.max_body_bytes,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    /// Name of the shadow destination.
+    pub fn destination(&self) -> &str {
+        &self.destination
+    }
+
+    pub fn max_body_bytes(&self) -> usize {
+        self.max_body_bytes
+    }
+
+    /// Whether the next request is mirrored. Picks are spread evenly, so any 100 consecutive
+    /// requests hold `percentage` mirrored ones.
+    pub fn sample(&self) -> bool {
+        let n = self.counter.fetch_add(1, Ordering::Relaxed) % 100;
+        (n + 1) * self.percentage / 100 > n * self.percentage / 100
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mirror(percentage: u32) -> TrafficMirror {
+        TrafficMirror::new(&DestinationMirror {
+            destination: "shadow".into(),
+            percentage,
+            max_body_bytes: 1024,
+        })
+    }
+
+    #[test]
+    fn samples_the_configured_share() {
+        for percentage in [0, 1, 10, 33, 50, 100] {
+            let mirror = mirror(percentage);
+            let sampled = (0..1000).filter(|_| mirror.sample()).count();
+            assert_eq!(sampled, percentage as usize * 10, "{percentage}%");
+        }
+    }
+
+    #[test]
+    fn spreads_samples_evenly() {
+        let mirror = mirror(25);
+        let picks = (0..8).map(|_| mirror.sample()).collect::<Vec<_>>();
+
+        assert_eq!(
+            picks,
+            [false, false, false, true, false, false, false, true]
+        );
+    }
+}
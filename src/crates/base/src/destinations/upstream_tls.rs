@@ -0,0 +1,112 @@
+use cardinal_config::{DestinationClientCertificate, DestinationTls};
+use cardinal_errors::internal::CardinalInternalError;
+use cardinal_errors::CardinalError;
+use pingora::tls::pkey::PKey;
+use pingora::tls::x509::X509;
+use pingora::upstreams::peer::{HttpPeer, Peer};
+use pingora::utils::tls::CertKey;
+use std::sync::Arc;
+
+/// Upstream TLS settings of a destination with its CA bundle and client certificate loaded.
+#[derive(Debug)]
+pub struct UpstreamTls {
+    ca: Option<Arc<Box<[X509]>>>,
+    client_cert_key: Option<Arc<CertKey>>,
+    verify_cert: bool,
+    verify_hostname: bool,
+    server_name: Option<String>,
+}
+
+impl UpstreamTls {
+    pub fn from_config(config: &DestinationTls) -> Result<Self, CardinalError> {
+        let ca = config
+            .ca_path
+            .as_deref()
+            .map(|path| load_certificates(path).map(|certs| Arc::new(certs.into_boxed_slice())))
+            .transpose()?;
+        let client_cert_key = config
+            .client_certificate
+            .as_ref()
+            .map(load_cert_key)
+            .transpose()?
+            .map(Arc::new);
+
+        Ok(Self {
+            ca,
+            client_cert_key,
+            verify_cert: !config.insecure_skip_verify,
+            verify_hostname: config.verify_hostname,
+            server_name: config.server_name.clone(),
+        })
+    }
+
+    /// Applies verification settings and the client certificate to a TLS peer.
+    pub fn apply(&self, peer: &mut HttpPeer) {
+        peer.client_cert_key = self.client_cert_key.clone();
+        if let Some(options) = peer.get_mut_peer_options() {
+            options.verify_cert = self.verify_cert;
+            options.verify_hostname = self.verify_hostname;
+            options.alternative_cn = self.server_name.clone();
+            options.ca = self.ca.clone();
+        }
+    }
+}
+
+fn load_certificates(path: &str) -> Result<Vec<X509>, CardinalError> {
+    let pem = std::fs::read(path)?;
+    let certs = X509::stack_from_pem(&pem).map_err(|e| invalid_tls(&format!("{path}: {e}")))?;
+    if certs.is_empty() {
+        return Err(invalid_tls(&format!("{path} contains no certificate")));
+    }
+    Ok(certs)
+}
+
+fn load_cert_key(config: &DestinationClientCertificate) -> Result<CertKey, CardinalError> {
+    let certs = load_certificates(&config.cert_path)?;
+    let key_pem = std::fs::read(&config.key_path)?;
+    let key = PKey::private_key_from_pem(&key_pem)
+        .map_err(|e| invalid_tls(&format!("{}: {e}", config.key_path)))?;
+    Ok(CertKey::new(certs, key))
+}
+
+fn invalid_tls(reason: &str) -> CardinalError {
+    CardinalError::InternalError(CardinalInternalError::InvalidTlsConfiguration(
+        reason.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> DestinationTls {
+        DestinationTls {
+            ca_path: None,
+            insecure_skip_verify: true,
+            verify_hostname: false,
+            server_name: Some("posts.internal".into()),
+            client_certificate: None,
+        }
+    }
+
+    #[test]
+    fn applies_verification_settings_to_peer() {
+        let tls = UpstreamTls::from_config(&config()).unwrap();
+        let mut peer = HttpPeer::new("10.0.0.1:8443", true, "posts.example.com".into());
+        tls.apply(&mut peer);
+
+        let options = peer.get_peer_options().unwrap();
+        assert!(!options.verify_cert);
+        assert!(!options.verify_hostname);
+        assert_eq!(options.alternative_cn.as_deref(), Some("posts.internal"));
+        assert!(peer.client_cert_key.is_none());
+    }
+
+    #[test]
+    fn missing_ca_bundle_is_reported() {
+        let mut config = config();
+        config.ca_path = Some("does-not-exist.pem".into());
+
+        assert!(UpstreamTls::from_config(&config).is_err());
+    }
+}
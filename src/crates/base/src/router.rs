@@ -4,7 +4,8 @@ use matchit::Router;
 use std::collections::HashMap;
 
 pub struct CardinalRouter {
-    router: Router<()>,
+    /// Routes with their request body limit.
+    router: Router<Option<usize>>,
 }
 
 impl Default for CardinalRouter {
@@ -21,12 +22,33 @@ impl CardinalRouter {
     }
 
     pub fn add(&mut self, method: &str, path: &str) -> Result<(), CardinalError> {
+        self.add_with_body_limit(method, path, None)
+    }
+
+    pub fn add_with_body_limit(
+        &mut self,
+        method: &str,
+        path: &str,
+        max_request_body_bytes: Option<usize>,
+    ) -> Result<(), CardinalError> {
         self.router
-            .insert(format!("{}:{}", method.to_lowercase(), path), ())
+            .insert(
+                format!("{}:{}", method.to_lowercase(), path),
+                max_request_body_bytes,
+            )
             .map_err(|e| CardinalInternalError::InvalidRouteConfiguration(e.to_string()))?;
         Ok(())
     }
 
+    /// Request body limit of the route matching `method` and `path`, if it sets one.
+    pub fn max_request_body_bytes(&self, method: &str, path: &str) -> Option<usize> {
+        let actual_path = format!("{}:{}", method.to_lowercase(), path);
+        self.router
+            .at(actual_path.as_str())
+            .ok()
+            .and_then(|matched| *matched.value)
+    }
+
     pub fn valid(&self, method: &str, path: &str) -> Option<(bool, HashMap<String, String>)> {
         let actual_path = format!("{}:{}", method.to_lowercase(), path);
         let route_res = self.router.at(actual_path.as_str());
@@ -105,6 +127,22 @@ mod tests {
         assert!(router.valid("get", "/status").is_some());
     }
 
+    #[test]
+    fn max_request_body_bytes_follows_matched_route() {
+        let mut router = CardinalRouter::new();
+        router
+            .add_with_body_limit("POST", "/uploads/{id}", Some(1024))
+            .unwrap();
+        router.add("POST", "/status").unwrap();
+
+        assert_eq!(
+            router.max_request_body_bytes("POST", "/uploads/7"),
+            Some(1024)
+        );
+        assert_eq!(router.max_request_body_bytes("POST", "/status"), None);
+        assert_eq!(router.max_request_body_bytes("GET", "/uploads/7"), None);
+    }
+
     #[test]
     fn add_duplicate_route_returns_error() {
         let mut router = CardinalRouter::new();
@@ -1,5 +1,3 @@
-use std::io::Read;
-
 use benches::support::http_support::Route;
 use benches::support::http_url;
 use benches::support::{
@@ -23,6 +21,11 @@ fn bench_routes_path_exact_before_prefix(c: &mut Criterion) {
             host: Some(DestinationMatchValue::String("status.example.com".into())),
             path_prefix: None,
             path_exact: Some("/status".into()),
+            methods: vec![],
+            headers: vec![],
+            query: vec![],
+            cookies: vec![],
+            split: None,
         }),
         false,
     );
@@ -34,6 +37,11 @@ fn bench_routes_path_exact_before_prefix(c: &mut Criterion) {
             host: Some(DestinationMatchValue::String("status.example.com".into())),
             path_prefix: Some(DestinationMatchValue::String("/status".into())),
             path_exact: None,
+            methods: vec![],
+            headers: vec![],
+            query: vec![],
+            cookies: vec![],
+            split: None,
         }),
         false,
     );
@@ -115,6 +123,11 @@ fn bench_routes_regex_hosts_and_fallback(c: &mut Criterion) {
         }),
         path_prefix: Some(DestinationMatchValue::String(path.into())),
         path_exact: None,
+        methods: vec![],
+        headers: vec![],
+        query: vec![],
+        cookies: vec![],
+        split: None,
     };
 
     let config = config_with_destinations(
@@ -1,5 +1,4 @@
 use std::collections::HashMap;
-use std::io::Read;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
@@ -64,7 +63,6 @@ fn bench_global_request_middleware_executes_before_backend(c: &mut Criterion) {
         b.iter(|| {
             let mut response = agent.get(&url).call().expect("successful response");
             assert_eq!(response.status(), 200);
-            let mut body = String::new();
             let body = response.body_mut().read_to_string().unwrap();
             assert_eq!(body, "middleware-ok");
         });
@@ -114,7 +112,6 @@ fn bench_wasm_outbound_plugin_adds_response_headers(c: &mut Criterion) {
                 .get("x-wasm-response")
                 .map(|s| s.to_str().unwrap());
             assert_eq!(tag, Some("enabled"));
-            let mut body = String::new();
             let body = response.body_mut().read_to_string().unwrap();
             assert_eq!(body, "outbound-ok");
         });
@@ -65,6 +65,19 @@ pub mod support {
                 log_upstream_response: true,
                 global_request_middleware: vec![],
                 global_response_middleware: vec![],
+                tls: None,
+                proxy_protocol: false,
+                h2c: false,
+                listeners: vec![],
+                reload: None,
+                admin: None,
+                metrics: None,
+                tracing: None,
+                access_log: None,
+                request_id: None,
+                forwarding: None,
+                cache: None,
+                limits: None,
             },
             destinations: map,
             plugins: vec![],
@@ -81,6 +94,8 @@ pub mod support {
         Destination {
             name: name.to_string(),
             url: url.to_string(),
+            upstreams: vec![],
+            load_balancer: None,
             health_check: None,
             default,
             r#match: matcher,
@@ -88,6 +103,17 @@ pub mod support {
             middleware: vec![],
             timeout: None,
             retry: None,
+            circuit_breaker: None,
+            preserve_host: false,
+            host_header: None,
+            sni: None,
+            tls: None,
+            mirror: None,
+            cache: None,
+            compression: None,
+            limits: None,
+            websocket: None,
+            grpc: None,
         }
     }
 
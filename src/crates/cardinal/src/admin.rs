@@ -0,0 +1,382 @@
+use crate::reload::ConfigReloader;
+use async_trait::async_trait;
+use cardinal_base::context::{CardinalContext, ContextHandle};
+use cardinal_base::destinations::circuit_breaker::CircuitState;
+use cardinal_base::destinations::container::DestinationContainer;
+use cardinal_base::destinations::matcher::MatcherTier;
+use cardinal_config::Plugin;
+use cardinal_plugins::container::{PluginBuiltInType, PluginContainer, PluginHandler};
+use cardinal_plugins::utils::parse_query_string_multi;
+use cardinal_proxy::cache::ResponseCache;
+use cardinal_proxy::drain::DrainState;
+use http::{Method, Response, StatusCode};
+use pingora::apps::http_app::ServeHttp;
+use pingora::protocols::http::ServerSession;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Admin HTTP API served on its own listener: introspection of the running gateway plus
+/// reload and drain controls. Without a token only the read-only endpoints are served.
+pub struct AdminService {
+    context: ContextHandle,
+    reloader: ConfigReloader,
+    drain: DrainState,
+    drain_timeout: Duration,
+    token: Option<String>,
+    cache: Option<&'static ResponseCache>,
+}
+
+impl AdminService {
+    pub fn new(
+        context: ContextHandle,
+        reloader: ConfigReloader,
+        drain: DrainState,
+        token: Option<String>,
+    ) -> Self {
+        Self {
+            context,
+            reloader,
+            drain,
+            drain_timeout: Duration::from_millis(30_000),
+            token,
+            cache: None,
+        }
+    }
+
+    /// Time `POST /drain` waits for the requests in flight to finish.
+    pub fn with_drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Enables `DELETE /cache/{destination}` against `cache`.
+    pub fn with_cache(mut self, cache: &'static ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    fn authorized(&self, session: &ServerSession) -> bool {
+        let Some(expected) = &self.token else {
+            return true;
+        };
+
+        session
+            .req_header()
+            .headers
+            .get(http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|provided| token_matches(expected, provided))
+            .unwrap_or(false)
+    }
+
+    async fn route(&self, method: &Method, path: &str, query: Option<&str>) -> (StatusCode, Value) {
+        let context = self.context.current();
+
+        match (method, path) {
+            (&Method::GET, "/config") => (StatusCode::OK, config_view(&context)),
+            (&Method::GET, "/destinations") => match destinations_view(&context).await {
+                Ok(view) => (StatusCode::OK, view),
+                Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": err })),
+            },
+            (&Method::GET, "/plugins") => match plugins_view(&context).await {
+                Ok(view) => (StatusCode::OK, view),
+                Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": err })),
+            },
+            (&Method::GET, "/health") => match health_view(&context).await {
+                Ok(upstreams) => (
+                    StatusCode::OK,
+                    json!({ "draining": self.drain.is_draining(), "upstreams": upstreams }),
+                ),
+                Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": err })),
+            },
+            (&Method::GET, "/ready") => {
+                if self.drain.is_draining() {
+                    (StatusCode::SERVICE_UNAVAILABLE, json!({ "ready": false }))
+                } else {
+                    (StatusCode::OK, json!({ "ready": true }))
+                }
+            }
+            (&Method::GET, "/version") => (
+                StatusCode::OK,
+                json!({ "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") }),
+            ),
+            (&Method::POST, "/reload") => match self.reloader.reload().await {
+                Ok(_) => {
+                    info!("Configuration reloaded through the admin API");
+                    (StatusCode::OK, json!({ "reloaded": true }))
+                }
+                Err(err) => {
+                    warn!(%err, "Configuration reload through the admin API rejected");
+                    (
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        json!({ "reloaded": false, "error": err.to_string() }),
+                    )
+                }
+            },
+            (&Method::POST, "/drain") => {
+                self.drain.start();
+                info!("Draining started through the admin API");
+                let drained = self.drain.wait_idle(self.drain_timeout).await;
+                let in_flight = self.drain.in_flight();
+                if !drained {
+                    warn!(in_flight, "Drain timed out with requests still in flight");
+                }
+                (
+                    StatusCode::OK,
+                    json!({ "draining": true, "drained": drained, "in_flight": in_flight }),
+                )
+            }
+            (&Method::POST, "/resume") => {
+                self.drain.resume();
+                info!("Draining stopped through the admin API");
+                (StatusCode::OK, json!({ "draining": false }))
+            }
+            (&Method::DELETE, path) if path.starts_with("/cache/") => {
+                self.purge(&path["/cache/".len()..], query).await
+            }
+            (_, path) if path.starts_with("/cache/") => (
+                StatusCode::METHOD_NOT_ALLOWED,
+                json!({ "error": "method not allowed" }),
+            ),
+            (
+                _,
+                "/config" | "/destinations" | "/plugins" | "/health" | "/ready" | "/version"
+                | "/reload" | "/drain" | "/resume",
+            ) => (
+                StatusCode::METHOD_NOT_ALLOWED,
+                json!({ "error": "method not allowed" }),
+            ),
+            _ => (StatusCode::NOT_FOUND, json!({ "error": "not found" })),
+        }
+    }
+
+    /// Drops the cached responses of a destination, those under `?path=` when given.
+    async fn purge(&self, destination: &str, query: Option<&str>) -> (StatusCode, Value) {
+        let Some(cache) = self.cache else {
+            return (
+                StatusCode::NOT_FOUND,
+                json!({ "error": "caching is disabled" }),
+            );
+        };
+        if !self
+            .context
+            .current()
+            .config
+            .destinations
+            .contains_key(destination)
+        {
+            return (
+                StatusCode::NOT_FOUND,
+                json!({ "error": "unknown destination" }),
+            );
+        }
+
+        let path_prefix = query
+            .map(parse_query_string_multi)
+            .and_then(|mut params| params.remove("path"))
+            .and_then(|mut paths| paths.pop());
+        let purged = cache.purge(destination, path_prefix.as_deref()).await;
+        info!(destination, purged, "Cache purged through the admin API");
+        (StatusCode::OK, json!({ "purged": purged }))
+    }
+}
+
+#[async_trait]
+impl ServeHttp for AdminService {
+    async fn response(&self, session: &mut ServerSession) -> Response<Vec<u8>> {
+        if !self.authorized(session) {
+            let mut response = json_response(
+                StatusCode::UNAUTHORIZED,
+                &json!({ "error": "unauthorized" }),
+            );
+            response.headers_mut().insert(
+                http::header::WWW_AUTHENTICATE,
+                http::HeaderValue::from_static("Bearer"),
+            );
+            return response;
+        }
+
+        let method = session.req_header().method.clone();
+        if self.token.is_none() && method != Method::GET {
+            return json_response(
+                StatusCode::FORBIDDEN,
+                &json!({ "error": "an admin token is required to change the gateway" }),
+            );
+        }
+        let path = session.req_header().uri.path().to_string();
+        let query = session.req_header().uri.query().map(str::to_string);
+        let (status, body) = self.route(&method, &path, query.as_deref()).await;
+
+        json_response(status, &body)
+    }
+}
+
+fn json_response(status: StatusCode, body: &Value) -> Response<Vec<u8>> {
+    let body = serde_json::to_vec_pretty(body).unwrap_or_default();
+    Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .header(http::header::CONTENT_LENGTH, body.len())
+        .body(body)
+        .unwrap_or_default()
+}
+
+/// Compares without short-circuiting on the first differing byte.
+fn token_matches(expected: &str, provided: &str) -> bool {
+    expected.len() == provided.len()
+        && expected
+            .bytes()
+            .zip(provided.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn config_view(context: &CardinalContext) -> Value {
+    let mut config = serde_json::to_value(context.config.as_ref()).unwrap_or(Value::Null);
+    if let Some(token) = config.pointer_mut("/server/admin/token") {
+        if !token.is_null() {
+            *token = json!("<redacted>");
+        }
+    }
+    config
+}
+
+async fn destinations_view(context: &CardinalContext) -> Result<Value, String> {
+    let container = context
+        .get::<DestinationContainer>()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let destinations = container
+        .destinations()
+        .iter()
+        .map(|wrapper| {
+            let destination = &wrapper.destination;
+            json!({
+                "name": destination.name,
+                "url": destination.url,
+                "default": destination.default,
+                "has_routes": wrapper.has_routes,
+                "match": destination.r#match,
+                "upstreams": wrapper.upstreams.health().into_iter().map(|h| json!({
+                    "url": h.url,
+                    "healthy": h.healthy,
+                })).collect::<Vec<_>>(),
+                "circuit_breaker": wrapper.circuit_breaker.as_ref().map(|b| circuit_state(b.state())),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let matchers = container
+        .matcher()
+        .entries()
+        .into_iter()
+        .map(|entry| {
+            json!({
+                "tier": matcher_tier(entry.tier),
+                "destination": entry.destination,
+                "rule": entry.rule,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json!({
+        "destinations": destinations,
+        "default_destination": container.default_destination().map(|d| d.destination.name.clone()),
+        "matchers": matchers,
+    }))
+}
+
+async fn plugins_view(context: &CardinalContext) -> Result<Value, String> {
+    let container = context
+        .get::<PluginContainer>()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut plugins = container
+        .plugins()
+        .map(|(name, handler)| match handler {
+            PluginHandler::Builtin(builtin) => json!({
+                "name": name,
+                "kind": "builtin",
+                "phase": match builtin {
+                    PluginBuiltInType::Inbound(_) => "inbound",
+                    PluginBuiltInType::Outbound(_) => "outbound",
+                },
+            }),
+            PluginHandler::Wasm(_) => json!({
+                "name": name,
+                "kind": "wasm",
+                "path": wasm_path(context, name),
+            }),
+        })
+        .collect::<Vec<_>>();
+    plugins.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+    Ok(Value::Array(plugins))
+}
+
+async fn health_view(context: &CardinalContext) -> Result<Value, String> {
+    let container = context
+        .get::<DestinationContainer>()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    serde_json::to_value(
+        container
+            .upstream_health()
+            .into_iter()
+            .map(|(name, endpoints)| {
+                let endpoints = endpoints
+                    .into_iter()
+                    .map(|h| json!({ "url": h.url, "healthy": h.healthy }))
+                    .collect::<Vec<_>>();
+                (name, endpoints)
+            })
+            .collect::<std::collections::BTreeMap<_, _>>(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn wasm_path(context: &CardinalContext, name: &str) -> Option<String> {
+    context
+        .config
+        .plugins
+        .iter()
+        .find_map(|plugin| match plugin {
+            Plugin::Wasm(wasm) if wasm.name == name => Some(wasm.path.clone()),
+            _ => None,
+        })
+}
+
+fn circuit_state(state: CircuitState) -> &'static str {
+    match state {
+        CircuitState::Closed => "closed",
+        CircuitState::Open => "open",
+        CircuitState::HalfOpen => "half_open",
+    }
+}
+
+fn matcher_tier(tier: MatcherTier) -> &'static str {
+    match tier {
+        MatcherTier::ExactHost => "exact_host",
+        MatcherTier::WildcardHost => "wildcard_host",
+        MatcherTier::RegexHost => "regex_host",
+        MatcherTier::Hostless => "hostless",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_matches_requires_exact_token() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secret", "secreT"));
+        assert!(!token_matches("secret", "secret2"));
+        assert!(!token_matches("secret", ""));
+    }
+}
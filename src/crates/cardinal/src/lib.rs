@@ -1,23 +1,48 @@
+mod admin;
+mod metrics;
+mod reload;
 mod tests;
 
-use cardinal_base::context::CardinalContext;
+pub use admin::AdminService;
+pub use metrics::MetricsService;
+pub use reload::{ConfigReloader, ReloadService};
+
+use crate::reload::Registration;
+use cardinal_base::context::{CardinalContext, ContextHandle};
 use cardinal_base::destinations::container::DestinationContainer;
 use cardinal_base::provider::{Provider, ProviderScope};
-use cardinal_config::{load_config, CardinalConfig};
+use cardinal_config::{load_config, CardinalConfig, ServerTls};
 use cardinal_errors::internal::CardinalInternalError;
 use cardinal_errors::CardinalError;
 use cardinal_plugins::container::PluginContainer;
 use cardinal_plugins::plugin_executor::CardinalPluginExecutor;
+use cardinal_proxy::access_log::AccessLogger;
+use cardinal_proxy::cache::ResponseCache;
 use cardinal_proxy::context_provider::CardinalContextProvider;
+use cardinal_proxy::drain::DrainState;
+use cardinal_proxy::forwarding::ForwardingHeaders;
+use cardinal_proxy::health::HealthCheckService;
+use cardinal_proxy::proxy_protocol::ProxyProtocolRelay;
+use cardinal_proxy::telemetry::{tracer_provider, TraceExportService};
+use cardinal_proxy::tls::tls_settings;
 use cardinal_proxy::{CardinalProxy, StaticContextProvider};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use pingora::apps::HttpServerOptions;
 use pingora::prelude::Server;
-use pingora::proxy::http_proxy_service;
+use pingora::proxy::{http_proxy_service, http_proxy_service_with_name, HttpProxy};
+use pingora::services::background::background_service;
+use pingora::services::listening::Service;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub struct Cardinal {
-    context: Arc<CardinalContext>,
+    context: ContextHandle,
+    reloader: ConfigReloader,
+    drain: DrainState,
     context_provider: Arc<dyn CardinalContextProvider>,
     plugin_executor: Arc<dyn CardinalPluginExecutor>,
+    tracer_provider: Option<SdkTracerProvider>,
 }
 
 impl Cardinal {
@@ -33,10 +58,25 @@ impl Cardinal {
         CardinalBuilder::new(config).build()
     }
 
+    /// The context currently serving requests.
     pub fn context(&self) -> Arc<CardinalContext> {
+        self.context.current()
+    }
+
+    pub fn context_handle(&self) -> ContextHandle {
         self.context.clone()
     }
 
+    /// Handle used to reload the configuration while the server is running.
+    pub fn reloader(&self) -> ConfigReloader {
+        self.reloader.clone()
+    }
+
+    /// Shared drain flag, also toggled by the admin API.
+    pub fn drain(&self) -> DrainState {
+        self.drain.clone()
+    }
+
     pub fn run(&self) -> Result<(), CardinalError> {
         let mut server = Server::new(None).map_err(|e| {
             CardinalError::InternalError(CardinalInternalError::FailedToInitiateServer(
@@ -45,28 +85,215 @@ impl Cardinal {
         })?;
         server.bootstrap();
 
-        let proxy = CardinalProxy::with_provider(
-            self.context_provider.clone(),
-            self.plugin_executor.clone(),
-        );
-        let mut proxy_service = http_proxy_service(&server.configuration, proxy);
+        let context = self.context.current();
+        let server_config = &context.config.server;
 
-        let server_addr = self.context.config.server.address.clone();
+        // Built here rather than in the builder, the OTLP exporter must not be created
+        // from within an async runtime.
+        let tracer_provider = match (&self.tracer_provider, &server_config.tracing) {
+            (Some(provider), _) => Some(provider.clone()),
+            (None, Some(tracing)) => Some(tracer_provider(tracing)?),
+            (None, None) => None,
+        };
+        let tracer = tracer_provider
+            .as_ref()
+            .map(|provider| provider.tracer(env!("CARGO_PKG_NAME")));
+        let access_log = server_config
+            .access_log
+            .as_ref()
+            .map(AccessLogger::from_config)
+            .transpose()?;
+        let forwarding = server_config
+            .forwarding
+            .as_ref()
+            .map(ForwardingHeaders::from_config)
+            .transpose()?;
+        // Shared by every proxy service and the admin API for the lifetime of the process,
+        // pingora's cache hooks take `'static` storage.
+        let cache: &'static ResponseCache = Box::leak(Box::new(ResponseCache::new(
+            &server_config.cache.clone().unwrap_or_default(),
+        )?));
+        let new_proxy = || {
+            let mut proxy = CardinalProxy::with_provider(
+                self.context_provider.clone(),
+                self.plugin_executor.clone(),
+            )
+            .with_drain(self.drain.clone())
+            .with_cache(cache);
+            if let Some(tracer) = &tracer {
+                proxy = proxy.with_tracer(tracer.clone());
+            }
+            if let Some(access_log) = &access_log {
+                proxy = proxy.with_access_log(access_log.clone());
+            }
+            if let Some(forwarding) = &forwarding {
+                proxy = proxy.with_forwarding(forwarding.clone());
+            }
+            if let Some(request_id) = &server_config.request_id {
+                proxy = proxy.with_request_id(request_id.clone());
+            }
+            proxy
+        };
 
-        proxy_service.add_tcp(&server_addr);
+        add_proxy_service(
+            &mut server,
+            None,
+            new_proxy(),
+            &server_config.address,
+            server_config.tls.as_ref(),
+            server_config.proxy_protocol,
+            server_config.h2c,
+        )?;
 
-        tracing::info!(addr = %server_addr, "Listening on address");
+        for listener in &server_config.listeners {
+            add_proxy_service(
+                &mut server,
+                Some(&listener.name),
+                new_proxy().with_listener(listener.clone()),
+                &listener.address,
+                listener.tls.as_ref(),
+                listener.proxy_protocol,
+                server_config.h2c,
+            )?;
+        }
+
+        let has_health_checks = context
+            .config
+            .destinations
+            .values()
+            .any(|d| d.health_check.is_some());
+        // A reload may introduce health checks, keep the service around when enabled.
+        if has_health_checks || server_config.reload.is_some() {
+            server.add_service(background_service(
+                "health checks",
+                HealthCheckService::from_handle(self.context.clone()),
+            ));
+        }
+
+        if let Some(admin) = &server_config.admin {
+            let mut admin_service = Service::new(
+                "admin".to_string(),
+                AdminService::new(
+                    self.context.clone(),
+                    self.reloader.clone(),
+                    self.drain.clone(),
+                    admin.token.clone(),
+                )
+                .with_cache(cache)
+                .with_drain_timeout(Duration::from_millis(admin.drain_timeout_ms)),
+            );
+            if admin.token.is_none() {
+                tracing::warn!(
+                    addr = %admin.address,
+                    "Admin API has no token, reload, drain and cache purge are refused"
+                );
+            }
+            add_listener(&mut admin_service, &admin.address, admin.tls.as_ref())?;
+            server.add_service(admin_service);
+        }
+
+        if let Some(metrics) = &server_config.metrics {
+            let mut metrics_service =
+                Service::new("metrics".to_string(), MetricsService::new(&metrics.path));
+            add_listener(&mut metrics_service, &metrics.address, None)?;
+            server.add_service(metrics_service);
+        }
+
+        if let Some(provider) = tracer_provider {
+            server.add_service(background_service(
+                "trace export",
+                TraceExportService::new(provider),
+            ));
+        }
+
+        if let Some(reload) = &server_config.reload {
+            server.add_service(background_service(
+                "config reload",
+                ReloadService::new(self.reloader.clone(), reload.clone()),
+            ));
+        }
 
-        server.add_service(proxy_service);
         server.run_forever();
     }
 }
 
+/// Serves `proxy` on `address`, behind a relay reading PROXY headers when `proxy_protocol` is
+/// set.
+fn add_proxy_service(
+    server: &mut Server,
+    name: Option<&str>,
+    proxy: CardinalProxy,
+    address: &str,
+    tls: Option<&ServerTls>,
+    proxy_protocol: bool,
+    h2c: bool,
+) -> Result<(), CardinalError> {
+    let relay = proxy_protocol
+        .then(|| ProxyProtocolRelay::bind(address))
+        .transpose()?;
+    let proxy = match &relay {
+        Some(relay) => proxy.with_proxy_protocol(relay.connections()),
+        None => proxy,
+    };
+    let mut service = match name {
+        Some(name) => http_proxy_service_with_name(&server.configuration, proxy, name),
+        None => http_proxy_service(&server.configuration, proxy),
+    };
+    if h2c {
+        accept_h2c(&mut service);
+    }
+
+    match relay {
+        Some(relay) => {
+            add_listener(&mut service, &relay.internal_address(), tls)?;
+            tracing::info!(addr = %address, "Expecting PROXY protocol headers");
+            server.add_service(relay.serve(service));
+        }
+        None => {
+            add_listener(&mut service, address, tls)?;
+            server.add_service(service);
+        }
+    }
+
+    Ok(())
+}
+
+fn add_listener<A>(
+    service: &mut Service<A>,
+    address: &str,
+    tls: Option<&ServerTls>,
+) -> Result<(), CardinalError> {
+    match tls {
+        Some(tls) => {
+            service.add_tls_with_settings(address, None, tls_settings(tls)?);
+            tracing::info!(addr = %address, "Listening on address with TLS");
+        }
+        None => {
+            service.add_tcp(address);
+            tracing::info!(addr = %address, "Listening on address");
+        }
+    }
+
+    Ok(())
+}
+
+/// Serves HTTP/2 to plaintext clients opening with its preface, others keep HTTP/1.1.
+fn accept_h2c(service: &mut Service<HttpProxy<CardinalProxy>>) {
+    if let Some(proxy) = service.app_logic_mut() {
+        let mut options = HttpServerOptions::default();
+        options.h2c = true;
+        proxy.server_options = Some(options);
+    }
+}
+
 pub struct CardinalBuilder {
     context: Arc<CardinalContext>,
     auto_register_defaults: bool,
     context_provider: Option<Arc<dyn CardinalContextProvider>>,
     plugin_executor: Option<Arc<dyn CardinalPluginExecutor>>,
+    registrations: Vec<Registration>,
+    config_paths: Vec<String>,
+    tracer_provider: Option<SdkTracerProvider>,
 }
 
 impl CardinalBuilder {
@@ -77,58 +304,75 @@ impl CardinalBuilder {
             auto_register_defaults: true,
             context_provider: None,
             plugin_executor: None,
+            registrations: Vec::new(),
+            config_paths: Vec::new(),
+            tracer_provider: None,
         }
     }
 
     pub fn new_empty(config: CardinalConfig) -> Self {
-        let context = Arc::new(CardinalContext::new(config));
         Self {
-            context,
             auto_register_defaults: false,
-            context_provider: None,
-            plugin_executor: None,
+            ..Self::new(config)
         }
     }
 
     pub fn from_paths(config_paths: &[String]) -> Result<Self, CardinalError> {
         let config = load_config(config_paths)?;
-        Ok(Self::new(config))
+        let mut builder = Self::new(config);
+        builder.config_paths = config_paths.to_vec();
+        Ok(builder)
     }
 
     pub fn context(&self) -> Arc<CardinalContext> {
         self.context.clone()
     }
 
+    /// Applies a registration to the current context and records it so reloaded
+    /// contexts get the same providers.
+    fn register_with(mut self, registration: Registration) -> Self {
+        registration(&self.context);
+        self.registrations.push(registration);
+        self
+    }
+
     pub fn register_provider<T>(self, scope: ProviderScope) -> Self
     where
         T: Provider + Send + Sync + 'static,
     {
-        self.context.register::<T>(scope);
-        self
+        self.register_with(Arc::new(move |context| context.register::<T>(scope)))
     }
     pub fn register_provider_with_factory<T, F>(self, scope: ProviderScope, factory: F) -> Self
     where
         T: Provider + Send + Sync + 'static,
         F: Fn(Arc<CardinalContext>) -> Result<T, CardinalError> + Send + Sync + 'static,
     {
-        let ctx = Arc::clone(&self.context);
         let factory: Arc<dyn Fn(Arc<CardinalContext>) -> Result<T, CardinalError> + Send + Sync> =
             Arc::new(factory);
-        self.context
-            .register_with_factory::<T, _, _>(scope, move |_ctx| {
-                let ctx_clone = Arc::clone(&ctx);
+        self.register_with(Arc::new(move |context| {
+            // Weak, the context owns this factory.
+            let ctx = Arc::downgrade(context);
+            let factory = Arc::clone(&factory);
+            context.register_with_factory::<T, _, _>(scope, move |_ctx| {
+                let ctx_clone = ctx.upgrade();
                 let factory = Arc::clone(&factory);
-                async move { (factory)(ctx_clone) }
+                async move {
+                    let ctx_clone = ctx_clone.ok_or(CardinalError::InternalError(
+                        CardinalInternalError::ProviderNotBuilt,
+                    ))?;
+                    (factory)(ctx_clone)
+                }
             });
-        self
+        }))
     }
 
     pub fn register_singleton_instance<T>(self, instance: Arc<T>) -> Self
     where
         T: Provider + Send + Sync + 'static,
     {
-        self.context.register_singleton_instance::<T>(instance);
-        self
+        self.register_with(Arc::new(move |context| {
+            context.register_singleton_instance::<T>(instance.clone())
+        }))
     }
 
     pub fn with_context_provider(mut self, provider: Arc<dyn CardinalContextProvider>) -> Self {
@@ -141,31 +385,43 @@ impl CardinalBuilder {
         self
     }
 
-    pub fn build(self) -> Cardinal {
+    /// Exports request spans through `provider` instead of the exporter configured under
+    /// `server.tracing`.
+    pub fn with_tracer_provider(mut self, provider: SdkTracerProvider) -> Self {
+        self.tracer_provider = Some(provider);
+        self
+    }
+
+    pub fn build(mut self) -> Cardinal {
         if self.auto_register_defaults {
-            if !self.context.is_registered::<DestinationContainer>() {
-                self.context
-                    .register::<DestinationContainer>(ProviderScope::Singleton);
-            }
+            self = self.register_with(Arc::new(|context| {
+                if !context.is_registered::<DestinationContainer>() {
+                    context.register::<DestinationContainer>(ProviderScope::Singleton);
+                }
 
-            if !self.context.is_registered::<PluginContainer>() {
-                self.context
-                    .register::<PluginContainer>(ProviderScope::Singleton);
-            }
+                if !context.is_registered::<PluginContainer>() {
+                    context.register::<PluginContainer>(ProviderScope::Singleton);
+                }
+            }));
         }
 
+        let handle = ContextHandle::new(self.context);
+
         let provider = self
             .context_provider
-            .unwrap_or_else(|| Arc::new(StaticContextProvider::new(self.context.clone())));
+            .unwrap_or_else(|| Arc::new(StaticContextProvider::from_handle(handle.clone())));
 
         let plugin_executor = self
             .plugin_executor
-            .unwrap_or_else(|| Arc::new(StaticContextProvider::new(self.context.clone())));
+            .unwrap_or_else(|| Arc::new(StaticContextProvider::from_handle(handle.clone())));
 
         Cardinal {
-            context: self.context,
+            reloader: ConfigReloader::new(handle.clone(), self.registrations, self.config_paths),
+            context: handle,
+            drain: DrainState::default(),
             context_provider: provider,
             plugin_executor,
+            tracer_provider: self.tracer_provider,
         }
     }
 }
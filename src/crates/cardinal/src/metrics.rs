@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+use http::{Method, Response, StatusCode};
+use pingora::apps::http_app::ServeHttp;
+use pingora::protocols::http::ServerSession;
+use prometheus::{Encoder, TextEncoder};
+
+/// Serves every metric of the default Prometheus registry, pingora's included, in the text
+/// exposition format on a single path.
+pub struct MetricsService {
+    path: String,
+}
+
+impl MetricsService {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl ServeHttp for MetricsService {
+    async fn response(&self, session: &mut ServerSession) -> Response<Vec<u8>> {
+        let req = session.req_header();
+        if req.uri.path() != self.path {
+            return text_response(StatusCode::NOT_FOUND, "text/plain", b"not found".to_vec());
+        }
+        if req.method != Method::GET {
+            return text_response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "text/plain",
+                b"method not allowed".to_vec(),
+            );
+        }
+
+        let encoder = TextEncoder::new();
+        let mut body = Vec::new();
+        if let Err(err) = encoder.encode(&prometheus::gather(), &mut body) {
+            return text_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "text/plain",
+                err.to_string().into_bytes(),
+            );
+        }
+
+        text_response(StatusCode::OK, encoder.format_type(), body)
+    }
+}
+
+fn text_response(status: StatusCode, content_type: &str, body: Vec<u8>) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, content_type)
+        .header(http::header::CONTENT_LENGTH, body.len())
+        .body(body)
+        .unwrap_or_default()
+}
@@ -0,0 +1,226 @@
+use cardinal_base::context::{CardinalContext, ContextHandle};
+use cardinal_base::destinations::container::DestinationContainer;
+use cardinal_config::{load_config, validate_config, CardinalConfig, ServerReload};
+use cardinal_errors::CardinalError;
+use cardinal_plugins::container::PluginContainer;
+use pingora::server::ShutdownWatch;
+use pingora::services::background::BackgroundService;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tracing::{error, info, warn};
+
+/// Re-applies a provider registration made on the builder to a freshly built context.
+pub(crate) type Registration = Arc<dyn Fn(&Arc<CardinalContext>) + Send + Sync>;
+
+/// Builds a new context from a configuration and swaps it in, leaving the running one
+/// untouched when the configuration is rejected.
+#[derive(Clone)]
+pub struct ConfigReloader {
+    handle: ContextHandle,
+    registrations: Arc<Vec<Registration>>,
+    config_paths: Arc<Vec<String>>,
+}
+
+impl ConfigReloader {
+    pub(crate) fn new(
+        handle: ContextHandle,
+        registrations: Vec<Registration>,
+        config_paths: Vec<String>,
+    ) -> Self {
+        Self {
+            handle,
+            registrations: Arc::new(registrations),
+            config_paths: Arc::new(config_paths),
+        }
+    }
+
+    pub fn config_paths(&self) -> &[String] {
+        &self.config_paths
+    }
+
+    /// Reloads from the configuration files the gateway was started with.
+    pub async fn reload(&self) -> Result<Arc<CardinalContext>, CardinalError> {
+        if self.config_paths.is_empty() {
+            return Err(CardinalError::Other(
+                "Configuration was not loaded from files, nothing to reload".to_string(),
+            ));
+        }
+
+        let config = load_config(&self.config_paths)?;
+        self.apply(config).await
+    }
+
+    /// Validates `config`, builds its destinations and plugins, then makes it current.
+    pub async fn apply(
+        &self,
+        config: CardinalConfig,
+    ) -> Result<Arc<CardinalContext>, CardinalError> {
+        validate_config(&config)?;
+        warn_on_static_changes(&self.handle.current().config, &config);
+
+        let context = Arc::new(CardinalContext::new(config));
+        for registration in self.registrations.iter() {
+            registration(&context);
+        }
+
+        // Build the singletons now so a broken destination or plugin rejects the reload.
+        if context.is_registered::<DestinationContainer>() {
+            let destinations = context.get::<DestinationContainer>().await?;
+            let current = self.handle.current();
+            if current.is_registered::<DestinationContainer>() {
+                if let Ok(previous) = current.get::<DestinationContainer>().await {
+                    destinations.carry_state_from(&previous);
+                }
+            }
+        }
+        if context.is_registered::<PluginContainer>() {
+            context.get::<PluginContainer>().await?;
+        }
+
+        self.handle.replace(context.clone());
+        Ok(context)
+    }
+}
+
+fn warn_on_static_changes(current: &CardinalConfig, next: &CardinalConfig) {
+    let listeners = |config: &CardinalConfig| {
+        let mut bound = vec![(
+            config.server.address.clone(),
+            config.server.tls.clone(),
+            config.server.proxy_protocol,
+        )];
+        bound.extend(
+            config
+                .server
+                .listeners
+                .iter()
+                .map(|l| (l.address.clone(), l.tls.clone(), l.proxy_protocol)),
+        );
+        bound
+    };
+
+    if listeners(current) != listeners(next) {
+        warn!("Listener addresses, TLS and PROXY protocol settings changed, they apply after a restart");
+    }
+    if current.server.tracing != next.server.tracing
+        || current.server.access_log != next.server.access_log
+        || current.server.request_id != next.server.request_id
+        || current.server.forwarding != next.server.forwarding
+        || current.server.cache != next.server.cache
+    {
+        warn!("Tracing, access log, request ID, forwarding and cache settings changed, they apply after a restart");
+    }
+}
+
+/// Background service reloading the configuration on `SIGHUP` and when the configuration
+/// files change on disk.
+pub struct ReloadService {
+    reloader: ConfigReloader,
+    settings: ServerReload,
+}
+
+impl ReloadService {
+    pub fn new(reloader: ConfigReloader, settings: ServerReload) -> Self {
+        Self { reloader, settings }
+    }
+
+    async fn trigger(&self, trigger: &str) {
+        match self.reloader.reload().await {
+            Ok(_) => info!(%trigger, "Configuration reloaded"),
+            Err(err) => {
+                error!(%err, %trigger, "Configuration reload rejected, keeping the running configuration")
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl BackgroundService for ReloadService {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        let mut hangup = if self.settings.signal {
+            match signal(SignalKind::hangup()) {
+                Ok(hangup) => Some(hangup),
+                Err(err) => {
+                    warn!(%err, "Failed to listen for SIGHUP, signal reloads disabled");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let mut ticker = tokio::time::interval(Duration::from_millis(
+            self.settings.watch_interval_ms.max(1),
+        ));
+        let mut fingerprint = files_fingerprint(self.reloader.config_paths());
+
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => break,
+                _ = next_hangup(&mut hangup) => self.trigger("signal").await,
+                _ = ticker.tick(), if self.settings.watch => {
+                    let current = files_fingerprint(self.reloader.config_paths());
+                    if current != fingerprint {
+                        fingerprint = current;
+                        self.trigger("file change").await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn next_hangup(hangup: &mut Option<Signal>) {
+    match hangup {
+        Some(hangup) => {
+            hangup.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+type Fingerprint = Vec<(PathBuf, Option<(SystemTime, u64)>)>;
+
+/// Modification time and size of every configuration file, including the `.toml` files
+/// of configuration directories.
+fn files_fingerprint(paths: &[String]) -> Fingerprint {
+    let mut files = Vec::new();
+    for path in paths {
+        collect_config_files(Path::new(path), &mut files);
+    }
+
+    files
+        .into_iter()
+        .map(|file| {
+            let stamp = std::fs::metadata(&file)
+                .and_then(|meta| Ok((meta.modified()?, meta.len())))
+                .ok();
+            (file, stamp)
+        })
+        .collect()
+}
+
+fn collect_config_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return;
+    }
+
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return;
+    };
+    let mut entries = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_config_files(&entry, files);
+        } else if entry.extension().is_some_and(|ext| ext == "toml") {
+            files.push(entry);
+        }
+    }
+}
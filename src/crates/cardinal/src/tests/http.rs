@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(clippy::module_inception, dead_code)]
 pub mod http {
     use std::collections::HashMap;
     use std::sync::Arc;
@@ -1,31 +1,45 @@
 pub mod http;
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use crate::tests::http::http::{create_server_with, Route, TestHttpServer};
     use crate::Cardinal;
     use async_trait::async_trait;
     use cardinal_base::context::CardinalContext;
-    use cardinal_base::destinations::container::DestinationWrapper;
+    use cardinal_base::destinations::circuit_breaker::CircuitState;
+    use cardinal_base::destinations::container::DestinationContainer;
     use cardinal_base::provider::ProviderScope;
     use cardinal_config::{
-        load_config, CardinalConfig, Destination, DestinationMatch, DestinationMatchValue,
-        DestinationRetry, DestinationRetryBackoffType, DestinationTimeouts, ServerConfig,
+        load_config, AccessLogFormat, AccessLogSink, CardinalConfig, Destination, DestinationCache,
+        DestinationCircuitBreaker, DestinationClientCertificate, DestinationCompression,
+        DestinationGrpc, DestinationLimits, DestinationMatch, DestinationMatchField,
+        DestinationMatchValue, DestinationMirror, DestinationRetry, DestinationRetryBackoffType,
+        DestinationRetryCondition, DestinationSplit, DestinationSplitTarget, DestinationTimeouts,
+        DestinationTls, DestinationUpstream, DestinationWebSocket, ForwardedForMode, HealthCheck,
+        LoadBalancerHashKey, RequestIdFormat, ServerAccessLog, ServerAdmin, ServerConfig,
+        ServerForwarding, ServerLimits, ServerListener, ServerMetrics, ServerRequestId, ServerTls,
+        TlsAlpn, TlsCertificate,
     };
     use cardinal_errors::CardinalError;
     use cardinal_plugins::container::{PluginBuiltInType, PluginContainer, PluginHandler};
     use cardinal_plugins::headers::CARDINAL_PARAMS_HEADER_BASE;
     use cardinal_plugins::plugin_executor::CardinalPluginExecutor;
-    use cardinal_plugins::request_context::{RequestContext, RequestContextBase};
+    use cardinal_plugins::request_context::RequestContext;
     use cardinal_plugins::runner::{MiddlewareResult, RequestMiddleware, ResponseMiddleware};
+    use cardinal_plugins::utils::respond_error;
     use cardinal_proxy::context_provider::CardinalContextProvider;
     use cardinal_proxy::req::ReqCtx;
     use cardinal_wasm_plugins::plugin::WasmPlugin;
     use cardinal_wasm_plugins::wasmer::AsStoreRef;
+    use opentelemetry::trace::SpanKind;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
     use pingora::proxy::Session;
-    use std::collections::{BTreeMap, HashMap};
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
     use std::path::Path;
-    use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+    use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
     use std::sync::{Arc, Mutex, OnceLock};
     use std::thread::JoinHandle;
     use std::time::{Duration, Instant};
@@ -39,8 +53,8 @@ mod tests {
     static TEST_HTTP_SERVER: OnceCell<Servers> = OnceCell::const_new();
 
     struct Servers {
-        posts_api: TestHttpServer,
-        auth_api: TestHttpServer,
+        _posts_api: TestHttpServer,
+        _auth_api: TestHttpServer,
     }
 
     pub fn run_cardinal() -> &'static Mutex<std::thread::JoinHandle<()>> {
@@ -95,6 +109,19 @@ mod tests {
                 log_upstream_response: true,
                 global_request_middleware: vec![],
                 global_response_middleware: vec![],
+                tls: None,
+                proxy_protocol: false,
+                h2c: false,
+                listeners: vec![],
+                reload: None,
+                admin: None,
+                metrics: None,
+                tracing: None,
+                access_log: None,
+                request_id: None,
+                forwarding: None,
+                cache: None,
+                limits: None,
             },
             destinations: map,
             plugins: vec![],
@@ -132,6 +159,8 @@ mod tests {
         Destination {
             name: name.to_string(),
             url: url.to_string(),
+            upstreams: vec![],
+            load_balancer: None,
             health_check: None,
             default,
             r#match: matcher,
@@ -139,6 +168,17 @@ mod tests {
             middleware: vec![],
             timeout: None,
             retry: None,
+            circuit_breaker: None,
+            preserve_host: false,
+            host_header: None,
+            sni: None,
+            tls: None,
+            mirror: None,
+            cache: None,
+            compression: None,
+            limits: None,
+            websocket: None,
+            grpc: None,
         }
     }
 
@@ -188,11 +228,11 @@ mod tests {
             vec![
                 Route::new(Method::Post, "/post", move |request| {
                     let response = Response::from_string("Hello World");
-                    let _ = request.respond(response).unwrap();
+                    request.respond(response).unwrap();
                 }),
                 Route::new(Method::Get, "/post", move |request| {
                     let response = Response::from_string("Hello World");
-                    let _ = request.respond(response).unwrap();
+                    request.respond(response).unwrap();
                 }),
             ],
         ))
@@ -203,15 +243,15 @@ mod tests {
             "127.0.0.1:9992".to_string(),
             vec![Route::new(Method::Post, "/current", move |request| {
                 let response = Response::from_string("Hello World");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         ))
     }
 
     async fn create_servers_collection() -> Result<Servers, ()> {
         Ok(Servers {
-            posts_api: create_posts_api().await?,
-            auth_api: create_auth_api().await?,
+            _posts_api: create_posts_api().await?,
+            _auth_api: create_auth_api().await?,
         })
     }
 
@@ -255,7 +295,7 @@ mod tests {
             vec![Route::new(Method::Get, "/post", move |request| {
                 backend_hits_clone.fetch_add(1, Ordering::SeqCst);
                 let response = Response::from_string("middleware-ok");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -308,7 +348,7 @@ mod tests {
             vec![Route::new(Method::Get, "/post", move |request| {
                 backend_hits_clone.fetch_add(1, Ordering::SeqCst);
                 let response = Response::from_string("backend-ok");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -365,7 +405,7 @@ mod tests {
             vec![Route::new(Method::Get, "/post", move |request| {
                 backend_hits_clone.fetch_add(1, Ordering::SeqCst);
                 let response = Response::from_string("request-middleware-ok");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -442,7 +482,7 @@ mod tests {
 
                 let body = user_header.unwrap_or_else(|| "missing".to_string());
                 let response = Response::from_string(body);
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -511,7 +551,7 @@ mod tests {
 
                 let body = user_header.unwrap_or_else(|| "missing".to_string());
                 let response = Response::from_string(body);
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -550,7 +590,7 @@ mod tests {
             vec![Route::new(Method::Get, "/post", move |request| {
                 backend_hits_clone.fetch_add(1, Ordering::SeqCst);
                 let response = Response::from_string("wasm-request-ok");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -586,7 +626,7 @@ mod tests {
             vec![Route::new(Method::Get, "/post", move |request| {
                 backend_hits_clone.fetch_add(1, Ordering::SeqCst);
                 let response = Response::from_string("shared-state-ok");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -621,7 +661,7 @@ mod tests {
             vec![Route::new(Method::Get, "/post", move |request| {
                 backend_hits_clone.fetch_add(1, Ordering::SeqCst);
                 let response = Response::from_string("shared-state-missing-ok");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -657,7 +697,7 @@ mod tests {
             vec![Route::new(Method::Get, "/post", move |request| {
                 backend_hits_clone.fetch_add(1, Ordering::SeqCst);
                 let response = Response::from_string("base-response");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -711,7 +751,7 @@ mod tests {
             vec![Route::new(Method::Get, "/post", move |request| {
                 backend_hits_clone.fetch_add(1, Ordering::SeqCst);
                 let response = Response::from_string("should-not-see");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -838,7 +878,7 @@ mod tests {
 
     #[tokio::test]
     async fn wasm_host_import_invokes_custom_function() {
-        use cardinal_wasm_plugins::wasmer::{Function, FunctionEnvMut, Store};
+        use cardinal_wasm_plugins::wasmer::{Function, FunctionEnvMut};
 
         let config = load_test_config("wasm_host_import.toml");
         let server_addr = config.server.address.clone();
@@ -851,7 +891,7 @@ mod tests {
             vec![Route::new(Method::Get, "/post", move |request| {
                 backend_hits_clone.fetch_add(1, Ordering::SeqCst);
                 let response = Response::from_string("host-import-ok");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -954,7 +994,7 @@ mod tests {
 
     #[tokio::test]
     async fn wasm_host_import_can_mutate_env_memory() {
-        use cardinal_wasm_plugins::wasmer::{Function, FunctionEnvMut, Store};
+        use cardinal_wasm_plugins::wasmer::{Function, FunctionEnvMut};
 
         let config = load_test_config("wasm_host_import_env.toml");
         let server_addr = config.server.address.clone();
@@ -967,7 +1007,7 @@ mod tests {
             vec![Route::new(Method::Get, "/post", move |request| {
                 backend_hits_clone.fetch_add(1, Ordering::SeqCst);
                 let response = Response::from_string("host-import-env");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -1043,7 +1083,7 @@ mod tests {
         let _cardinal_thread = spawn_cardinal(cardinal);
         wait_for_startup().await;
 
-        let mut response = ureq::get(&http_url(&server_addr, "/host/post"))
+        let response = ureq::get(&http_url(&server_addr, "/host/post"))
             .call()
             .unwrap();
 
@@ -1081,7 +1121,7 @@ mod tests {
             vec![Route::new(Method::Get, "/post", move |request| {
                 backend_hits_clone.fetch_add(1, Ordering::SeqCst);
                 let response = Response::from_string("wasm-backend");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -1090,7 +1130,7 @@ mod tests {
         let _cardinal_thread = spawn_cardinal(cardinal);
         wait_for_startup().await;
 
-        let mut response = ureq::get(&format!(
+        let response = ureq::get(&format!(
             "{}?tenant=cardinal",
             http_url(&server_addr, "/posts/post")
         ))
@@ -1217,7 +1257,7 @@ mod tests {
             vec![Route::new(Method::Get, "/post", move |request| {
                 backend_hits_clone.fetch_add(1, Ordering::SeqCst);
                 let response = Response::from_string("should-not-hit");
-                let _ = request.respond(response).unwrap();
+                request.respond(response).unwrap();
             })],
         );
 
@@ -1349,6 +1389,11 @@ mod tests {
                 host: Some(DestinationMatchValue::String("status.example.com".into())),
                 path_prefix: None,
                 path_exact: Some("/status".into()),
+                methods: vec![],
+                headers: vec![],
+                query: vec![],
+                cookies: vec![],
+                split: None,
             }),
             false,
         );
@@ -1360,6 +1405,11 @@ mod tests {
                 host: Some(DestinationMatchValue::String("status.example.com".into())),
                 path_prefix: Some(DestinationMatchValue::String("/status".into())),
                 path_exact: None,
+                methods: vec![],
+                headers: vec![],
+                query: vec![],
+                cookies: vec![],
+                split: None,
             }),
             false,
         );
@@ -1420,6 +1470,11 @@ mod tests {
             }),
             path_prefix: Some(DestinationMatchValue::String(path.into())),
             path_exact: None,
+            methods: vec![],
+            headers: vec![],
+            query: vec![],
+            cookies: vec![],
+            split: None,
         };
 
         let config = config_with_destinations(
@@ -1503,6 +1558,11 @@ mod tests {
                     regex: "^/reports/.*".into(),
                 }),
                 path_exact: None,
+                methods: vec![],
+                headers: vec![],
+                query: vec![],
+                cookies: vec![],
+                split: None,
             }),
             false,
         );
@@ -1514,6 +1574,11 @@ mod tests {
                 host: None,
                 path_prefix: Some(DestinationMatchValue::String("/reports".into())),
                 path_exact: None,
+                methods: vec![],
+                headers: vec![],
+                query: vec![],
+                cookies: vec![],
+                split: None,
             }),
             false,
         );
@@ -1619,6 +1684,11 @@ mod tests {
                 host: Some(DestinationMatchValue::String("billing.example.com".into())),
                 path_prefix: Some(DestinationMatchValue::String("/billing".into())),
                 path_exact: None,
+                methods: vec![],
+                headers: vec![],
+                query: vec![],
+                cookies: vec![],
+                split: None,
             }),
             false,
         );
@@ -1630,6 +1700,11 @@ mod tests {
                 host: Some(DestinationMatchValue::String("support.example.com".into())),
                 path_prefix: Some(DestinationMatchValue::String("/support".into())),
                 path_exact: None,
+                methods: vec![],
+                headers: vec![],
+                query: vec![],
+                cookies: vec![],
+                split: None,
             }),
             false,
         );
@@ -1777,6 +1852,7 @@ mod tests {
                 interval_ms: 100,
                 backoff_type: DestinationRetryBackoffType::Exponential,
                 max_interval: None,
+                ..Default::default()
             },
         );
 
@@ -1801,7 +1877,7 @@ mod tests {
                     vec![Route::new(Method::Get, "/resource", move |request| {
                         route_hits.fetch_add(1, Ordering::SeqCst);
                         let response = Response::from_string("retry-success");
-                        let _ = request.respond(response).unwrap();
+                        request.respond(response).unwrap();
                     })],
                 );
 
@@ -1842,6 +1918,7 @@ mod tests {
                 interval_ms: 150,
                 backoff_type: DestinationRetryBackoffType::Exponential,
                 max_interval: None,
+                ..Default::default()
             },
         );
 
@@ -1879,6 +1956,7 @@ mod tests {
                 interval_ms: 120,
                 backoff_type: DestinationRetryBackoffType::Exponential,
                 max_interval: Some(200),
+                ..Default::default()
             },
         );
 
@@ -1903,7 +1981,7 @@ mod tests {
                     vec![Route::new(Method::Get, "/resource", move |request| {
                         route_hits.fetch_add(1, Ordering::SeqCst);
                         let response = Response::from_string("retry-capped");
-                        let _ = request.respond(response).unwrap();
+                        request.respond(response).unwrap();
                     })],
                 );
 
@@ -1944,6 +2022,7 @@ mod tests {
                 interval_ms: 60,
                 backoff_type: DestinationRetryBackoffType::Linear,
                 max_interval: None,
+                ..Default::default()
             },
         );
 
@@ -1968,7 +2047,7 @@ mod tests {
                     vec![Route::new(Method::Get, "/resource", move |request| {
                         route_hits.fetch_add(1, Ordering::SeqCst);
                         let response = Response::from_string("retry-linear");
-                        let _ = request.respond(response).unwrap();
+                        request.respond(response).unwrap();
                     })],
                 );
 
@@ -2009,6 +2088,7 @@ mod tests {
                 interval_ms: 80,
                 backoff_type: DestinationRetryBackoffType::None,
                 max_interval: None,
+                ..Default::default()
             },
         );
 
@@ -2033,7 +2113,7 @@ mod tests {
                     vec![Route::new(Method::Get, "/resource", move |request| {
                         route_hits.fetch_add(1, Ordering::SeqCst);
                         let response = Response::from_string("retry-none");
-                        let _ = request.respond(response).unwrap();
+                        request.respond(response).unwrap();
                     })],
                 );
 
@@ -2160,107 +2240,2427 @@ mod tests {
         assert_eq!(backend_hits.load(Ordering::SeqCst), 1);
     }
 
-    fn spawn_cardinal(cardinal: Cardinal) -> JoinHandle<()> {
-        std::thread::spawn(move || {
-            cardinal.run().unwrap();
-        })
+    fn upstream(url: &str) -> DestinationUpstream {
+        DestinationUpstream {
+            url: url.to_string(),
+            weight: None,
+        }
     }
 
-    struct DenyingPluginExecutor {
-        can_run_calls: Arc<AtomicUsize>,
+    fn counting_backend(
+        address: &str,
+        body: &'static str,
+        hits: Arc<AtomicUsize>,
+    ) -> TestHttpServer {
+        spawn_backend(
+            address,
+            vec![Route::new(Method::Get, "/resource", move |request| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                request.respond(Response::from_string(body)).unwrap();
+            })],
+        )
     }
 
-    impl DenyingPluginExecutor {
-        fn new(can_run_calls: Arc<AtomicUsize>) -> Self {
-            Self { can_run_calls }
+    #[tokio::test]
+    async fn upstreams_round_robin_spreads_requests() {
+        let server_addr = "127.0.0.1:1970";
+        let first_hits = Arc::new(AtomicUsize::new(0));
+        let second_hits = Arc::new(AtomicUsize::new(0));
+        let _first = counting_backend("127.0.0.1:9870", "first", first_hits.clone());
+        let _second = counting_backend("127.0.0.1:9871", "second", second_hits.clone());
+
+        let mut destination = destination_with_match("pool", "", None, true);
+        destination.upstreams = vec![upstream("127.0.0.1:9870"), upstream("127.0.0.1:9871")];
+        let config = config_with_destinations(server_addr, true, vec![destination]);
+
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        for _ in 0..4 {
+            let response = ureq::get(&http_url(server_addr, "/pool/resource"))
+                .call()
+                .unwrap();
+            assert_eq!(response.status(), 200);
         }
+
+        assert_eq!(first_hits.load(Ordering::SeqCst), 2);
+        assert_eq!(second_hits.load(Ordering::SeqCst), 2);
     }
 
-    #[async_trait]
-    impl CardinalPluginExecutor for DenyingPluginExecutor {
-        async fn can_run_plugin(
-            &self,
-            _binding_id: &str,
-            _session: &mut Session,
-            _req_ctx: &mut RequestContext,
-        ) -> Result<bool, pingora::BError> {
-            self.can_run_calls.fetch_add(1, Ordering::SeqCst);
-            Ok(false)
+    #[tokio::test]
+    async fn upstreams_retry_moves_to_next_endpoint() {
+        let server_addr = "127.0.0.1:1971";
+        let live_hits = Arc::new(AtomicUsize::new(0));
+        let _live = counting_backend("127.0.0.1:9873", "live", live_hits.clone());
+
+        let mut destination = destination_with_match("pool", "", None, true);
+        destination.upstreams = vec![upstream("127.0.0.1:9872"), upstream("127.0.0.1:9873")];
+        destination.retry = Some(DestinationRetry {
+            max_attempts: 2,
+            interval_ms: 10,
+            backoff_type: DestinationRetryBackoffType::None,
+            max_interval: None,
+            ..Default::default()
+        });
+        let config = config_with_destinations(server_addr, true, vec![destination]);
+
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        for _ in 0..4 {
+            let mut response = ureq::get(&http_url(server_addr, "/pool/resource"))
+                .call()
+                .unwrap();
+            assert_eq!(response.body_mut().read_to_string().unwrap(), "live");
         }
-    }
 
-    struct TestContextProvider {
-        context: Option<Arc<CardinalContext>>,
-        resolve_count: Arc<AtomicUsize>,
+        assert_eq!(live_hits.load(Ordering::SeqCst), 4);
     }
 
-    impl TestContextProvider {
-        fn new(context: Option<Arc<CardinalContext>>, resolve_count: Arc<AtomicUsize>) -> Self {
-            Self {
-                context,
-                resolve_count,
-            }
+    #[tokio::test]
+    async fn health_checks_route_around_unhealthy_upstream() {
+        let server_addr = "127.0.0.1:1972";
+        let sick_hits = Arc::new(AtomicUsize::new(0));
+        let healthy_hits = Arc::new(AtomicUsize::new(0));
+
+        let sick_counter = sick_hits.clone();
+        let _sick = spawn_backend(
+            "127.0.0.1:9874",
+            vec![
+                Route::new(Method::Get, "/health", |request| {
+                    request
+                        .respond(Response::from_string("down").with_status_code(500))
+                        .unwrap();
+                }),
+                Route::new(Method::Get, "/resource", move |request| {
+                    sick_counter.fetch_add(1, Ordering::SeqCst);
+                    request.respond(Response::from_string("sick")).unwrap();
+                }),
+            ],
+        );
+        let healthy_counter = healthy_hits.clone();
+        let _healthy = spawn_backend(
+            "127.0.0.1:9875",
+            vec![
+                Route::new(Method::Get, "/health", |request| {
+                    request.respond(Response::from_string("ok")).unwrap();
+                }),
+                Route::new(Method::Get, "/resource", move |request| {
+                    healthy_counter.fetch_add(1, Ordering::SeqCst);
+                    request.respond(Response::from_string("healthy")).unwrap();
+                }),
+            ],
+        );
+
+        let mut destination = destination_with_match("pool", "", None, true);
+        destination.upstreams = vec![upstream("127.0.0.1:9874"), upstream("127.0.0.1:9875")];
+        destination.health_check = Some(HealthCheck {
+            path: "/health".into(),
+            interval_ms: 100,
+            timeout_ms: 200,
+            expect_status: 200,
+            healthy_threshold: 1,
+            unhealthy_threshold: 1,
+        });
+        let config = config_with_destinations(server_addr, true, vec![destination]);
+
+        let cardinal = Cardinal::new(config);
+        let context = cardinal.context();
+        let _cardinal_thread = spawn_cardinal(cardinal);
+        wait_for_startup().await;
+
+        for _ in 0..4 {
+            let mut response = ureq::get(&http_url(server_addr, "/pool/resource"))
+                .call()
+                .unwrap();
+            assert_eq!(response.body_mut().read_to_string().unwrap(), "healthy");
         }
+
+        assert_eq!(sick_hits.load(Ordering::SeqCst), 0);
+        assert_eq!(healthy_hits.load(Ordering::SeqCst), 4);
+
+        let container = context
+            .get::<cardinal_base::destinations::container::DestinationContainer>()
+            .await
+            .unwrap();
+        let health = container.upstream_health();
+        let pool = health.get("pool").unwrap();
+        assert!(!pool[0].healthy);
+        assert!(pool[1].healthy);
     }
 
-    impl CardinalContextProvider for TestContextProvider {
-        fn resolve(&self, _session: &Session, ctx: &mut ReqCtx) -> Option<Arc<CardinalContext>> {
-            self.resolve_count.fetch_add(1, Ordering::SeqCst);
-            self.context.as_ref().map(Arc::clone)
+    fn circuit_breaker(fallback: Option<&str>) -> DestinationCircuitBreaker {
+        DestinationCircuitBreaker {
+            failure_threshold: 2,
+            open_duration_ms: 60_000,
+            half_open_max_requests: None,
+            failure_status_codes: vec![503],
+            fallback_destination: fallback.map(str::to_string),
         }
     }
 
-    struct TestGlobalRequestMiddleware {
-        hits: Arc<AtomicUsize>,
+    fn failing_backend(address: &str, hits: Arc<AtomicUsize>) -> TestHttpServer {
+        spawn_backend(
+            address,
+            vec![Route::new(Method::Get, "/resource", move |request| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                request
+                    .respond(Response::from_string("unavailable").with_status_code(503))
+                    .unwrap();
+            })],
+        )
     }
 
-    #[async_trait]
-    impl RequestMiddleware for TestGlobalRequestMiddleware {
-        async fn on_request(
-            &self,
-            _session: &mut Session,
-            _backend: &mut RequestContext,
-            _cardinal: Arc<CardinalContext>,
-        ) -> Result<MiddlewareResult, CardinalError> {
-            self.hits.fetch_add(1, Ordering::SeqCst);
-            Ok(MiddlewareResult::Continue(HashMap::new()))
+    #[tokio::test]
+    async fn circuit_breaker_routes_to_fallback_when_open() {
+        let server_addr = "127.0.0.1:1973";
+        let failing_hits = Arc::new(AtomicUsize::new(0));
+        let fallback_hits = Arc::new(AtomicUsize::new(0));
+        let _failing = failing_backend("127.0.0.1:9876", failing_hits.clone());
+        let _fallback = counting_backend("127.0.0.1:9877", "fallback", fallback_hits.clone());
+
+        let mut primary = destination_with_match("primary", "127.0.0.1:9876", None, false);
+        primary.circuit_breaker = Some(circuit_breaker(Some("backup")));
+        let backup = destination_with_match("backup", "127.0.0.1:9877", None, false);
+        let config = config_with_destinations(server_addr, true, vec![primary, backup]);
+
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        for _ in 0..2 {
+            let err = ureq::get(&http_url(server_addr, "/primary/resource"))
+                .call()
+                .expect_err("expected upstream 503");
+            expect_status(err, 503);
         }
+
+        let mut response = ureq::get(&http_url(server_addr, "/primary/resource"))
+            .call()
+            .unwrap();
+        assert_eq!(response.body_mut().read_to_string().unwrap(), "fallback");
+        assert_eq!(failing_hits.load(Ordering::SeqCst), 2);
+        assert_eq!(fallback_hits.load(Ordering::SeqCst), 1);
     }
 
-    struct TestGlobalResponseMiddleware {
-        hits: Arc<AtomicUsize>,
-        header_name: &'static str,
-        header_value: &'static str,
+    #[tokio::test]
+    async fn circuit_breaker_rejects_fast_without_fallback() {
+        let server_addr = "127.0.0.1:1974";
+        let failing_hits = Arc::new(AtomicUsize::new(0));
+        let _failing = failing_backend("127.0.0.1:9878", failing_hits.clone());
+
+        let mut primary = destination_with_match("primary", "127.0.0.1:9878", None, false);
+        primary.circuit_breaker = Some(circuit_breaker(None));
+        let config = config_with_destinations(server_addr, true, vec![primary]);
+
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        for _ in 0..4 {
+            let err = ureq::get(&http_url(server_addr, "/primary/resource"))
+                .call()
+                .expect_err("expected 503");
+            expect_status(err, 503);
+        }
+
+        assert_eq!(failing_hits.load(Ordering::SeqCst), 2);
     }
 
-    #[async_trait]
-    impl ResponseMiddleware for TestGlobalResponseMiddleware {
-        async fn on_response(
-            &self,
-            _session: &mut Session,
-            _backend: &mut RequestContext,
-            response: &mut pingora::http::ResponseHeader,
-            _cardinal: Arc<CardinalContext>,
-        ) {
-            self.hits.fetch_add(1, Ordering::SeqCst);
-            let _ = response.insert_header(self.header_name, self.header_value);
+    fn flaky_backend(address: &str, hits: Arc<AtomicUsize>) -> TestHttpServer {
+        let get_hits = hits.clone();
+        spawn_backend(
+            address,
+            vec![
+                Route::new(Method::Get, "/resource", move |request| {
+                    let status = if get_hits.fetch_add(1, Ordering::SeqCst) == 0 {
+                        503
+                    } else {
+                        200
+                    };
+                    request
+                        .respond(Response::from_string("flaky").with_status_code(status))
+                        .unwrap();
+                }),
+                Route::new(Method::Post, "/resource", move |request| {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    request
+                        .respond(Response::from_string("flaky").with_status_code(503))
+                        .unwrap();
+                }),
+            ],
+        )
+    }
+
+    #[tokio::test]
+    async fn retry_on_status_retries_idempotent_requests() {
+        let server_addr = "127.0.0.1:1975";
+        let backend_addr = "127.0.0.1:9879";
+        let hits = Arc::new(AtomicUsize::new(0));
+        let _backend = flaky_backend(backend_addr, hits.clone());
+
+        let config = retry_test_config(
+            server_addr,
+            backend_addr,
+            DestinationRetry {
+                max_attempts: 3,
+                interval_ms: 10,
+                backoff_type: DestinationRetryBackoffType::None,
+                max_interval: None,
+                retry_on_status: vec![503],
+                ..Default::default()
+            },
+        );
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let response = ureq::get(&http_url(server_addr, "/retry/resource"))
+            .call()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        let err = ureq::post(&http_url(server_addr, "/retry/resource"))
+            .send("payload")
+            .expect_err("POST must not be retried");
+        expect_status(err, 503);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retried_request_body_is_counted_once_against_the_limit() {
+        let server_addr = "127.0.0.1:2006";
+        let backend_addr = "127.0.0.1:9908";
+        let hits = Arc::new(AtomicUsize::new(0));
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![Route::new(Method::Put, "/resource", {
+                let hits = hits.clone();
+                move |mut request| {
+                    let mut body = Vec::new();
+                    let _ = request.as_reader().read_to_end(&mut body);
+                    let status = if hits.fetch_add(1, Ordering::SeqCst) == 0 {
+                        503
+                    } else {
+                        200
+                    };
+                    let _ = request.respond(
+                        Response::from_string(body.len().to_string()).with_status_code(status),
+                    );
+                }
+            })],
+        );
+
+        let mut config = retry_test_config(
+            server_addr,
+            backend_addr,
+            DestinationRetry {
+                max_attempts: 3,
+                interval_ms: 10,
+                backoff_type: DestinationRetryBackoffType::None,
+                max_interval: None,
+                retry_on_status: vec![503],
+                ..Default::default()
+            },
+        );
+        config.destinations.get_mut("retry").unwrap().limits = Some(DestinationLimits {
+            max_request_body_bytes: Some(50),
+            max_response_body_bytes: None,
+        });
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let mut response = ureq::put(&http_url(server_addr, "/retry/resource"))
+            .send("p".repeat(30))
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body_mut().read_to_string().unwrap(), "30");
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn retry_on_timeout_retries_slow_upstream() {
+        let server_addr = "127.0.0.1:1976";
+        let backend_addr = "127.0.0.1:9880";
+        let hits = Arc::new(AtomicUsize::new(0));
+        let backend_hits = hits.clone();
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![Route::new(Method::Get, "/resource", move |request| {
+                if backend_hits.fetch_add(1, Ordering::SeqCst) == 0 {
+                    std::thread::sleep(Duration::from_millis(400));
+                }
+                let _ = request.respond(Response::from_string("eventually"));
+            })],
+        );
+
+        let mut destination = destination_with_match("retry", backend_addr, None, true);
+        destination.timeout = Some(DestinationTimeouts {
+            read: Some(300),
+            connect: None,
+            write: None,
+            idle: None,
+        });
+        destination.retry = Some(DestinationRetry {
+            max_attempts: 2,
+            interval_ms: 10,
+            backoff_type: DestinationRetryBackoffType::None,
+            max_interval: None,
+            retry_on: vec![DestinationRetryCondition::Timeout],
+            ..Default::default()
+        });
+        let config = config_with_destinations(server_addr, true, vec![destination]);
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let mut response = ureq::get(&http_url(server_addr, "/retry/resource"))
+            .call()
+            .unwrap();
+        assert_eq!(response.body_mut().read_to_string().unwrap(), "eventually");
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    fn test_certificate(name: &str, server_names: &[&str]) -> TlsCertificate {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/tests/certs");
+        TlsCertificate {
+            cert_path: dir
+                .join(format!("{name}.crt"))
+                .to_string_lossy()
+                .to_string(),
+            key_path: dir
+                .join(format!("{name}.key"))
+                .to_string_lossy()
+                .to_string(),
+            server_names: server_names.iter().map(|n| n.to_string()).collect(),
         }
     }
 
-    struct TestRequestShortCircuitMiddleware {
-        hits: Arc<AtomicUsize>,
+    fn tls_get(address: &str, server_name: &str, path: &str) -> (String, String) {
+        use pingora::tls::nid::Nid;
+        use pingora::tls::ssl::{SslConnector, SslMethod, SslVerifyMode};
+
+        let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
+        builder.set_verify(SslVerifyMode::NONE);
+        let connector = builder.build();
+
+        let tcp = TcpStream::connect(address).unwrap();
+        tcp.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut stream = connector.connect(server_name, tcp).unwrap();
+
+        let common_name = stream
+            .ssl()
+            .peer_certificate()
+            .and_then(|cert| {
+                cert.subject_name()
+                    .entries_by_nid(Nid::COMMONNAME)
+                    .next()
+                    .and_then(|entry| entry.data().as_utf8().ok())
+                    .map(|cn| cn.to_string())
+            })
+            .expect("peer certificate common name");
+
+        let request =
+            format!("GET {path} HTTP/1.1\r\nHost: {server_name}\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+
+        (common_name, response)
     }
 
-    #[async_trait]
-    impl RequestMiddleware for TestRequestShortCircuitMiddleware {
-        async fn on_request(
-            &self,
-            session: &mut Session,
-            _backend: &mut RequestContext,
-            _cardinal: Arc<CardinalContext>,
-        ) -> Result<MiddlewareResult, CardinalError> {
-            self.hits.fetch_add(1, Ordering::SeqCst);
-            let _ = session.respond_error(418).await;
+    #[tokio::test]
+    async fn tls_listener_selects_certificate_by_sni() {
+        let server_addr = "127.0.0.1:1977";
+        let backend_addr = "127.0.0.1:9881";
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![Route::new(Method::Get, "/resource", |request| {
+                let _ = request.respond(Response::from_string("secure"));
+            })],
+        );
+
+        let mut config = config_with_destinations(
+            server_addr,
+            true,
+            vec![destination_with_match("tls", backend_addr, None, true)],
+        );
+        config.server.tls = Some(ServerTls {
+            certificates: vec![
+                test_certificate("alpha", &["alpha.test", "*.alpha.test"]),
+                test_certificate("beta", &["beta.test", "*.beta.test"]),
+            ],
+            min_version: None,
+            alpn: TlsAlpn::H1,
+        });
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let (alpha_cn, response) = tokio::task::spawn_blocking(move || {
+            tls_get(server_addr, "api.alpha.test", "/tls/resource")
+        })
+        .await
+        .unwrap();
+        assert_eq!(alpha_cn, "alpha.test");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("secure"));
+
+        let (beta_cn, _) =
+            tokio::task::spawn_blocking(move || tls_get(server_addr, "beta.test", "/tls/resource"))
+                .await
+                .unwrap();
+        assert_eq!(beta_cn, "beta.test");
+
+        let (fallback_cn, _) = tokio::task::spawn_blocking(move || {
+            tls_get(server_addr, "unknown.test", "/tls/resource")
+        })
+        .await
+        .unwrap();
+        assert_eq!(fallback_cn, "alpha.test");
+    }
+
+    /// HTTPS backend requiring a client certificate issued by the test CA, answering with the
+    /// client certificate common name.
+    fn spawn_mtls_backend(address: &'static str) {
+        use pingora::tls::nid::Nid;
+        use pingora::tls::ssl::{SslAcceptor, SslFiletype, SslMethod, SslVerifyMode};
+
+        let certs = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/tests/certs");
+        let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+        builder
+            .set_certificate_chain_file(certs.join("upstream.crt"))
+            .unwrap();
+        builder
+            .set_private_key_file(certs.join("upstream.key"), SslFiletype::PEM)
+            .unwrap();
+        builder.set_ca_file(certs.join("upstream-ca.crt")).unwrap();
+        builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        let acceptor = Arc::new(builder.build());
+        let listener = std::net::TcpListener::bind(address).unwrap();
+
+        std::thread::spawn(move || {
+            for tcp in listener.incoming().flatten() {
+                let acceptor = acceptor.clone();
+                std::thread::spawn(move || {
+                    let Ok(mut stream) = acceptor.accept(tcp) else {
+                        return;
+                    };
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        match stream.read(&mut buf) {
+                            Ok(0) | Err(_) => return,
+                            Ok(read) => request.extend_from_slice(&buf[..read]),
+                        }
+                    }
+                    let client = stream
+                        .ssl()
+                        .peer_certificate()
+                        .and_then(|cert| {
+                            cert.subject_name()
+                                .entries_by_nid(Nid::COMMONNAME)
+                                .next()
+                                .and_then(|entry| entry.data().as_utf8().ok())
+                                .map(|cn| cn.to_string())
+                        })
+                        .unwrap_or_default();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{client}",
+                        client.len()
+                    );
+                    let _ = stream.write_all(response.as_bytes());
+                    let _ = stream.shutdown();
+                });
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn upstream_mtls_uses_destination_ca_and_client_certificate() {
+        let server_addr = "127.0.0.1:1991";
+        let backend_addr = "127.0.0.1:9894";
+        spawn_mtls_backend(backend_addr);
+
+        let certs = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/tests/certs");
+        let path = |name: &str| certs.join(name).to_string_lossy().to_string();
+        let upstream_tls = |client_certificate| DestinationTls {
+            ca_path: Some(path("upstream-ca.crt")),
+            insecure_skip_verify: false,
+            verify_hostname: true,
+            server_name: None,
+            client_certificate,
+        };
+        let url = format!("https://{backend_addr}");
+
+        let mut mtls = destination_with_match("mtls", &url, None, false);
+        mtls.sni = Some("upstream.test".into());
+        mtls.tls = Some(upstream_tls(Some(DestinationClientCertificate {
+            cert_path: path("gateway-client.crt"),
+            key_path: path("gateway-client.key"),
+        })));
+        let mut anonymous = destination_with_match("anonymous", &url, None, false);
+        anonymous.sni = Some("upstream.test".into());
+        anonymous.tls = Some(upstream_tls(None));
+        let mut wrong_name = destination_with_match("wrongname", &url, None, false);
+        wrong_name.tls = mtls.tls.clone();
+
+        let config = config_with_destinations(server_addr, true, vec![mtls, anonymous, wrong_name]);
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let status_and_body = |prefix: &str| {
+            let mut response = ureq::get(&http_url(server_addr, &format!("/{prefix}/resource")))
+                .config()
+                .http_status_as_error(false)
+                .build()
+                .call()
+                .unwrap();
+            let body = response.body_mut().read_to_string().unwrap_or_default();
+            (response.status().as_u16(), body)
+        };
+
+        assert_eq!(status_and_body("mtls"), (200, "gateway".to_string()));
+        assert_eq!(status_and_body("anonymous").0, 502);
+        // The certificate is issued to upstream.test, not to the connect address.
+        assert_eq!(status_and_body("wrongname").0, 502);
+    }
+
+    #[tokio::test]
+    async fn listeners_restrict_destinations_and_override_middleware() {
+        let server_addr = "127.0.0.1:1978";
+        let listener_addr = "127.0.0.1:1979";
+        let public_addr = "127.0.0.1:9882";
+        let private_addr = "127.0.0.1:9883";
+        let _public = spawn_backend(
+            public_addr,
+            vec![Route::new(Method::Get, "/resource", |request| {
+                let _ = request.respond(Response::from_string("public"));
+            })],
+        );
+        let _private = spawn_backend(
+            private_addr,
+            vec![Route::new(Method::Get, "/resource", |request| {
+                let _ = request.respond(Response::from_string("private"));
+            })],
+        );
+
+        let mut config = config_with_destinations(
+            server_addr,
+            true,
+            vec![
+                destination_with_match("public", public_addr, None, false),
+                destination_with_match("private", private_addr, None, false),
+            ],
+        );
+        config.server.global_request_middleware = vec!["CountingGlobal".into()];
+        config.server.listeners = vec![ServerListener {
+            name: "internal".into(),
+            address: listener_addr.into(),
+            tls: None,
+            proxy_protocol: false,
+            destinations: Some(vec!["public".into()]),
+            force_path_parameter: None,
+            global_request_middleware: Some(vec![]),
+            global_response_middleware: None,
+        }];
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let plugin_hits = hits.clone();
+        let cardinal = cardinal_with_plugin_factory(config, move |container| {
+            container.add_plugin(
+                "CountingGlobal".to_string(),
+                PluginHandler::Builtin(PluginBuiltInType::Inbound(Arc::new(
+                    TestGlobalRequestMiddleware {
+                        hits: plugin_hits.clone(),
+                    },
+                ))),
+            );
+        });
+        let _cardinal_thread = spawn_cardinal(cardinal);
+        wait_for_startup().await;
+
+        let mut response = ureq::get(&http_url(server_addr, "/private/resource"))
+            .call()
+            .unwrap();
+        assert_eq!(response.body_mut().read_to_string().unwrap(), "private");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let mut response = ureq::get(&http_url(listener_addr, "/public/resource"))
+            .call()
+            .unwrap();
+        assert_eq!(response.body_mut().read_to_string().unwrap(), "public");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let err = ureq::get(&http_url(listener_addr, "/private/resource"))
+            .call()
+            .unwrap_err();
+        expect_status(err, 404);
+    }
+
+    fn reload_test_config(server_addr: &str, backend_addr: &str) -> String {
+        format!(
+            r#"
+[server]
+address = "{server_addr}"
+force_path_parameter = true
+log_upstream_response = false
+global_request_middleware = []
+global_response_middleware = []
+reload = {{ watch = true, watch_interval_ms = 50, signal = false }}
+
+[destinations.reload]
+name = "reload"
+url = "{backend_addr}"
+"#
+        )
+    }
+
+    #[tokio::test]
+    async fn config_reload_swaps_destinations_and_rejects_invalid_config() {
+        let server_addr = "127.0.0.1:1980";
+        let first_addr = "127.0.0.1:9884";
+        let second_addr = "127.0.0.1:9885";
+        let _first = spawn_backend(
+            first_addr,
+            vec![Route::new(Method::Get, "/resource", |request| {
+                let _ = request.respond(Response::from_string("first"));
+            })],
+        );
+        let _second = spawn_backend(
+            second_addr,
+            vec![Route::new(Method::Get, "/resource", |request| {
+                let _ = request.respond(Response::from_string("second"));
+            })],
+        );
+
+        let config_path =
+            std::env::temp_dir().join(format!("cardinal-reload-{}.toml", std::process::id()));
+        std::fs::write(&config_path, reload_test_config(server_addr, first_addr)).unwrap();
+        let config_path_str = config_path.to_string_lossy().to_string();
+
+        let cardinal = Cardinal::from_paths(std::slice::from_ref(&config_path_str)).unwrap();
+        let reloader = cardinal.reloader();
+        let _cardinal_thread = spawn_cardinal(cardinal);
+        wait_for_startup().await;
+
+        let fetch = || {
+            ureq::get(&http_url(server_addr, "/reload/resource"))
+                .call()
+                .unwrap()
+                .body_mut()
+                .read_to_string()
+                .unwrap()
+        };
+        assert_eq!(fetch(), "first");
+
+        // File watch picks up the new destination url.
+        std::fs::write(&config_path, reload_test_config(server_addr, second_addr)).unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(fetch(), "second");
+
+        // A destination without url or upstreams fails validation and is not applied.
+        std::fs::write(
+            &config_path,
+            reload_test_config(server_addr, first_addr)
+                .replace(&format!("url = \"{first_addr}\""), ""),
+        )
+        .unwrap();
+        assert!(reloader.reload().await.is_err());
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(fetch(), "second");
+
+        // Explicit reload through the API.
+        std::fs::write(&config_path, reload_test_config(server_addr, first_addr)).unwrap();
+        reloader.reload().await.unwrap();
+        assert_eq!(fetch(), "first");
+
+        let _ = std::fs::remove_file(&config_path);
+    }
+
+    #[tokio::test]
+    async fn config_reload_keeps_upstream_health_and_breaker_state() {
+        let mut destination = destination_with_match("pool", "", None, true);
+        destination.upstreams = vec![upstream("127.0.0.1:9876"), upstream("127.0.0.1:9877")];
+        destination.circuit_breaker = Some(circuit_breaker(None));
+        let config = config_with_destinations("127.0.0.1:1999", true, vec![destination]);
+
+        let cardinal = Cardinal::new(config.clone());
+        let container = cardinal
+            .context()
+            .get::<DestinationContainer>()
+            .await
+            .unwrap();
+        let pool = container.get_destination("pool").unwrap();
+        pool.upstreams.endpoints()[0].set_healthy(false);
+        let breaker = pool.circuit_breaker.clone().unwrap();
+        for _ in 0..2 {
+            breaker.try_acquire().unwrap().failure();
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let mut next = config;
+        let reloaded = next.destinations.get_mut("pool").unwrap();
+        reloaded.upstreams.push(upstream("127.0.0.1:9878"));
+        let context = cardinal.reloader().apply(next).await.unwrap();
+
+        let container = context.get::<DestinationContainer>().await.unwrap();
+        let pool = container.get_destination("pool").unwrap();
+        let healthy = pool
+            .upstreams
+            .endpoints()
+            .iter()
+            .map(|endpoint| endpoint.is_healthy())
+            .collect::<Vec<_>>();
+        assert_eq!(healthy, [false, true, true]);
+        let breaker = pool.circuit_breaker.as_ref().unwrap();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.try_acquire().is_none());
+    }
+
+    #[tokio::test]
+    async fn admin_api_introspects_and_controls_gateway() {
+        let server_addr = "127.0.0.1:1981";
+        let admin_addr = "127.0.0.1:1982";
+        let backend_addr = "127.0.0.1:9886";
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![Route::new(Method::Get, "/resource", |request| {
+                let _ = request.respond(Response::from_string("admin"));
+            })],
+        );
+
+        let mut config = config_with_destinations(
+            server_addr,
+            true,
+            vec![destination_with_match("admin", backend_addr, None, false)],
+        );
+        config.server.admin = Some(ServerAdmin {
+            address: admin_addr.into(),
+            token: Some("secret".into()),
+            tls: None,
+            drain_timeout_ms: 1_000,
+        });
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let admin_get = |path: &str| {
+            ureq::get(&http_url(admin_addr, path))
+                .header("Authorization", "Bearer secret")
+                .call()
+        };
+        let admin_post = |path: &str| {
+            ureq::post(&http_url(admin_addr, path))
+                .header("Authorization", "Bearer secret")
+                .send_empty()
+        };
+
+        let err = ureq::get(&http_url(admin_addr, "/version"))
+            .call()
+            .unwrap_err();
+        expect_status(err, 401);
+
+        let version = admin_get("/version")
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        assert!(version.contains(env!("CARGO_PKG_VERSION")));
+
+        let config = admin_get("/config")
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        assert!(config.contains("<redacted>"));
+        assert!(!config.contains("secret"));
+
+        let destinations = admin_get("/destinations")
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        assert!(destinations.contains(backend_addr));
+
+        let plugins = admin_get("/plugins")
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        assert!(plugins.contains("RestrictedRouteMiddleware"));
+
+        // Not started from files, so there is nothing to reload.
+        let err = admin_post("/reload").unwrap_err();
+        expect_status(err, 422);
+
+        let drained = admin_post("/drain")
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        assert!(drained.contains("\"drained\": true"), "{drained}");
+        expect_status(admin_get("/ready").unwrap_err(), 503);
+        let response = ureq::get(&http_url(server_addr, "/admin/resource"))
+            .call()
+            .unwrap();
+        assert_eq!(
+            response
+                .headers()
+                .get("connection")
+                .and_then(|v| v.to_str().ok()),
+            Some("close")
+        );
+
+        admin_post("/resume").unwrap();
+        assert_eq!(admin_get("/ready").unwrap().status(), 200);
+    }
+
+    #[tokio::test]
+    async fn admin_api_without_token_is_read_only() {
+        let server_addr = "127.0.0.1:2004";
+        let admin_addr = "127.0.0.1:2005";
+        let mut config = config_with_destinations(
+            server_addr,
+            true,
+            vec![destination_with_match(
+                "admin",
+                "127.0.0.1:9907",
+                None,
+                false,
+            )],
+        );
+        config.server.admin = Some(ServerAdmin {
+            address: admin_addr.into(),
+            token: None,
+            tls: None,
+            drain_timeout_ms: 1_000,
+        });
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let version = ureq::get(&http_url(admin_addr, "/version")).call().unwrap();
+        assert_eq!(version.status(), 200);
+
+        for path in ["/drain", "/reload"] {
+            let err = ureq::post(&http_url(admin_addr, path))
+                .send_empty()
+                .unwrap_err();
+            expect_status(err, 403);
+        }
+        let err = ureq::delete(&http_url(admin_addr, "/cache/admin"))
+            .call()
+            .unwrap_err();
+        expect_status(err, 403);
+        assert_eq!(
+            ureq::get(&http_url(admin_addr, "/ready"))
+                .call()
+                .unwrap()
+                .status(),
+            200
+        );
+    }
+
+    #[tokio::test]
+    async fn metrics_listener_exposes_request_metrics() {
+        let server_addr = "127.0.0.1:1984";
+        let metrics_addr = "127.0.0.1:1983";
+        let backend_addr = "127.0.0.1:9887";
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![Route::new(Method::Get, "/resource", |request| {
+                let _ = request.respond(Response::from_string("metrics"));
+            })],
+        );
+
+        let mut config = config_with_destinations(
+            server_addr,
+            true,
+            vec![destination_with_match("metrics", backend_addr, None, false)],
+        );
+        config.server.global_request_middleware = vec!["RestrictedRouteMiddleware".into()];
+        config.server.metrics = Some(ServerMetrics {
+            address: metrics_addr.into(),
+            path: "/metrics".into(),
+        });
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let body = ureq::get(&http_url(server_addr, "/metrics/resource"))
+            .call()
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        assert_eq!(body, "metrics");
+
+        let mut response = ureq::get(&http_url(metrics_addr, "/metrics"))
+            .call()
+            .unwrap();
+        assert!(response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/plain")));
+        let exposition = response.body_mut().read_to_string().unwrap();
+        assert!(exposition.contains("cardinal_requests_total"));
+        assert!(exposition.contains(r#"destination="metrics""#));
+        assert!(exposition.contains("cardinal_upstream_response_duration_seconds"));
+        assert!(exposition.contains("cardinal_plugin_duration_seconds"));
+
+        let err = ureq::get(&http_url(metrics_addr, "/other"))
+            .call()
+            .unwrap_err();
+        expect_status(err, 404);
+    }
+
+    #[tokio::test]
+    async fn tracing_continues_incoming_trace_context() {
+        let server_addr = "127.0.0.1:1985";
+        let backend_addr = "127.0.0.1:9888";
+        let forwarded: Arc<Mutex<Vec<(String, String)>>> = Arc::new(Mutex::new(Vec::new()));
+        let forwarded_clone = forwarded.clone();
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![Route::new(Method::Get, "/resource", move |request| {
+                *forwarded_clone.lock().unwrap() = request
+                    .headers()
+                    .iter()
+                    .filter(|h| {
+                        let field = h.field.as_str().as_str();
+                        field.eq_ignore_ascii_case("traceparent")
+                            || field.eq_ignore_ascii_case("tracestate")
+                    })
+                    .map(|h| {
+                        (
+                            h.field.as_str().as_str().to_ascii_lowercase(),
+                            h.value.to_string(),
+                        )
+                    })
+                    .collect();
+                let _ = request.respond(Response::from_string("traced"));
+            })],
+        );
+
+        let mut config = config_with_destinations(
+            server_addr,
+            true,
+            vec![destination_with_match("traced", backend_addr, None, false)],
+        );
+        config.server.global_request_middleware = vec!["RestrictedRouteMiddleware".into()];
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let cardinal = Cardinal::builder(config)
+            .with_tracer_provider(provider)
+            .build();
+        let _cardinal_thread = spawn_cardinal(cardinal);
+        wait_for_startup().await;
+
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+        let body = ureq::get(&http_url(server_addr, "/traced/resource"))
+            .header(
+                "traceparent",
+                format!("00-{trace_id}-00f067aa0ba902b7-01").as_str(),
+            )
+            .header("tracestate", "vendor=value")
+            .call()
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        assert_eq!(body, "traced");
+
+        // The request span ends in the logging phase, after the response went out.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let spans = loop {
+            let spans = exporter.get_finished_spans().unwrap();
+            if spans.iter().any(|s| s.span_kind == SpanKind::Server) || Instant::now() > deadline {
+                break spans;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        };
+        let find = |name: &str| -> &SpanData {
+            spans
+                .iter()
+                .find(|s| s.name == name)
+                .unwrap_or_else(|| panic!("missing span {name}"))
+        };
+
+        let request = find("GET");
+        assert_eq!(request.span_kind, SpanKind::Server);
+        assert_eq!(request.span_context.trace_id().to_string(), trace_id);
+        assert_eq!(request.parent_span_id.to_string(), "00f067aa0ba902b7");
+
+        let request_span_id = request.span_context.span_id();
+        for name in [
+            "resolve_context",
+            "match_destination",
+            "middleware RestrictedRouteMiddleware",
+            "upstream GET",
+        ] {
+            assert_eq!(find(name).parent_span_id, request_span_id, "{name}");
+        }
+
+        let upstream = find("upstream GET");
+        assert_eq!(upstream.span_kind, SpanKind::Client);
+        let forwarded = forwarded.lock().unwrap().clone();
+        let header = |name: &str| {
+            forwarded
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        assert_eq!(
+            header("traceparent"),
+            Some(format!(
+                "00-{trace_id}-{}-01",
+                upstream.span_context.span_id()
+            ))
+        );
+        assert_eq!(header("tracestate").as_deref(), Some("vendor=value"));
+    }
+
+    #[tokio::test]
+    async fn access_log_writes_json_lines_to_file() {
+        let server_addr = "127.0.0.1:1986";
+        let backend_addr = "127.0.0.1:9889";
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![Route::new(Method::Get, "/resource", |request| {
+                let _ = request.respond(Response::from_string("logged"));
+            })],
+        );
+
+        let dir = std::env::temp_dir().join(format!("cardinal-access-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log_path = dir.join("access.log");
+        let _ = std::fs::remove_file(&log_path);
+
+        let mut config = config_with_destinations(
+            server_addr,
+            true,
+            vec![destination_with_match("logged", backend_addr, None, false)],
+        );
+        config.server.access_log = Some(ServerAccessLog {
+            format: AccessLogFormat::Json,
+            template: None,
+            sink: AccessLogSink::File {
+                path: log_path.display().to_string(),
+                max_bytes: None,
+                max_files: 1,
+            },
+        });
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let body = ureq::get(&http_url(server_addr, "/logged/resource?page=2"))
+            .header("x-request-id", "req-42")
+            .header("user-agent", "access-log-test")
+            .call()
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        assert_eq!(body, "logged");
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let contents = loop {
+            let contents = std::fs::read_to_string(&log_path).unwrap_or_default();
+            if !contents.is_empty() || Instant::now() > deadline {
+                break contents;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        };
+        let line: serde_json::Value =
+            serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+
+        assert_eq!(line["method"], "GET");
+        assert_eq!(line["path"], "/logged/resource?page=2");
+        assert_eq!(line["destination"], "logged");
+        assert_eq!(line["upstream"], backend_addr);
+        assert_eq!(line["status"], 200);
+        assert_eq!(line["bytes_out"], "logged".len());
+        assert_eq!(line["client_ip"], "127.0.0.1");
+        assert_eq!(line["request_id"], "req-42");
+        assert_eq!(line["user_agent"], "access-log-test");
+        assert_eq!(line["retries"], 0);
+        assert!(
+            line["duration_ms"].as_f64().unwrap() >= line["upstream_duration_ms"].as_f64().unwrap()
+        );
+        assert!(line["timestamp"].as_str().is_some_and(|ts| !ts.is_empty()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn request_id_is_forwarded_upstream_and_echoed() {
+        let server_addr = "127.0.0.1:1987";
+        let backend_addr = "127.0.0.1:9890";
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![Route::new(Method::Get, "/resource", |request| {
+                let seen = request
+                    .headers()
+                    .iter()
+                    .find(|h| h.field.equiv("x-correlation-id"))
+                    .map(|h| h.value.as_str().to_string())
+                    .unwrap_or_default();
+                let _ = request.respond(Response::from_string(seen));
+            })],
+        );
+
+        let mut config = config_with_destinations(
+            server_addr,
+            true,
+            vec![destination_with_match("ids", backend_addr, None, false)],
+        );
+        config.server.request_id = Some(ServerRequestId {
+            header: "x-correlation-id".into(),
+            format: RequestIdFormat::UuidV7,
+            trust_incoming: true,
+        });
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let mut response = ureq::get(&http_url(server_addr, "/ids/resource"))
+            .call()
+            .unwrap();
+        let echoed = response.headers()["x-correlation-id"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let forwarded = response.body_mut().read_to_string().unwrap();
+        assert_eq!(echoed.len(), 36);
+        assert_eq!(&echoed[14..15], "7");
+        assert_eq!(forwarded, echoed);
+
+        let mut response = ureq::get(&http_url(server_addr, "/ids/resource"))
+            .header("x-correlation-id", "client-supplied-1")
+            .call()
+            .unwrap();
+        assert_eq!(response.headers()["x-correlation-id"], "client-supplied-1");
+        assert_eq!(
+            response.body_mut().read_to_string().unwrap(),
+            "client-supplied-1"
+        );
+
+        // Responses generated by the gateway carry the ID as well.
+        let err = ureq::get(&http_url(server_addr, "/missing/resource"))
+            .header("x-correlation-id", "client-supplied-2")
+            .config()
+            .http_status_as_error(false)
+            .build()
+            .call()
+            .unwrap();
+        assert_eq!(err.status(), 404);
+        assert_eq!(err.headers()["x-correlation-id"], "client-supplied-2");
+    }
+
+    #[tokio::test]
+    async fn forwarding_headers_reach_upstream() {
+        let server_addr = "127.0.0.1:1988";
+        let backend_addr = "127.0.0.1:9891";
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![Route::new(Method::Get, "/resource", |request| {
+                let seen = [
+                    "x-forwarded-for",
+                    "x-forwarded-proto",
+                    "x-forwarded-port",
+                    "x-real-ip",
+                    "forwarded",
+                ]
+                .iter()
+                .map(|name| {
+                    request
+                        .headers()
+                        .iter()
+                        .find(|h| h.field.equiv(name))
+                        .map(|h| h.value.as_str().to_string())
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<_>>();
+                let _ = request.respond(Response::from_string(seen.join("\n")));
+            })],
+        );
+
+        let mut config = config_with_destinations(
+            server_addr,
+            true,
+            vec![destination_with_match("fwd", backend_addr, None, false)],
+        );
+        config.server.forwarding = Some(ServerForwarding {
+            x_forwarded_for: ForwardedForMode::Append,
+            x_forwarded_proto: true,
+            x_forwarded_port: true,
+            x_real_ip: true,
+            forwarded: true,
+            trusted_proxies: vec!["127.0.0.1".into()],
+        });
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let body = ureq::get(&http_url(server_addr, "/fwd/resource"))
+            .header("x-forwarded-for", "203.0.113.9")
+            .call()
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        let seen = body.lines().collect::<Vec<_>>();
+
+        assert_eq!(seen[0], "203.0.113.9, 127.0.0.1");
+        assert_eq!(seen[1], "http");
+        assert_eq!(seen[2], "1988");
+        assert_eq!(seen[3], "203.0.113.9");
+        assert_eq!(seen[4], "for=127.0.0.1;proto=http;host=\"127.0.0.1:1988\"");
+    }
+
+    #[tokio::test]
+    async fn proxy_protocol_client_reaches_forwarding_headers() {
+        let server_addr = "127.0.0.1:1989";
+        let backend_addr = "127.0.0.1:9892";
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![Route::new(Method::Get, "/resource", |request| {
+                let seen = ["x-forwarded-for", "x-forwarded-port", "x-real-ip"]
+                    .iter()
+                    .map(|name| {
+                        request
+                            .headers()
+                            .iter()
+                            .find(|h| h.field.equiv(name))
+                            .map(|h| h.value.as_str().to_string())
+                            .unwrap_or_default()
+                    })
+                    .collect::<Vec<_>>();
+                let _ = request.respond(Response::from_string(seen.join("\n")));
+            })],
+        );
+
+        let mut config = config_with_destinations(
+            server_addr,
+            true,
+            vec![destination_with_match("pp", backend_addr, None, false)],
+        );
+        config.server.proxy_protocol = true;
+        config.server.forwarding = Some(ServerForwarding {
+            x_forwarded_for: ForwardedForMode::Append,
+            x_forwarded_proto: true,
+            x_forwarded_port: true,
+            x_real_ip: true,
+            forwarded: false,
+            trusted_proxies: vec![],
+        });
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let mut stream = std::net::TcpStream::connect(server_addr).unwrap();
+        stream
+            .write_all(
+                b"PROXY TCP4 203.0.113.9 10.0.0.5 40000 8443\r\n\
+                  GET /pp/resource HTTP/1.1\r\nHost: 127.0.0.1:1989\r\nConnection: close\r\n\r\n",
+            )
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let body = response.split("\r\n\r\n").nth(1).unwrap_or_default();
+        let seen = body.lines().collect::<Vec<_>>();
+
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert_eq!(seen, ["203.0.113.9", "8443", "203.0.113.9"]);
+
+        let mut plain = std::net::TcpStream::connect(server_addr).unwrap();
+        plain
+            .write_all(b"GET /pp/resource HTTP/1.1\r\nHost: 127.0.0.1:1989\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        let _ = plain.read_to_string(&mut response);
+        assert!(response.is_empty());
+    }
+
+    #[tokio::test]
+    async fn destination_host_header_modes() {
+        let server_addr = "127.0.0.1:1990";
+        let backend_addr = "127.0.0.1:9893";
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![Route::new(Method::Get, "/host", |request| {
+                let host = request
+                    .headers()
+                    .iter()
+                    .find(|h| h.field.equiv("host"))
+                    .map(|h| h.value.as_str().to_string())
+                    .unwrap_or_default();
+                let _ = request.respond(Response::from_string(host));
+            })],
+        );
+
+        let mut preserved = destination_with_match("preserved", backend_addr, None, false);
+        preserved.preserve_host = true;
+        let mut explicit = destination_with_match("explicit", backend_addr, None, false);
+        explicit.host_header = Some("posts.internal".into());
+        let config = config_with_destinations(
+            server_addr,
+            true,
+            vec![
+                preserved,
+                explicit,
+                destination_with_match("origin", backend_addr, None, false),
+            ],
+        );
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let host_seen = |prefix: &str| {
+            ureq::get(&http_url(server_addr, &format!("/{prefix}/host")))
+                .header("host", "api.example.com")
+                .call()
+                .unwrap()
+                .body_mut()
+                .read_to_string()
+                .unwrap()
+        };
+
+        assert_eq!(host_seen("preserved"), "api.example.com");
+        assert_eq!(host_seen("explicit"), "posts.internal");
+        assert_eq!(host_seen("origin"), backend_addr);
+    }
+
+    #[tokio::test]
+    async fn routes_on_header_and_cookie_predicates() {
+        let server_addr = "127.0.0.1:1992";
+        let v2_addr = "127.0.0.1:9895";
+        let canary_addr = "127.0.0.1:9896";
+        let stable_addr = "127.0.0.1:9897";
+        let _backends = [
+            (v2_addr, "v2"),
+            (canary_addr, "canary"),
+            (stable_addr, "stable"),
+        ]
+        .map(|(addr, body)| {
+            spawn_backend(
+                addr,
+                vec![Route::new(Method::Get, "/orders", move |request| {
+                    let _ = request.respond(Response::from_string(body));
+                })],
+            )
+        });
+
+        let rule = |headers, cookies| {
+            single_match(DestinationMatch {
+                host: Some(DestinationMatchValue::String("shop.example.com".into())),
+                path_prefix: None,
+                path_exact: None,
+                methods: vec![],
+                headers,
+                query: vec![],
+                cookies,
+                split: None,
+            })
+        };
+        let field = |name: &str, value: &str| DestinationMatchField {
+            name: name.into(),
+            value: Some(DestinationMatchValue::String(value.into())),
+        };
+        let config = config_with_destinations(
+            server_addr,
+            false,
+            vec![
+                destination_with_match(
+                    "a_v2",
+                    v2_addr,
+                    rule(vec![field("X-Api-Version", "2")], vec![]),
+                    false,
+                ),
+                destination_with_match(
+                    "b_canary",
+                    canary_addr,
+                    rule(vec![], vec![field("beta", "1")]),
+                    false,
+                ),
+                destination_with_match("c_stable", stable_addr, rule(vec![], vec![]), false),
+            ],
+        );
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let routed = |header: Option<(&str, &str)>| {
+            let mut request =
+                ureq::get(&http_url(server_addr, "/orders")).header("Host", "shop.example.com");
+            if let Some((name, value)) = header {
+                request = request.header(name, value);
+            }
+            request.call().unwrap().body_mut().read_to_string().unwrap()
+        };
+
+        assert_eq!(routed(Some(("x-api-version", "2"))), "v2");
+        assert_eq!(routed(Some(("cookie", "session=abc; beta=1"))), "canary");
+        assert_eq!(routed(Some(("x-api-version", "1"))), "stable");
+        assert_eq!(routed(None), "stable");
+    }
+
+    #[tokio::test]
+    async fn splits_traffic_between_destinations_by_weight() {
+        let server_addr = "127.0.0.1:1993";
+        let stable_addr = "127.0.0.1:9898";
+        let canary_addr = "127.0.0.1:9899";
+        let _backends = [(stable_addr, "stable"), (canary_addr, "canary")].map(|(addr, body)| {
+            spawn_backend(
+                addr,
+                vec![Route::new(Method::Get, "/orders", move |request| {
+                    let _ = request.respond(Response::from_string(body));
+                })],
+            )
+        });
+
+        let split = |host: &str, sticky| DestinationMatch {
+            host: Some(DestinationMatchValue::String(host.into())),
+            path_prefix: None,
+            path_exact: None,
+            methods: vec![],
+            headers: vec![],
+            query: vec![],
+            cookies: vec![],
+            split: Some(DestinationSplit {
+                targets: vec![
+                    DestinationSplitTarget {
+                        destination: "stable".into(),
+                        weight: 3,
+                    },
+                    DestinationSplitTarget {
+                        destination: "canary".into(),
+                        weight: 1,
+                    },
+                ],
+                sticky,
+            }),
+        };
+        let config = config_with_destinations(
+            server_addr,
+            false,
+            vec![
+                destination_with_match(
+                    "shop",
+                    stable_addr,
+                    Some(vec![
+                        split("shop.example.com", None),
+                        split(
+                            "sticky.example.com",
+                            Some(LoadBalancerHashKey::Header("x-user".into())),
+                        ),
+                    ]),
+                    false,
+                ),
+                destination_with_match("stable", stable_addr, None, false),
+                destination_with_match("canary", canary_addr, None, false),
+            ],
+        );
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let routed = |host: &str, user: Option<&str>| {
+            let mut request = ureq::get(&http_url(server_addr, "/orders")).header("Host", host);
+            if let Some(user) = user {
+                request = request.header("x-user", user);
+            }
+            request.call().unwrap().body_mut().read_to_string().unwrap()
+        };
+
+        let canary = (0..8)
+            .filter(|_| routed("shop.example.com", None) == "canary")
+            .count();
+        assert_eq!(canary, 2);
+
+        let mut targets = HashSet::new();
+        for user in 0..20 {
+            let user = format!("user-{user}");
+            let first = routed("sticky.example.com", Some(&user));
+            assert_eq!(routed("sticky.example.com", Some(&user)), first);
+            targets.insert(first);
+        }
+        assert_eq!(targets.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn mirrors_requests_to_shadow_destination() {
+        let server_addr = "127.0.0.1:1994";
+        let primary_addr = "127.0.0.1:9900";
+        let shadow_addr = "127.0.0.1:9901";
+        let _primary = spawn_backend(
+            primary_addr,
+            vec![Method::Get, Method::Post]
+                .into_iter()
+                .map(|method| {
+                    Route::new(method, "/items", |request| {
+                        let _ = request.respond(Response::from_string("primary"));
+                    })
+                })
+                .collect(),
+        );
+        let mirrored = Arc::new(Mutex::new(Vec::new()));
+        let shadow_mirrored = mirrored.clone();
+        let _shadow = spawn_backend(
+            shadow_addr,
+            vec![Method::Get, Method::Post]
+                .into_iter()
+                .map(|method| {
+                    let shadow_mirrored = shadow_mirrored.clone();
+                    Route::new(method, "/items", move |mut request| {
+                        let host = request
+                            .headers()
+                            .iter()
+                            .find(|h| h.field.equiv("Host"))
+                            .map(|h| h.value.to_string());
+                        let mut body = String::new();
+                        let _ = request.as_reader().read_to_string(&mut body);
+                        shadow_mirrored.lock().unwrap().push((host, body));
+                        let _ =
+                            request.respond(Response::from_string("shadow").with_status_code(500));
+                    })
+                })
+                .collect(),
+        );
+
+        let mut orders = destination_with_match("orders", primary_addr, None, true);
+        orders.mirror = Some(DestinationMirror {
+            destination: "orders_shadow".into(),
+            percentage: 100,
+            max_body_bytes: 16,
+        });
+        let config = config_with_destinations(
+            server_addr,
+            false,
+            vec![
+                orders,
+                destination_with_match("orders_shadow", shadow_addr, None, false),
+            ],
+        );
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let post = |body: &str| {
+            ureq::post(&http_url(server_addr, "/items"))
+                .send(body)
+                .unwrap()
+                .body_mut()
+                .read_to_string()
+                .unwrap()
+        };
+        assert_eq!(post("small order"), "primary");
+        assert_eq!(post("an order too large to mirror"), "primary");
+        let response = ureq::get(&http_url(server_addr, "/items"))
+            .call()
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        assert_eq!(response, "primary");
+
+        for _ in 0..50 {
+            if mirrored.lock().unwrap().len() >= 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let mut mirrored = mirrored.lock().unwrap().clone();
+        mirrored.sort();
+        let shadow_host = Some(shadow_addr.to_string());
+        assert_eq!(
+            mirrored,
+            vec![
+                (shadow_host.clone(), String::new()),
+                (shadow_host, "small order".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn caches_responses_until_purged() {
+        let server_addr = "127.0.0.1:1995";
+        let admin_addr = "127.0.0.1:1996";
+        let backend_addr = "127.0.0.1:9902";
+        let hits = Arc::new(AtomicUsize::new(0));
+        let cached_hits = hits.clone();
+        let private_hits = hits.clone();
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![
+                Route::new(Method::Get, "/items", move |request| {
+                    let n = cached_hits.fetch_add(1, Ordering::SeqCst);
+                    let header =
+                        tiny_http::Header::from_bytes(&b"Cache-Control"[..], &b"max-age=60"[..])
+                            .unwrap();
+                    let _ = request
+                        .respond(Response::from_string(format!("items-{n}")).with_header(header));
+                }),
+                Route::new(Method::Get, "/private", move |request| {
+                    let n = private_hits.fetch_add(1, Ordering::SeqCst);
+                    let header =
+                        tiny_http::Header::from_bytes(&b"Cache-Control"[..], &b"no-store"[..])
+                            .unwrap();
+                    let _ = request
+                        .respond(Response::from_string(format!("private-{n}")).with_header(header));
+                }),
+            ],
+        );
+
+        let mut catalog = destination_with_match("catalog", backend_addr, None, true);
+        catalog.cache = Some(DestinationCache::default());
+        let mut config = config_with_destinations(server_addr, false, vec![catalog]);
+        config.server.admin = Some(ServerAdmin {
+            address: admin_addr.into(),
+            token: Some("secret".into()),
+            tls: None,
+            drain_timeout_ms: 30_000,
+        });
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let get = |path: &str| {
+            ureq::get(&http_url(server_addr, path))
+                .call()
+                .unwrap()
+                .body_mut()
+                .read_to_string()
+                .unwrap()
+        };
+        assert_eq!(get("/items"), "items-0");
+        assert_eq!(get("/items"), "items-0");
+        assert_eq!(get("/items?page=2"), "items-1");
+        assert_eq!(get("/items?page=2"), "items-1");
+        assert_eq!(get("/private"), "private-2");
+        assert_eq!(get("/private"), "private-3");
+
+        let purged = ureq::delete(&http_url(admin_addr, "/cache/catalog?path=/items"))
+            .header("Authorization", "Bearer secret")
+            .call()
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        assert!(purged.contains("\"purged\": 2"), "{purged}");
+        assert_eq!(get("/items"), "items-4");
+        assert_eq!(get("/items"), "items-4");
+
+        let err = ureq::delete(&http_url(admin_addr, "/cache/unknown"))
+            .header("Authorization", "Bearer secret")
+            .call()
+            .unwrap_err();
+        expect_status(err, 404);
+    }
+
+    #[tokio::test]
+    async fn compresses_qualifying_responses() {
+        let server_addr = "127.0.0.1:1997";
+        let backend_addr = "127.0.0.1:9903";
+        let payload = format!("[{}]", vec!["{\"id\":1,\"name\":\"item\"}"; 200].join(","));
+        let route = |path: &'static str, content_type: &'static str, body: String| {
+            Route::new(Method::Get, path, move |request| {
+                let header =
+                    tiny_http::Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes())
+                        .unwrap();
+                let _ = request.respond(Response::from_data(body.clone()).with_header(header));
+            })
+        };
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![
+                route("/data", "application/json", payload.clone()),
+                route("/small", "text/plain", "tiny".into()),
+                route("/image", "image/png", payload.clone()),
+            ],
+        );
+
+        let mut catalog = destination_with_match("catalog", backend_addr, None, true);
+        catalog.compression = Some(DestinationCompression::default());
+        let config = config_with_destinations(server_addr, false, vec![catalog]);
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let get = |path: &str, accept_encoding: &str| {
+            let mut response = ureq::get(&http_url(server_addr, path))
+                .header("Accept-Encoding", accept_encoding)
+                .call()
+                .unwrap();
+            let header = |name: &str| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string)
+            };
+            let encoding = header("content-encoding");
+            let vary = header("vary");
+            let body = response.body_mut().read_to_vec().unwrap();
+            (encoding, vary, body)
+        };
+
+        let (encoding, vary, body) = get("/data", "gzip;q=0.5, br");
+        assert_eq!(encoding.as_deref(), Some("br"));
+        assert!(vary.is_some_and(|vary| vary.to_ascii_lowercase().contains("accept-encoding")));
+        assert!(body.len() < payload.len() / 4, "{} bytes", body.len());
+
+        let (encoding, _, body) = get("/data", "zstd");
+        assert_eq!(encoding.as_deref(), Some("zstd"));
+        assert!(body.len() < payload.len() / 4, "{} bytes", body.len());
+
+        let (encoding, _, body) = get("/data", "identity");
+        assert_eq!(encoding, None);
+        assert_eq!(body, payload.as_bytes());
+
+        let (encoding, _, body) = get("/small", "br");
+        assert_eq!(encoding, None);
+        assert_eq!(body, b"tiny");
+
+        let (encoding, _, body) = get("/image", "br");
+        assert_eq!(encoding, None);
+        assert_eq!(body, payload.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn enforces_body_and_header_limits() {
+        let server_addr = "127.0.0.1:1998";
+        let backend_addr = "127.0.0.1:9904";
+        let echo_length = |path: &'static str| {
+            Route::new(Method::Post, path, |mut request| {
+                let mut body = Vec::new();
+                let _ = request.as_reader().read_to_end(&mut body);
+                let _ = request.respond(Response::from_string(body.len().to_string()));
+            })
+        };
+        let _backend = spawn_backend(
+            backend_addr,
+            vec![
+                echo_length("/notes"),
+                echo_length("/uploads"),
+                Route::new(Method::Get, "/report", |request| {
+                    let _ = request.respond(Response::from_string("x".repeat(2048)));
+                }),
+                Route::new(Method::Get, "/stream", |request| {
+                    let body = std::io::Cursor::new(vec![b'x'; 128 * 1024]);
+                    let _ = request.respond(Response::new(200.into(), vec![], body, None, None));
+                }),
+            ],
+        );
+
+        let mut files = destination_with_match("files", backend_addr, None, true);
+        files.routes = [
+            ("POST", "/notes", None),
+            ("POST", "/uploads", Some(64)),
+            ("GET", "/report", None),
+            ("GET", "/stream", None),
+        ]
+        .map(
+            |(method, path, max_request_body_bytes)| cardinal_config::Route {
+                path: path.into(),
+                method: method.into(),
+                max_request_body_bytes,
+            },
+        )
+        .to_vec();
+        files.limits = Some(DestinationLimits {
+            max_request_body_bytes: Some(16),
+            max_response_body_bytes: None,
+        });
+        let mut config = config_with_destinations(server_addr, false, vec![files]);
+        config.server.limits = Some(ServerLimits {
+            max_response_body_bytes: Some(1024),
+            max_request_header_bytes: Some(512),
+            ..ServerLimits::default()
+        });
+        let _cardinal_thread = spawn_cardinal(Cardinal::new(config));
+        wait_for_startup().await;
+
+        let post = |path: &str, body: &str| ureq::post(&http_url(server_addr, path)).send(body);
+        let read = |mut response: ureq::http::Response<ureq::Body>| {
+            response.body_mut().read_to_string().unwrap()
+        };
+        assert_eq!(read(post("/notes", "small note").unwrap()), "10");
+        expect_status(post("/notes", "a note over the limit").unwrap_err(), 413);
+        assert_eq!(read(post("/uploads", &"u".repeat(64)).unwrap()), "64");
+        expect_status(post("/uploads", &"u".repeat(65)).unwrap_err(), 413);
+
+        let mut stream = TcpStream::connect(server_addr).unwrap();
+        stream
+            .write_all(
+                b"POST /notes HTTP/1.1\r\nHost: 127.0.0.1:1998\r\nTransfer-Encoding: chunked\r\n\
+                  Connection: close\r\n\r\n20\r\nchunked note over the body limit\r\n0\r\n\r\n",
+            )
+            .unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        assert!(response.starts_with("HTTP/1.1 413"), "{response}");
+
+        let err = ureq::get(&http_url(server_addr, "/report"))
+            .header("X-Padding", "p".repeat(600))
+            .call()
+            .unwrap_err();
+        expect_status(err, 431);
+
+        let err = ureq::get(&http_url(server_addr, "/report"))
+            .call()
+            .unwrap_err();
+        expect_status(err, 502);
+        // Cut off with a 502 when caught before the header went out, mid-body otherwise.
+        match ureq::get(&http_url(server_addr, "/stream")).call() {
+            Ok(mut streamed) => assert!(streamed.body_mut().read_to_string().is_err()),
+            Err(err) => expect_status(err, 502),
+        }
+    }
+
+    fn read_http_head(stream: &mut TcpStream) -> String {
+        let mut head = Vec::new();
+        let mut byte = [0u8; 1];
+        while !head.ends_with(b"\r\n\r\n") && stream.read_exact(&mut byte).is_ok() {
+            head.push(byte[0]);
+        }
+        String::from_utf8_lossy(&head).into_owned()
+    }
+
+    /// Reads one WebSocket frame as `(opcode, unmasked payload)`, `None` once the stream ends.
+    fn read_ws_frame(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+        let mut head = [0u8; 2];
+        stream.read_exact(&mut head).ok()?;
+        let len = match head[1] & 0x7f {
+            126 => {
+                let mut len = [0u8; 2];
+                stream.read_exact(&mut len).ok()?;
+                u16::from_be_bytes(len) as usize
+            }
+            127 => {
+                let mut len = [0u8; 8];
+                stream.read_exact(&mut len).ok()?;
+                u64::from_be_bytes(len) as usize
+            }
+            len => len as usize,
+        };
+        let mut mask = [0u8; 4];
+        if head[1] & 0x80 != 0 {
+            stream.read_exact(&mut mask).ok()?;
+        }
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).ok()?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        Some((head[0] & 0x0f, payload))
+    }
+
+    /// Writes a final frame, masked as clients must.
+    fn write_ws_frame(stream: &mut TcpStream, opcode: u8, payload: &[u8], masked: bool) {
+        let mask_bit = if masked { 0x80 } else { 0 };
+        let mut frame = vec![0x80 | opcode];
+        if payload.len() < 126 {
+            frame.push(mask_bit | payload.len() as u8);
+        } else {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        }
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        if masked {
+            frame.extend_from_slice(&mask);
+            frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        } else {
+            frame.extend_from_slice(payload);
+        }
+        let _ = stream.write_all(&frame);
+    }
+
+    /// Echoes every WebSocket message back until the client closes.
+    fn spawn_websocket_echo(address: &'static str, handshakes: Arc<AtomicUsize>) {
+        use base64::Engine;
+        use pingora::tls::hash::{hash, MessageDigest};
+
+        let listener = std::net::TcpListener::bind(address).unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let handshakes = handshakes.clone();
+                std::thread::spawn(move || {
+                    let mut stream = stream;
+                    let head = read_http_head(&mut stream);
+                    let Some(key) = head.lines().find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("sec-websocket-key")
+                            .then(|| value.trim().to_string())
+                    }) else {
+                        return;
+                    };
+                    handshakes.fetch_add(1, Ordering::SeqCst);
+                    let digest = hash(
+                        MessageDigest::sha1(),
+                        format!("{key}258EAFA5-E914-47DA-95CA-C5AB0DC85B11").as_bytes(),
+                    )
+                    .unwrap();
+                    let accept = base64::engine::general_purpose::STANDARD.encode(digest);
+                    let response = format!(
+                        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                         Connection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+                    );
+                    if stream.write_all(response.as_bytes()).is_err() {
+                        return;
+                    }
+                    while let Some((opcode, payload)) = read_ws_frame(&mut stream) {
+                        write_ws_frame(&mut stream, opcode, &payload, false);
+                        if opcode == 0x8 {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    fn open_websocket(address: &str, path: &str, token: Option<&str>) -> (TcpStream, String) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let token = token
+            .map(|token| format!("X-Ws-Token: {token}\r\n"))
+            .unwrap_or_default();
+        let request = format!(
+            "GET {path} HTTP/1.1\r\nHost: {address}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n{token}\r\n"
+        );
+        stream.write_all(request.as_bytes()).unwrap();
+        let head = read_http_head(&mut stream);
+        (stream, head)
+    }
+
+    #[tokio::test]
+    async fn proxies_websocket_upgrades_with_limits() {
+        let server_addr = "127.0.0.1:1999";
+        let backend_addr = "127.0.0.1:9905";
+        let handshakes = Arc::new(AtomicUsize::new(0));
+        spawn_websocket_echo(backend_addr, handshakes.clone());
+
+        let mut chat = destination_with_match("chat", backend_addr, None, true);
+        chat.websocket = Some(DestinationWebSocket {
+            idle_timeout_ms: Some(500),
+            max_frame_bytes: None,
+            max_message_bytes: Some(64),
+        });
+        let mut config = config_with_destinations(server_addr, false, vec![chat]);
+        config.server.global_request_middleware = vec!["WebSocketToken".into()];
+        let cardinal = cardinal_with_plugin_factory(config, |container| {
+            container.add_plugin(
+                "WebSocketToken".into(),
+                PluginHandler::Builtin(PluginBuiltInType::Inbound(Arc::new(
+                    TestWebSocketTokenMiddleware,
+                ))),
+            );
+        });
+        let _cardinal_thread = spawn_cardinal(cardinal);
+        wait_for_startup().await;
+
+        let (_rejected, head) = open_websocket(server_addr, "/chat", None);
+        assert!(head.starts_with("HTTP/1.1 403"), "{head}");
+        assert_eq!(handshakes.load(Ordering::SeqCst), 0);
+
+        let (mut stream, head) = open_websocket(server_addr, "/chat", Some("letmein"));
+        assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+        assert!(head.contains("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="), "{head}");
+        let labels = ["chat", "websocket"];
+        assert_eq!(
+            cardinal_proxy::metrics::UPGRADED_CONNECTIONS
+                .with_label_values(&labels)
+                .get(),
+            1
+        );
+        write_ws_frame(&mut stream, 0x1, b"hello", true);
+        assert_eq!(read_ws_frame(&mut stream), Some((0x1, b"hello".to_vec())));
+        write_ws_frame(&mut stream, 0x1, &[b'x'; 65], true);
+        assert_eq!(read_ws_frame(&mut stream), None);
+
+        let (mut idle, head) = open_websocket(server_addr, "/chat", Some("letmein"));
+        assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+        let started = Instant::now();
+        assert_eq!(read_ws_frame(&mut idle), None);
+        assert!(started.elapsed() < Duration::from_secs(3));
+
+        assert_eq!(handshakes.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            cardinal_proxy::metrics::UPGRADED_CONNECTIONS_TOTAL
+                .with_label_values(&labels)
+                .get(),
+            2
+        );
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while cardinal_proxy::metrics::UPGRADED_CONNECTIONS
+            .with_label_values(&labels)
+            .get()
+            > 0
+            && Instant::now() < deadline
+        {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(
+            cardinal_proxy::metrics::UPGRADED_CONNECTIONS
+                .with_label_values(&labels)
+                .get(),
+            0
+        );
+    }
+
+    /// Answers gRPC calls over h2c with the request message and an OK status, recording the
+    /// `content-type` and `te` headers the upstream received.
+    fn spawn_grpc_backend(address: &'static str, seen: Arc<Mutex<Vec<String>>>) {
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::bind(address).await.unwrap();
+                loop {
+                    let Ok((socket, _)) = listener.accept().await else {
+                        continue;
+                    };
+                    let seen = seen.clone();
+                    tokio::spawn(async move {
+                        let Ok(mut connection) = h2::server::handshake(socket).await else {
+                            return;
+                        };
+                        while let Some(Ok((request, mut respond))) = connection.accept().await {
+                            let seen = seen.clone();
+                            tokio::spawn(async move {
+                                let header = |name: &str| {
+                                    request
+                                        .headers()
+                                        .get(name)
+                                        .map_or("-", |value| value.to_str().unwrap_or("-"))
+                                        .to_string()
+                                };
+                                seen.lock().unwrap().push(format!(
+                                    "{} {} te={}",
+                                    request.uri().path(),
+                                    header("content-type"),
+                                    header("te")
+                                ));
+                                let mut body = request.into_body();
+                                let mut message = Vec::new();
+                                while let Some(Ok(chunk)) = body.data().await {
+                                    let _ = body.flow_control().release_capacity(chunk.len());
+                                    message.extend_from_slice(&chunk);
+                                }
+
+                                let response = http::Response::builder()
+                                    .status(200)
+                                    .header("content-type", "application/grpc+proto")
+                                    .body(())
+                                    .unwrap();
+                                let Ok(mut stream) = respond.send_response(response, false) else {
+                                    return;
+                                };
+                                let _ = stream.send_data(message.into(), false);
+                                let mut trailers = http::HeaderMap::new();
+                                trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                                let _ = stream.send_trailers(trailers);
+                            });
+                        }
+                    });
+                }
+            });
+        });
+    }
+
+    /// Length-prefixed gRPC message frame.
+    fn grpc_frame(message: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0];
+        frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        frame.extend_from_slice(message);
+        frame
+    }
+
+    /// Sends a gRPC-Web call over HTTP/1.1 and returns the response head and decoded body.
+    fn grpc_web_call(
+        address: &str,
+        content_type: &str,
+        body: &[u8],
+        token: bool,
+    ) -> (String, Vec<u8>) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let token = if token {
+            "X-Grpc-Token: letmein\r\n"
+        } else {
+            ""
+        };
+        let head = format!(
+            "POST /helloworld.Greeter/SayHello HTTP/1.1\r\nHost: {address}\r\n\
+             Content-Type: {content_type}\r\nContent-Length: {}\r\n{token}Connection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).unwrap();
+        stream.write_all(body).unwrap();
+
+        let head = read_http_head(&mut stream);
+        let mut raw = Vec::new();
+        let _ = stream.read_to_end(&mut raw);
+        if !head
+            .to_ascii_lowercase()
+            .contains("transfer-encoding: chunked")
+        {
+            return (head, raw);
+        }
+        let mut body = Vec::new();
+        let mut rest = &raw[..];
+        while let Some(line_end) = rest.windows(2).position(|window| window == b"\r\n") {
+            let size = std::str::from_utf8(&rest[..line_end]).unwrap();
+            let size = usize::from_str_radix(size.trim(), 16).unwrap();
+            if size == 0 {
+                break;
+            }
+            body.extend_from_slice(&rest[line_end + 2..line_end + 2 + size]);
+            rest = &rest[line_end + 4 + size..];
+        }
+        (head, body)
+    }
+
+    #[tokio::test]
+    async fn proxies_grpc_and_translates_grpc_web() {
+        use base64::Engine;
+
+        let server_addr = "127.0.0.1:2000";
+        let backend_addr = "127.0.0.1:9906";
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        spawn_grpc_backend(backend_addr, seen.clone());
+
+        let mut greeter = destination_with_match("greeter", backend_addr, None, true);
+        greeter.grpc = Some(DestinationGrpc {
+            services: vec!["helloworld.Greeter".into()],
+        });
+        let mut config = config_with_destinations(server_addr, true, vec![greeter]);
+        config.server.h2c = true;
+        config.server.global_request_middleware = vec!["GrpcToken".into()];
+        let cardinal = cardinal_with_plugin_factory(config, |container| {
+            container.add_plugin(
+                "GrpcToken".into(),
+                PluginHandler::Builtin(PluginBuiltInType::Inbound(Arc::new(
+                    TestGrpcTokenMiddleware,
+                ))),
+            );
+        });
+        let _cardinal_thread = spawn_cardinal(cardinal);
+        wait_for_startup().await;
+
+        let message = grpc_frame(b"hello");
+        let trailer_frame = b"\x80\x00\x00\x00\x0fgrpc-status:0\r\n";
+        let (head, body) = grpc_web_call(server_addr, "application/grpc-web+proto", &message, true);
+        assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+        assert!(head.contains("application/grpc-web+proto"), "{head}");
+        assert_eq!(body, [&message[..], trailer_frame].concat());
+
+        let base64 = base64::engine::general_purpose::STANDARD;
+        let (head, body) = grpc_web_call(
+            server_addr,
+            "application/grpc-web-text",
+            base64.encode(&message).as_bytes(),
+            true,
+        );
+        assert!(head.contains("application/grpc-web-text"), "{head}");
+        // Encoded chunk by chunk, each group of four characters decodes on its own.
+        let decoded = body
+            .chunks(4)
+            .flat_map(|group| base64.decode(group).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(decoded, [&message[..], trailer_frame].concat());
+
+        let (head, body) =
+            grpc_web_call(server_addr, "application/grpc-web+proto", &message, false);
+        assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+        assert!(head.contains("grpc-status: 7"), "{head}");
+        assert!(head.contains("grpc-message: Forbidden"), "{head}");
+        assert!(body.is_empty());
+
+        let socket = tokio::net::TcpStream::connect(server_addr).await.unwrap();
+        let (client, connection) = h2::client::handshake(socket).await.unwrap();
+        tokio::spawn(connection);
+        let mut client = client.ready().await.unwrap();
+        let call = |token: bool| {
+            let mut request = http::Request::builder()
+                .method("POST")
+                .uri(format!("http://{server_addr}/helloworld.Greeter/SayHello"))
+                .header("content-type", "application/grpc")
+                .header("te", "trailers");
+            if token {
+                request = request.header("x-grpc-token", "letmein");
+            }
+            request.body(()).unwrap()
+        };
+        let (response, mut send) = client.send_request(call(true), false).unwrap();
+        send.send_data(message.clone().into(), true).unwrap();
+        let mut response = response.await.unwrap();
+        assert_eq!(response.status(), 200);
+        let mut received = Vec::new();
+        while let Some(chunk) = response.body_mut().data().await {
+            received.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(received, message);
+        let trailers = response.body_mut().trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["grpc-status"], "0");
+
+        let (response, _) = client.send_request(call(false), true).unwrap();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["grpc-status"], "7");
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                "/helloworld.Greeter/SayHello application/grpc+proto te=trailers".to_string(),
+                "/helloworld.Greeter/SayHello application/grpc te=trailers".to_string(),
+                "/helloworld.Greeter/SayHello application/grpc te=trailers".to_string()
+            ]
+        );
+        let calls = |status: &str| {
+            cardinal_proxy::metrics::GRPC_REQUESTS_TOTAL
+                .with_label_values(&["greeter", "helloworld.Greeter", "SayHello", status])
+                .get()
+        };
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while calls("0") + calls("7") < 5 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(calls("0"), 3);
+        assert_eq!(calls("7"), 2);
+    }
+
+    fn spawn_cardinal(cardinal: Cardinal) -> JoinHandle<()> {
+        std::thread::spawn(move || {
+            cardinal.run().unwrap();
+        })
+    }
+
+    struct DenyingPluginExecutor {
+        can_run_calls: Arc<AtomicUsize>,
+    }
+
+    impl DenyingPluginExecutor {
+        fn new(can_run_calls: Arc<AtomicUsize>) -> Self {
+            Self { can_run_calls }
+        }
+    }
+
+    #[async_trait]
+    impl CardinalPluginExecutor for DenyingPluginExecutor {
+        async fn can_run_plugin(
+            &self,
+            _binding_id: &str,
+            _session: &mut Session,
+            _req_ctx: &mut RequestContext,
+        ) -> Result<bool, pingora::BError> {
+            self.can_run_calls.fetch_add(1, Ordering::SeqCst);
+            Ok(false)
+        }
+    }
+
+    struct TestContextProvider {
+        context: Option<Arc<CardinalContext>>,
+        resolve_count: Arc<AtomicUsize>,
+    }
+
+    impl TestContextProvider {
+        fn new(context: Option<Arc<CardinalContext>>, resolve_count: Arc<AtomicUsize>) -> Self {
+            Self {
+                context,
+                resolve_count,
+            }
+        }
+    }
+
+    impl CardinalContextProvider for TestContextProvider {
+        fn resolve(&self, _session: &Session, _ctx: &mut ReqCtx) -> Option<Arc<CardinalContext>> {
+            self.resolve_count.fetch_add(1, Ordering::SeqCst);
+            self.context.as_ref().map(Arc::clone)
+        }
+    }
+
+    struct TestGlobalRequestMiddleware {
+        hits: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl RequestMiddleware for TestGlobalRequestMiddleware {
+        async fn on_request(
+            &self,
+            _session: &mut Session,
+            _backend: &mut RequestContext,
+            _cardinal: Arc<CardinalContext>,
+        ) -> Result<MiddlewareResult, CardinalError> {
+            self.hits.fetch_add(1, Ordering::SeqCst);
+            Ok(MiddlewareResult::Continue(HashMap::new()))
+        }
+    }
+
+    struct TestGlobalResponseMiddleware {
+        hits: Arc<AtomicUsize>,
+        header_name: &'static str,
+        header_value: &'static str,
+    }
+
+    #[async_trait]
+    impl ResponseMiddleware for TestGlobalResponseMiddleware {
+        async fn on_response(
+            &self,
+            _session: &mut Session,
+            _backend: &mut RequestContext,
+            response: &mut pingora::http::ResponseHeader,
+            _cardinal: Arc<CardinalContext>,
+        ) {
+            self.hits.fetch_add(1, Ordering::SeqCst);
+            let _ = response.insert_header(self.header_name, self.header_value);
+        }
+    }
+
+    struct TestRequestShortCircuitMiddleware {
+        hits: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl RequestMiddleware for TestRequestShortCircuitMiddleware {
+        async fn on_request(
+            &self,
+            session: &mut Session,
+            _backend: &mut RequestContext,
+            _cardinal: Arc<CardinalContext>,
+        ) -> Result<MiddlewareResult, CardinalError> {
+            self.hits.fetch_add(1, Ordering::SeqCst);
+            let _ = session.respond_error(418).await;
+            Ok(MiddlewareResult::Responded)
+        }
+    }
+
+    struct TestWebSocketTokenMiddleware;
+
+    #[async_trait]
+    impl RequestMiddleware for TestWebSocketTokenMiddleware {
+        async fn on_request(
+            &self,
+            session: &mut Session,
+            _backend: &mut RequestContext,
+            _cardinal: Arc<CardinalContext>,
+        ) -> Result<MiddlewareResult, CardinalError> {
+            if session.req_header().headers.contains_key("x-ws-token") {
+                return Ok(MiddlewareResult::Continue(HashMap::new()));
+            }
+            let _ = session.respond_error(403).await;
+            Ok(MiddlewareResult::Responded)
+        }
+    }
+
+    struct TestGrpcTokenMiddleware;
+
+    #[async_trait]
+    impl RequestMiddleware for TestGrpcTokenMiddleware {
+        async fn on_request(
+            &self,
+            session: &mut Session,
+            _backend: &mut RequestContext,
+            _cardinal: Arc<CardinalContext>,
+        ) -> Result<MiddlewareResult, CardinalError> {
+            if session.req_header().headers.contains_key("x-grpc-token") {
+                return Ok(MiddlewareResult::Continue(HashMap::new()));
+            }
+            let _ = respond_error(session, 403).await;
             Ok(MiddlewareResult::Responded)
         }
     }
@@ -2,7 +2,7 @@ use crate::config::get_config_builder;
 use ::config::ConfigError;
 use derive_builder::Builder;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use ts_rs::TS;
 
 pub mod config;
@@ -14,6 +14,20 @@ pub struct HealthCheck {
     pub interval_ms: u64,
     pub timeout_ms: u64,
     pub expect_status: u16,
+    /// Consecutive successful probes marking an unhealthy endpoint healthy again.
+    #[serde(default = "default_healthy_threshold")]
+    pub healthy_threshold: u32,
+    /// Consecutive failed probes marking a healthy endpoint unhealthy.
+    #[serde(default = "default_unhealthy_threshold")]
+    pub unhealthy_threshold: u32,
+}
+
+fn default_healthy_threshold() -> u32 {
+    2
+}
+
+fn default_unhealthy_threshold() -> u32 {
+    3
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
@@ -118,9 +132,49 @@ pub enum DestinationMatchValue {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
 #[ts(export)]
 pub struct DestinationMatch {
-    pub host: Option<DestinationMatchValue>, // exact or wildcard “*.tenant.com”
+    pub host: Option<DestinationMatchValue>, // exact or wildcard “*.tenant.com” (any subdomain depth)
     pub path_prefix: Option<DestinationMatchValue>, // e.g. “/billing/”
     pub path_exact: Option<String>,
+    /// Request methods accepted, any method when empty.
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// Headers that must all be present, and match when a value is given.
+    #[serde(default)]
+    pub headers: Vec<DestinationMatchField>,
+    /// Query parameters that must all be present, and match when a value is given.
+    #[serde(default)]
+    pub query: Vec<DestinationMatchField>,
+    /// Cookies that must all be present, and match when a value is given.
+    #[serde(default)]
+    pub cookies: Vec<DestinationMatchField>,
+    /// Spreads matching requests over several destinations instead of the owning one.
+    #[serde(default)]
+    pub split: Option<DestinationSplit>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct DestinationSplit {
+    pub targets: Vec<DestinationSplitTarget>,
+    /// Keeps a client on the same target, weighted round-robin when unset.
+    #[serde(default)]
+    pub sticky: Option<LoadBalancerHashKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct DestinationSplitTarget {
+    pub destination: String,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct DestinationMatchField {
+    pub name: String,
+    /// Exact value or `{ regex = "..." }`, only presence is checked when unset.
+    #[serde(default)]
+    pub value: Option<DestinationMatchValue>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS, Default)]
@@ -141,20 +195,127 @@ pub enum DestinationRetryBackoffType {
     None,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS, Default)]
+/// Failure classes that can trigger a retry.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
+#[ts(export)]
+pub enum DestinationRetryCondition {
+    /// The upstream could not be reached; the request was never sent.
+    ConnectFailure,
+    /// Reading from or writing to the upstream timed out.
+    Timeout,
+    /// The upstream connection was closed or failed mid-request.
+    ConnectionError,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
 #[ts(export)]
 pub struct DestinationRetry {
     pub max_attempts: u64,
     pub interval_ms: u64,
     pub backoff_type: DestinationRetryBackoffType,
     pub max_interval: Option<u64>,
+    /// Upstream response statuses that are retried, e.g. `[502, 503, 504]`.
+    #[serde(default)]
+    pub retry_on_status: Vec<u16>,
+    #[serde(default = "default_retry_conditions")]
+    pub retry_on: Vec<DestinationRetryCondition>,
+    /// Allows retrying non-idempotent methods (e.g. POST) after the request was sent.
+    #[serde(default)]
+    pub retry_non_idempotent: bool,
+    /// Upper bound, measured from the start of the request, for scheduling another attempt.
+    #[serde(default)]
+    pub budget_ms: Option<u64>,
+}
+
+fn default_retry_conditions() -> Vec<DestinationRetryCondition> {
+    vec![DestinationRetryCondition::ConnectFailure]
+}
+
+impl Default for DestinationRetry {
+    fn default() -> Self {
+        DestinationRetry {
+            max_attempts: 0,
+            interval_ms: 0,
+            backoff_type: DestinationRetryBackoffType::default(),
+            max_interval: None,
+            retry_on_status: vec![],
+            retry_on: default_retry_conditions(),
+            retry_non_idempotent: false,
+            budget_ms: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct DestinationUpstream {
+    pub url: String,
+    #[serde(default)]
+    pub weight: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS, Default)]
+#[ts(export)]
+pub enum LoadBalancingStrategy {
+    #[default]
+    RoundRobin,
+    Weighted,
+    LeastConnections,
+    ConsistentHash,
+}
+
+/// Request attribute used to pick an upstream when the strategy is `ConsistentHash`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS, Default)]
+#[ts(export)]
+pub enum LoadBalancerHashKey {
+    #[default]
+    ClientIp,
+    Path,
+    Header(String),
+    Cookie(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS, Default)]
+#[ts(export)]
+pub struct DestinationLoadBalancer {
+    #[serde(default)]
+    pub strategy: LoadBalancingStrategy,
+    #[serde(default)]
+    pub hash_key: Option<LoadBalancerHashKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct DestinationCircuitBreaker {
+    /// Consecutive failed requests that open the breaker.
+    pub failure_threshold: u32,
+    /// How long the breaker stays open before letting trial requests through.
+    pub open_duration_ms: u64,
+    /// Trial requests allowed while half-open. Defaults to 1.
+    #[serde(default)]
+    pub half_open_max_requests: Option<u32>,
+    /// Upstream statuses counted as failures, in addition to connect errors and timeouts.
+    #[serde(default = "default_circuit_breaker_statuses")]
+    pub failure_status_codes: Vec<u16>,
+    /// Destination that receives traffic while the breaker is open, instead of a 503.
+    #[serde(default)]
+    pub fallback_destination: Option<String>,
+}
+
+fn default_circuit_breaker_statuses() -> Vec<u16> {
+    vec![502, 503, 504]
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Builder, TS)]
 #[ts(export)]
 pub struct Destination {
     pub name: String,
+    #[serde(default)]
     pub url: String,
+    #[serde(default)]
+    pub upstreams: Vec<DestinationUpstream>,
+    #[serde(default)]
+    pub load_balancer: Option<DestinationLoadBalancer>,
     pub health_check: Option<HealthCheck>,
     #[serde(default)]
     pub default: bool,
@@ -168,6 +329,565 @@ pub struct Destination {
     pub timeout: Option<DestinationTimeouts>,
     #[serde(default)]
     pub retry: Option<DestinationRetry>,
+    #[serde(default)]
+    pub circuit_breaker: Option<DestinationCircuitBreaker>,
+    /// Forward the client `Host` header instead of the upstream origin.
+    #[serde(default)]
+    pub preserve_host: bool,
+    /// `Host` header sent upstream instead of the upstream origin.
+    #[serde(default)]
+    pub host_header: Option<String>,
+    /// TLS server name sent to HTTPS upstreams instead of the origin host.
+    #[serde(default)]
+    pub sni: Option<String>,
+    /// Verification and client certificate used for HTTPS upstreams.
+    #[serde(default)]
+    pub tls: Option<DestinationTls>,
+    /// Copies a share of the requests to a shadow destination.
+    #[serde(default)]
+    pub mirror: Option<DestinationMirror>,
+    /// Serves cacheable `GET` responses from the gateway cache.
+    #[serde(default)]
+    pub cache: Option<DestinationCache>,
+    /// Compresses responses for clients accepting gzip, brotli or zstd.
+    #[serde(default)]
+    pub compression: Option<DestinationCompression>,
+    /// Body size limits, taking precedence over the server ones.
+    #[serde(default)]
+    pub limits: Option<DestinationLimits>,
+    /// Idle timeout and frame size limits of upgraded WebSocket connections.
+    #[serde(default)]
+    pub websocket: Option<DestinationWebSocket>,
+    /// Routes gRPC calls by service and talks HTTP/2 only to the upstreams.
+    #[serde(default)]
+    pub grpc: Option<DestinationGrpc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, Builder, TS)]
+#[ts(export)]
+pub struct DestinationGrpc {
+    /// Fully qualified services, e.g. `helloworld.Greeter`, whose calls are routed here.
+    #[serde(default)]
+    pub services: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, Builder, TS)]
+#[ts(export)]
+pub struct DestinationWebSocket {
+    /// Closes the connection once either side has sent nothing for this long.
+    #[serde(default)]
+    pub idle_timeout_ms: Option<u64>,
+    /// Larger frames, in either direction, close the connection.
+    #[serde(default)]
+    pub max_frame_bytes: Option<usize>,
+    /// Larger messages, summed over their fragments, close the connection.
+    #[serde(default)]
+    pub max_message_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, Builder, TS)]
+#[ts(export)]
+pub struct DestinationLimits {
+    /// Larger request bodies are rejected with `413`.
+    #[serde(default)]
+    pub max_request_body_bytes: Option<usize>,
+    /// Larger response bodies are not passed to the client.
+    #[serde(default)]
+    pub max_response_body_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct DestinationCompression {
+    /// Encodings offered, the earlier one wins when the client accepts several equally.
+    #[serde(default = "default_compression_algorithms")]
+    pub algorithms: Vec<CompressionAlgorithm>,
+    /// Compression level from 1 to 9. Defaults to 6.
+    #[serde(default = "default_compression_level")]
+    pub level: u32,
+    /// Responses announcing a smaller `Content-Length` are sent as is. Defaults to 1 KiB.
+    #[serde(default = "default_compression_min_size_bytes")]
+    pub min_size_bytes: usize,
+    /// Media types compressed, either exact or `type/*`.
+    #[serde(default = "default_compression_content_types")]
+    pub content_types: Vec<String>,
+}
+
+fn default_compression_algorithms() -> Vec<CompressionAlgorithm> {
+    vec![
+        CompressionAlgorithm::Brotli,
+        CompressionAlgorithm::Zstd,
+        CompressionAlgorithm::Gzip,
+    ]
+}
+
+fn default_compression_level() -> u32 {
+    6
+}
+
+fn default_compression_min_size_bytes() -> usize {
+    1024
+}
+
+fn default_compression_content_types() -> Vec<String> {
+    [
+        "text/*",
+        "application/json",
+        "application/javascript",
+        "application/xml",
+        "image/svg+xml",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+impl Default for DestinationCompression {
+    fn default() -> Self {
+        Self {
+            algorithms: default_compression_algorithms(),
+            level: default_compression_level(),
+            min_size_bytes: default_compression_min_size_bytes(),
+            content_types: default_compression_content_types(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[ts(export)]
+pub enum CompressionAlgorithm {
+    Gzip,
+    Brotli,
+    Zstd,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct DestinationCache {
+    #[serde(default)]
+    pub key: DestinationCacheKey,
+    /// Freshness of responses without `Cache-Control` or `Expires`, not cached when unset.
+    #[serde(default)]
+    pub default_ttl_secs: Option<u64>,
+    /// Window to serve a stale response while refreshing it, unless the response sets
+    /// `stale-while-revalidate`.
+    #[serde(default)]
+    pub stale_while_revalidate_secs: u32,
+    /// Window to serve a stale response when the upstream fails, unless the response sets
+    /// `stale-if-error`.
+    #[serde(default)]
+    pub stale_if_error_secs: u32,
+    /// Responses with a larger body are not cached. Defaults to 8 MiB.
+    #[serde(default = "default_cache_max_object_bytes")]
+    pub max_object_bytes: usize,
+}
+
+fn default_cache_max_object_bytes() -> usize {
+    8 * 1024 * 1024
+}
+
+impl Default for DestinationCache {
+    fn default() -> Self {
+        Self {
+            key: DestinationCacheKey::default(),
+            default_ttl_secs: None,
+            stale_while_revalidate_secs: 0,
+            stale_if_error_secs: 0,
+            max_object_bytes: default_cache_max_object_bytes(),
+        }
+    }
+}
+
+/// Request parts a cached response is stored under, besides the path.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct DestinationCacheKey {
+    #[serde(default = "default_true")]
+    pub host: bool,
+    /// Query parameters in the key, the whole query string when unset.
+    #[serde(default)]
+    pub query: Option<Vec<String>>,
+    #[serde(default)]
+    pub headers: Vec<String>,
+}
+
+impl Default for DestinationCacheKey {
+    fn default() -> Self {
+        Self {
+            host: true,
+            query: None,
+            headers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct DestinationMirror {
+    /// Destination receiving the copies, its responses are discarded.
+    pub destination: String,
+    /// Share of requests mirrored, from 0 to 100. Defaults to 100.
+    #[serde(default = "default_mirror_percentage")]
+    pub percentage: u32,
+    /// Requests with a larger body are not mirrored. Defaults to 64 KiB.
+    #[serde(default = "default_mirror_max_body_bytes")]
+    pub max_body_bytes: usize,
+}
+
+fn default_mirror_percentage() -> u32 {
+    100
+}
+
+fn default_mirror_max_body_bytes() -> usize {
+    64 * 1024
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct DestinationTls {
+    /// PEM bundle of CAs trusted for upstream certificates, instead of the system roots.
+    #[serde(default)]
+    pub ca_path: Option<String>,
+    /// Accept any upstream certificate. Meant for development only.
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+    /// Check that the upstream certificate is issued to the SNI or `server_name`.
+    #[serde(default = "default_true")]
+    pub verify_hostname: bool,
+    /// Name accepted on the upstream certificate in addition to the SNI.
+    #[serde(default)]
+    pub server_name: Option<String>,
+    /// Certificate presented to upstreams requiring mutual TLS.
+    #[serde(default)]
+    pub client_certificate: Option<DestinationClientCertificate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct DestinationClientCertificate {
+    /// PEM file holding the certificate followed by its intermediates.
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct TlsCertificate {
+    pub cert_path: String,
+    pub key_path: String,
+    /// SNI names served by this certificate, exact or wildcard (`*.example.com`).
+    #[serde(default)]
+    pub server_names: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
+#[ts(export)]
+pub enum TlsVersion {
+    Tls1_2,
+    Tls1_3,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS, Default)]
+#[ts(export)]
+pub enum TlsAlpn {
+    H1,
+    H2,
+    #[default]
+    H2H1,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct ServerTls {
+    /// Certificates picked by SNI. The first one is used when no server name matches.
+    pub certificates: Vec<TlsCertificate>,
+    #[serde(default)]
+    pub min_version: Option<TlsVersion>,
+    #[serde(default)]
+    pub alpn: TlsAlpn,
+}
+
+/// Additional listener served next to `server.address`. Unset overrides inherit the
+/// values from `server`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct ServerListener {
+    pub name: String,
+    pub address: String,
+    #[serde(default)]
+    pub tls: Option<ServerTls>,
+    /// Expect a PROXY protocol v1/v2 header at the start of every connection.
+    #[serde(default)]
+    pub proxy_protocol: bool,
+    /// Destinations reachable through this listener. All destinations when unset.
+    #[serde(default)]
+    pub destinations: Option<Vec<String>>,
+    #[serde(default)]
+    pub force_path_parameter: Option<bool>,
+    #[serde(default)]
+    pub global_request_middleware: Option<Vec<String>>,
+    #[serde(default)]
+    pub global_response_middleware: Option<Vec<String>>,
+}
+
+impl ServerListener {
+    pub fn allows_destination(&self, name: &str) -> bool {
+        self.destinations
+            .as_ref()
+            .map(|allowed| allowed.iter().any(|d| d == name))
+            .unwrap_or(true)
+    }
+}
+
+/// Admin HTTP API listener. When `token` is set every request must carry
+/// `Authorization: Bearer <token>`, without one the endpoints changing the gateway are refused.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct ServerAdmin {
+    pub address: String,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub tls: Option<ServerTls>,
+    /// Time `POST /drain` waits for the requests in flight to finish.
+    #[serde(default = "default_drain_timeout_ms")]
+    pub drain_timeout_ms: u64,
+}
+
+fn default_drain_timeout_ms() -> u64 {
+    30_000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS, Default)]
+#[ts(export)]
+pub enum TracingExporter {
+    /// OTLP over HTTP/protobuf to `endpoint`.
+    #[default]
+    Otlp,
+    /// Prints finished spans to stdout, meant for local debugging and tests.
+    Stdout,
+}
+
+/// OpenTelemetry tracing of proxied requests.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct ServerTracing {
+    #[serde(default)]
+    pub exporter: TracingExporter,
+    /// OTLP/HTTP traces endpoint, used by the `Otlp` exporter.
+    #[serde(default = "default_tracing_endpoint")]
+    pub endpoint: String,
+    #[serde(default = "default_tracing_service_name")]
+    pub service_name: String,
+}
+
+fn default_tracing_endpoint() -> String {
+    "http://localhost:4318/v1/traces".to_string()
+}
+
+fn default_tracing_service_name() -> String {
+    "cardinal".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS, Default)]
+#[ts(export)]
+pub enum AccessLogFormat {
+    /// One JSON object per line.
+    #[default]
+    Json,
+    /// Apache/NCSA Combined Log Format.
+    Combined,
+    /// `template` with `{field}` placeholders, e.g. `{method} {path} {status} {duration_ms}`.
+    Template,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS, Default)]
+#[ts(export)]
+pub enum AccessLogSink {
+    #[default]
+    Stdout,
+    /// Appends to `path`, rotating to `path.1`..`path.<max_files>` once it grows past
+    /// `max_bytes`.
+    File {
+        path: String,
+        #[serde(default)]
+        max_bytes: Option<u64>,
+        #[serde(default = "default_access_log_max_files")]
+        max_files: u32,
+    },
+    /// Sends every line as a UDP datagram, e.g. to a syslog collector.
+    Udp { address: String },
+}
+
+fn default_access_log_max_files() -> u32 {
+    5
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS, Default)]
+#[ts(export)]
+pub enum RequestIdFormat {
+    /// Time-ordered UUID, e.g. `0190b6f4-6c4e-7b1a-9c3d-5f2e8a7b6c1d`.
+    #[default]
+    UuidV7,
+    /// Time-ordered 26 character Crockford base32 identifier.
+    Ulid,
+}
+
+/// Request ID accepted from the client or generated by the gateway, forwarded upstream
+/// and echoed in the response.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct ServerRequestId {
+    #[serde(default = "default_request_id_header")]
+    pub header: String,
+    #[serde(default)]
+    pub format: RequestIdFormat,
+    /// Keep an ID sent by the client instead of always generating one.
+    #[serde(default = "default_true")]
+    pub trust_incoming: bool,
+}
+
+fn default_request_id_header() -> String {
+    "x-request-id".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS, Default)]
+#[ts(export)]
+pub enum ForwardedForMode {
+    /// Appends the client address to the chain received from a trusted proxy.
+    #[default]
+    Append,
+    /// Sends only the client address.
+    Replace,
+}
+
+/// Client and scheme information added to upstream requests. Forwarding headers sent by
+/// peers outside `trusted_proxies` are stripped before the gateway sets its own.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct ServerForwarding {
+    #[serde(default)]
+    pub x_forwarded_for: ForwardedForMode,
+    #[serde(default = "default_true")]
+    pub x_forwarded_proto: bool,
+    #[serde(default = "default_true")]
+    pub x_forwarded_port: bool,
+    #[serde(default)]
+    pub x_real_ip: bool,
+    /// RFC 7239 `Forwarded` header.
+    #[serde(default)]
+    pub forwarded: bool,
+    /// CIDRs or addresses, e.g. `10.0.0.0/8`, whose forwarding headers are kept.
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+}
+
+/// Parses a CIDR, or a single address as a host network.
+pub fn parse_network(value: &str) -> Option<ipnet::IpNet> {
+    value
+        .parse::<ipnet::IpNet>()
+        .or_else(|_| value.parse::<std::net::IpAddr>().map(ipnet::IpNet::from))
+        .ok()
+}
+
+/// Store shared by the destinations with a `cache`. Bound at startup, like listeners.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct ServerCache {
+    /// Size of the stored responses, least recently used ones are evicted beyond it.
+    #[serde(default = "default_cache_max_size_bytes")]
+    pub max_size_bytes: usize,
+    /// Keeps response bodies on disk instead of memory, in a `cardinal-cache-<id>` directory
+    /// per process created under this one. Directories of exited processes are removed at
+    /// startup, nothing else in it is touched.
+    #[serde(default)]
+    pub disk_path: Option<String>,
+    /// How long requests for a response being fetched wait before going upstream themselves.
+    #[serde(default = "default_cache_lock_timeout_ms")]
+    pub lock_timeout_ms: u64,
+}
+
+impl Default for ServerCache {
+    fn default() -> Self {
+        Self {
+            max_size_bytes: default_cache_max_size_bytes(),
+            disk_path: None,
+            lock_timeout_ms: default_cache_lock_timeout_ms(),
+        }
+    }
+}
+
+fn default_cache_max_size_bytes() -> usize {
+    256 * 1024 * 1024
+}
+
+fn default_cache_lock_timeout_ms() -> u64 {
+    5_000
+}
+
+/// Size limits applied to every request, unless a destination or route sets its own body limits.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, Builder, TS)]
+#[ts(export)]
+pub struct ServerLimits {
+    /// Larger request bodies are rejected with `413`.
+    #[serde(default)]
+    pub max_request_body_bytes: Option<usize>,
+    /// Larger response bodies are not passed to the client.
+    #[serde(default)]
+    pub max_response_body_bytes: Option<usize>,
+    /// Requests with more headers are rejected with `431`.
+    #[serde(default)]
+    pub max_request_headers: Option<usize>,
+    /// Requests whose header names and values add up to more bytes are rejected with `431`.
+    #[serde(default)]
+    pub max_request_header_bytes: Option<usize>,
+}
+
+/// Access log written once per proxied request.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct ServerAccessLog {
+    #[serde(default)]
+    pub format: AccessLogFormat,
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub sink: AccessLogSink,
+}
+
+/// Prometheus exposition listener.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct ServerMetrics {
+    pub address: String,
+    #[serde(default = "default_metrics_path")]
+    pub path: String,
+}
+
+fn default_metrics_path() -> String {
+    "/metrics".to_string()
+}
+
+/// Hot reload triggers. Listener addresses and TLS settings are bound at startup and are
+/// not affected by a reload.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder, TS)]
+#[ts(export)]
+pub struct ServerReload {
+    /// Poll the configuration files and reload when they change.
+    #[serde(default = "default_true")]
+    pub watch: bool,
+    #[serde(default = "default_reload_watch_interval_ms")]
+    pub watch_interval_ms: u64,
+    /// Reload on `SIGHUP`.
+    #[serde(default = "default_true")]
+    pub signal: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_reload_watch_interval_ms() -> u64 {
+    2_000
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Builder, TS)]
@@ -178,6 +898,34 @@ pub struct ServerConfig {
     pub log_upstream_response: bool,
     pub global_request_middleware: Vec<String>,
     pub global_response_middleware: Vec<String>,
+    #[serde(default)]
+    pub tls: Option<ServerTls>,
+    /// Expect a PROXY protocol v1/v2 header at the start of every connection.
+    #[serde(default)]
+    pub proxy_protocol: bool,
+    /// Accept HTTP/2 without TLS on plaintext listeners, as native gRPC clients speak it.
+    #[serde(default)]
+    pub h2c: bool,
+    #[serde(default)]
+    pub listeners: Vec<ServerListener>,
+    #[serde(default)]
+    pub reload: Option<ServerReload>,
+    #[serde(default)]
+    pub admin: Option<ServerAdmin>,
+    #[serde(default)]
+    pub metrics: Option<ServerMetrics>,
+    #[serde(default)]
+    pub tracing: Option<ServerTracing>,
+    #[serde(default)]
+    pub access_log: Option<ServerAccessLog>,
+    #[serde(default)]
+    pub request_id: Option<ServerRequestId>,
+    #[serde(default)]
+    pub forwarding: Option<ServerForwarding>,
+    #[serde(default)]
+    pub cache: Option<ServerCache>,
+    #[serde(default)]
+    pub limits: Option<ServerLimits>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Builder, TS)]
@@ -185,6 +933,9 @@ pub struct ServerConfig {
 pub struct Route {
     pub path: String,
     pub method: String,
+    /// Request body limit on this route, taking precedence over the destination and server ones.
+    #[serde(default)]
+    pub max_request_body_bytes: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, Builder, TS)]
@@ -204,6 +955,19 @@ impl Default for ServerConfig {
             log_upstream_response: true,
             global_response_middleware: vec![],
             global_request_middleware: vec![],
+            tls: None,
+            proxy_protocol: false,
+            h2c: false,
+            listeners: vec![],
+            reload: None,
+            admin: None,
+            metrics: None,
+            tracing: None,
+            access_log: None,
+            request_id: None,
+            forwarding: None,
+            cache: None,
+            limits: None,
         }
     }
 }
@@ -229,18 +993,223 @@ pub fn validate_config(config: &CardinalConfig) -> Result<(), ConfigError> {
         )));
     }
 
+    if let Some(tls) = &config.server.tls {
+        validate_tls(tls)?;
+    }
+
+    validate_listeners(config)?;
+
+    if let Some(access_log) = &config.server.access_log {
+        if access_log.format == AccessLogFormat::Template && access_log.template.is_none() {
+            return Err(ConfigError::Message(
+                "Access log format Template requires a template".to_string(),
+            ));
+        }
+    }
+
+    if let Some(request_id) = &config.server.request_id {
+        let is_token = |b: u8| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b);
+        if request_id.header.is_empty() || !request_id.header.bytes().all(is_token) {
+            return Err(ConfigError::Message(format!(
+                "Invalid request ID header name: {}",
+                request_id.header
+            )));
+        }
+    }
+
+    if let Some(forwarding) = &config.server.forwarding {
+        if let Some(invalid) = forwarding
+            .trusted_proxies
+            .iter()
+            .find(|network| parse_network(network).is_none())
+        {
+            return Err(ConfigError::Message(format!(
+                "Invalid trusted proxy network: {invalid}"
+            )));
+        }
+    }
+
     let all_plugin_names = config
         .plugins
         .iter()
         .map(|p| p.name())
         .collect::<Vec<&str>>();
 
-    for middleware in config.destinations.values().flat_map(|d| &d.middleware) {
-        if !all_plugin_names.contains(&middleware.name.as_str()) {
-            return Err(ConfigError::Message(format!(
-                "Middleware {} not found. {0} must be included in the list of plugins.",
-                middleware.name
-            )));
+    for middleware in config.destinations.values().flat_map(|d| &d.middleware) {
+        if !all_plugin_names.contains(&middleware.name.as_str()) {
+            return Err(ConfigError::Message(format!(
+                "Middleware {} not found. {0} must be included in the list of plugins.",
+                middleware.name
+            )));
+        }
+    }
+
+    for destination in config.destinations.values() {
+        if destination.url.is_empty() && destination.upstreams.is_empty() {
+            return Err(ConfigError::Message(format!(
+                "Destination {} must define either a url or at least one upstream.",
+                destination.name
+            )));
+        }
+
+        if !destination.url.is_empty() && !destination.upstreams.is_empty() {
+            return Err(ConfigError::Message(format!(
+                "Destination {} cannot define both a url and upstreams.",
+                destination.name
+            )));
+        }
+
+        for split in destination
+            .r#match
+            .iter()
+            .flatten()
+            .filter_map(|m| m.split.as_ref())
+        {
+            if split.targets.iter().all(|t| t.weight == 0) {
+                return Err(ConfigError::Message(format!(
+                    "Traffic split of destination {} needs a target with a weight greater than 0.",
+                    destination.name
+                )));
+            }
+            if let Some(target) = split.targets.iter().find(|t| {
+                !config
+                    .destinations
+                    .values()
+                    .any(|d| d.name == t.destination)
+            }) {
+                return Err(ConfigError::Message(format!(
+                    "Split target {} of destination {} does not exist.",
+                    target.destination, destination.name
+                )));
+            }
+        }
+
+        if destination.preserve_host && destination.host_header.is_some() {
+            return Err(ConfigError::Message(format!(
+                "Destination {} cannot set both preserve_host and host_header.",
+                destination.name
+            )));
+        }
+
+        if let Some(host) = &destination.host_header {
+            if host.is_empty() || !host.bytes().all(|b| b.is_ascii_graphic()) {
+                return Err(ConfigError::Message(format!(
+                    "Host header {host} of destination {} is not a valid host.",
+                    destination.name
+                )));
+            }
+        }
+
+        if destination.sni.as_ref().is_some_and(|sni| sni.is_empty()) {
+            return Err(ConfigError::Message(format!(
+                "SNI of destination {} cannot be empty.",
+                destination.name
+            )));
+        }
+
+        if let Some(tls) = &destination.tls {
+            let empty_path = tls.ca_path.as_ref().is_some_and(|p| p.is_empty())
+                || tls
+                    .client_certificate
+                    .as_ref()
+                    .is_some_and(|c| c.cert_path.is_empty() || c.key_path.is_empty());
+            if empty_path {
+                return Err(ConfigError::Message(format!(
+                    "TLS settings of destination {} cannot have empty paths.",
+                    destination.name
+                )));
+            }
+        }
+
+        if let Some(check) = &destination.health_check {
+            if check.healthy_threshold == 0 || check.unhealthy_threshold == 0 {
+                return Err(ConfigError::Message(format!(
+                    "Health check thresholds of destination {} must be greater than 0.",
+                    destination.name
+                )));
+            }
+        }
+
+        if let Some(mirror) = &destination.mirror {
+            if mirror.destination == destination.name
+                || !config
+                    .destinations
+                    .values()
+                    .any(|d| d.name == mirror.destination)
+            {
+                return Err(ConfigError::Message(format!(
+                    "Mirror destination {} of destination {} does not exist.",
+                    mirror.destination, destination.name
+                )));
+            }
+
+            if mirror.percentage > 100 {
+                return Err(ConfigError::Message(format!(
+                    "Mirror percentage of destination {} must be between 0 and 100.",
+                    destination.name
+                )));
+            }
+        }
+
+        if let Some(compression) = &destination.compression {
+            if compression.algorithms.is_empty() {
+                return Err(ConfigError::Message(format!(
+                    "Compression of destination {} needs at least one algorithm.",
+                    destination.name
+                )));
+            }
+
+            if !(1..=9).contains(&compression.level) {
+                return Err(ConfigError::Message(format!(
+                    "Compression level of destination {} must be between 1 and 9.",
+                    destination.name
+                )));
+            }
+        }
+
+        if let Some(websocket) = &destination.websocket {
+            if websocket.idle_timeout_ms == Some(0) {
+                return Err(ConfigError::Message(format!(
+                    "WebSocket idle_timeout_ms of destination {} must be greater than 0.",
+                    destination.name
+                )));
+            }
+        }
+
+        if let Some(breaker) = &destination.circuit_breaker {
+            if breaker.failure_threshold == 0 {
+                return Err(ConfigError::Message(format!(
+                    "Circuit breaker of destination {} must have a failure_threshold greater than 0.",
+                    destination.name
+                )));
+            }
+
+            if let Some(fallback) = &breaker.fallback_destination {
+                if fallback == &destination.name
+                    || !config.destinations.values().any(|d| &d.name == fallback)
+                {
+                    return Err(ConfigError::Message(format!(
+                        "Fallback destination {} of destination {} does not exist.",
+                        fallback, destination.name
+                    )));
+                }
+            }
+        }
+
+        for upstream in &destination.upstreams {
+            if upstream.url.is_empty() {
+                return Err(ConfigError::Message(format!(
+                    "Destination {} has an upstream without a url.",
+                    destination.name
+                )));
+            }
+
+            if upstream.weight == Some(0) {
+                return Err(ConfigError::Message(format!(
+                    "Upstream {} of destination {} must have a weight greater than 0.",
+                    upstream.url, destination.name
+                )));
+            }
         }
     }
 
@@ -273,6 +1242,128 @@ pub fn validate_config(config: &CardinalConfig) -> Result<(), ConfigError> {
         }
     }
 
+    let mut grpc_services = HashMap::new();
+    for destination in config.destinations.values() {
+        let services = destination.grpc.iter().flat_map(|grpc| &grpc.services);
+        for service in services {
+            if service.is_empty() || service.contains('/') {
+                return Err(ConfigError::Message(format!(
+                    "Invalid gRPC service {service:?} on destination {}.",
+                    destination.name
+                )));
+            }
+            if let Some(other) = grpc_services.insert(service.as_str(), &destination.name) {
+                return Err(ConfigError::Message(format!(
+                    "gRPC service {service} is routed to both {other} and {}.",
+                    destination.name
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_listeners(config: &CardinalConfig) -> Result<(), ConfigError> {
+    let mut names = Vec::new();
+    let mut addresses = vec![config.server.address.as_str()];
+
+    for listener in &config.server.listeners {
+        if listener.address.parse::<std::net::SocketAddr>().is_err() {
+            return Err(ConfigError::Message(format!(
+                "Invalid address {} for listener {}",
+                listener.address, listener.name
+            )));
+        }
+
+        if names.contains(&listener.name.as_str()) {
+            return Err(ConfigError::Message(format!(
+                "Listener {} is defined more than once.",
+                listener.name
+            )));
+        }
+
+        if addresses.contains(&listener.address.as_str()) {
+            return Err(ConfigError::Message(format!(
+                "Listener {} reuses address {} which is already bound.",
+                listener.name, listener.address
+            )));
+        }
+
+        if let Some(tls) = &listener.tls {
+            validate_tls(tls)?;
+        }
+
+        for destination in listener.destinations.iter().flatten() {
+            if !config.destinations.contains_key(destination) {
+                return Err(ConfigError::Message(format!(
+                    "Listener {} references unknown destination {destination}.",
+                    listener.name
+                )));
+            }
+        }
+
+        names.push(listener.name.as_str());
+        addresses.push(listener.address.as_str());
+    }
+
+    if let Some(admin) = &config.server.admin {
+        if admin.address.parse::<std::net::SocketAddr>().is_err() {
+            return Err(ConfigError::Message(format!(
+                "Invalid admin address: {}",
+                admin.address
+            )));
+        }
+
+        if addresses.contains(&admin.address.as_str()) {
+            return Err(ConfigError::Message(format!(
+                "Admin listener reuses address {} which is already bound.",
+                admin.address
+            )));
+        }
+
+        if let Some(tls) = &admin.tls {
+            validate_tls(tls)?;
+        }
+    }
+
+    if let Some(metrics) = &config.server.metrics {
+        if metrics.address.parse::<std::net::SocketAddr>().is_err() {
+            return Err(ConfigError::Message(format!(
+                "Invalid metrics address: {}",
+                metrics.address
+            )));
+        }
+
+        let admin_address = config.server.admin.as_ref().map(|a| a.address.as_str());
+        if addresses.contains(&metrics.address.as_str())
+            || admin_address == Some(metrics.address.as_str())
+        {
+            return Err(ConfigError::Message(format!(
+                "Metrics listener reuses address {} which is already bound.",
+                metrics.address
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_tls(tls: &ServerTls) -> Result<(), ConfigError> {
+    if tls.certificates.is_empty() {
+        return Err(ConfigError::Message(
+            "TLS listeners require at least one certificate.".to_string(),
+        ));
+    }
+
+    for certificate in &tls.certificates {
+        if certificate.cert_path.is_empty() || certificate.key_path.is_empty() {
+            return Err(ConfigError::Message(
+                "TLS certificates must define both cert_path and key_path.".to_string(),
+            ));
+        }
+    }
+
     Ok(())
 }
 
@@ -608,4 +1699,679 @@ match = []
         let reparsed: ConfigHarness = toml::from_str(&serialized).unwrap();
         assert_eq!(reparsed, parsed);
     }
+
+    #[test]
+    fn destination_with_upstreams_and_load_balancer() {
+        let toml_source = r#"
+name = "posts"
+
+[[upstreams]]
+url = "http://10.0.0.1:8080"
+weight = 3
+
+[[upstreams]]
+url = "http://10.0.0.2:8080"
+
+[load_balancer]
+strategy = "ConsistentHash"
+hash_key = { Header = "x-user-id" }
+"#;
+
+        let destination: Destination = toml::from_str(toml_source).unwrap();
+        assert!(destination.url.is_empty());
+        assert_eq!(
+            destination.upstreams,
+            vec![
+                DestinationUpstream {
+                    url: "http://10.0.0.1:8080".into(),
+                    weight: Some(3),
+                },
+                DestinationUpstream {
+                    url: "http://10.0.0.2:8080".into(),
+                    weight: None,
+                },
+            ]
+        );
+        assert_eq!(
+            destination.load_balancer,
+            Some(DestinationLoadBalancer {
+                strategy: LoadBalancingStrategy::ConsistentHash,
+                hash_key: Some(LoadBalancerHashKey::Header("x-user-id".into())),
+            })
+        );
+    }
+
+    #[test]
+    fn validate_config_rejects_destination_without_upstreams() {
+        let destination: Destination = toml::from_str(r#"name = "empty""#).unwrap();
+        let config = CardinalConfig {
+            destinations: BTreeMap::from([("empty".to_string(), destination)]),
+            ..Default::default()
+        };
+
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn validate_config_rejects_zero_weight_upstream() {
+        let destination: Destination = toml::from_str(
+            r#"
+name = "posts"
+
+[[upstreams]]
+url = "http://10.0.0.1:8080"
+weight = 0
+"#,
+        )
+        .unwrap();
+        let config = CardinalConfig {
+            destinations: BTreeMap::from([("posts".to_string(), destination)]),
+            ..Default::default()
+        };
+
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn health_check_thresholds_default_and_reject_zero() {
+        let destination: Destination = toml::from_str(
+            r#"
+name = "posts"
+url = "http://10.0.0.1:8080"
+
+[health_check]
+path = "/health"
+interval_ms = 1000
+timeout_ms = 500
+expect_status = 200
+"#,
+        )
+        .unwrap();
+        let check = destination.health_check.clone().unwrap();
+        assert_eq!(check.healthy_threshold, 2);
+        assert_eq!(check.unhealthy_threshold, 3);
+
+        let mut config = CardinalConfig {
+            destinations: BTreeMap::from([("posts".to_string(), destination)]),
+            ..Default::default()
+        };
+        assert!(validate_config(&config).is_ok());
+
+        let posts = config.destinations.get_mut("posts").unwrap();
+        posts.health_check.as_mut().unwrap().unhealthy_threshold = 0;
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn destination_circuit_breaker_defaults() {
+        let destination: Destination = toml::from_str(
+            r#"
+name = "posts"
+url = "http://10.0.0.1:8080"
+
+[circuit_breaker]
+failure_threshold = 5
+open_duration_ms = 30000
+fallback_destination = "posts_backup"
+"#,
+        )
+        .unwrap();
+
+        let breaker = destination.circuit_breaker.unwrap();
+        assert_eq!(breaker.failure_threshold, 5);
+        assert_eq!(breaker.half_open_max_requests, None);
+        assert_eq!(breaker.failure_status_codes, vec![502, 503, 504]);
+        assert_eq!(
+            breaker.fallback_destination.as_deref(),
+            Some("posts_backup")
+        );
+    }
+
+    #[test]
+    fn validate_config_rejects_preserve_host_with_host_header() {
+        let destination: Destination = toml::from_str(
+            r#"
+name = "posts"
+url = "https://10.0.0.1:8443"
+preserve_host = true
+host_header = "posts.internal"
+sni = "posts.example.com"
+"#,
+        )
+        .unwrap();
+        assert_eq!(destination.sni.as_deref(), Some("posts.example.com"));
+
+        let mut config = CardinalConfig {
+            destinations: BTreeMap::from([("posts".to_string(), destination)]),
+            ..Default::default()
+        };
+        assert!(validate_config(&config).is_err());
+
+        config.destinations.get_mut("posts").unwrap().preserve_host = false;
+        assert!(validate_config(&config).is_ok());
+    }
+
+    #[test]
+    fn destination_tls_defaults_and_validation() {
+        let destination: Destination = toml::from_str(
+            r#"
+name = "posts"
+url = "https://10.0.0.1:8443"
+
+[tls]
+ca_path = "certs/internal-ca.pem"
+client_certificate = { cert_path = "certs/gateway.crt", key_path = "" }
+"#,
+        )
+        .unwrap();
+        let tls = destination.tls.clone().unwrap();
+        assert!(tls.verify_hostname);
+        assert!(!tls.insecure_skip_verify);
+        assert_eq!(tls.server_name, None);
+
+        let config = CardinalConfig {
+            destinations: BTreeMap::from([("posts".to_string(), destination)]),
+            ..Default::default()
+        };
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn validate_config_checks_split_targets() {
+        let billing: Destination = toml::from_str(
+            r#"
+name = "billing"
+url = "http://10.0.0.1:8080"
+
+[[match]]
+host = "billing.example.com"
+split = { targets = [{ destination = "billing", weight = 95 }, { destination = "billing_canary", weight = 5 }], sticky = { Cookie = "session" } }
+"#,
+        )
+        .unwrap();
+        let split = billing.r#match.as_ref().unwrap()[0].split.clone().unwrap();
+        assert_eq!(
+            split.sticky,
+            Some(LoadBalancerHashKey::Cookie("session".into()))
+        );
+
+        let mut config = CardinalConfig {
+            destinations: BTreeMap::from([("billing".to_string(), billing)]),
+            ..Default::default()
+        };
+        assert!(validate_config(&config).is_err());
+
+        let canary: Destination = toml::from_str(
+            r#"
+name = "billing_canary"
+url = "http://10.0.0.2:8080"
+"#,
+        )
+        .unwrap();
+        config
+            .destinations
+            .insert("billing_canary".to_string(), canary);
+        assert!(validate_config(&config).is_ok());
+    }
+
+    #[test]
+    fn validate_config_checks_mirror() {
+        let posts: Destination = toml::from_str(
+            r#"
+name = "posts"
+url = "http://10.0.0.1:8080"
+mirror = { destination = "posts_shadow", percentage = 10 }
+"#,
+        )
+        .unwrap();
+        let mirror = posts.mirror.clone().unwrap();
+        assert_eq!(mirror.percentage, 10);
+        assert_eq!(mirror.max_body_bytes, 64 * 1024);
+
+        let shadow: Destination = toml::from_str(
+            r#"
+name = "posts_shadow"
+url = "http://10.0.0.2:8080"
+"#,
+        )
+        .unwrap();
+        let mut config = CardinalConfig {
+            destinations: BTreeMap::from([("posts".to_string(), posts)]),
+            ..Default::default()
+        };
+        assert!(validate_config(&config).is_err());
+
+        config
+            .destinations
+            .insert("posts_shadow".to_string(), shadow);
+        assert!(validate_config(&config).is_ok());
+
+        config
+            .destinations
+            .get_mut("posts")
+            .unwrap()
+            .mirror
+            .as_mut()
+            .unwrap()
+            .percentage = 101;
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn destination_cache_defaults() {
+        let destination: Destination = toml::from_str(
+            r#"
+name = "catalog"
+url = "http://10.0.0.1:8080"
+
+[cache]
+default_ttl_secs = 30
+
+[cache.key]
+query = ["page"]
+"#,
+        )
+        .unwrap();
+
+        let cache = destination.cache.unwrap();
+        assert_eq!(cache.default_ttl_secs, Some(30));
+        assert_eq!(cache.max_object_bytes, 8 * 1024 * 1024);
+        assert!(cache.key.host);
+        assert_eq!(cache.key.query, Some(vec!["page".to_string()]));
+        assert_eq!(cache.stale_while_revalidate_secs, 0);
+
+        let server: ServerCache = toml::from_str("").unwrap();
+        assert_eq!(server, ServerCache::default());
+        assert_eq!(server.max_size_bytes, 256 * 1024 * 1024);
+    }
+
+    #[test]
+    fn destination_compression_defaults_and_validation() {
+        let destination: Destination = toml::from_str(
+            r#"
+name = "legacy"
+url = "http://10.0.0.1:8080"
+compression = { algorithms = ["Gzip"] }
+"#,
+        )
+        .unwrap();
+
+        let compression = destination.compression.clone().unwrap();
+        assert_eq!(compression.algorithms, vec![CompressionAlgorithm::Gzip]);
+        assert_eq!(compression.level, 6);
+        assert_eq!(compression.min_size_bytes, 1024);
+        assert!(compression.content_types.contains(&"text/*".to_string()));
+
+        let mut config = CardinalConfig {
+            destinations: BTreeMap::from([("legacy".to_string(), destination)]),
+            ..Default::default()
+        };
+        assert!(validate_config(&config).is_ok());
+
+        let compression = config
+            .destinations
+            .get_mut("legacy")
+            .unwrap()
+            .compression
+            .as_mut()
+            .unwrap();
+        compression.level = 10;
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn destination_and_route_limits_parse() {
+        let destination: Destination = toml::from_str(
+            r#"
+name = "files"
+url = "http://10.0.0.1:8080"
+limits = { max_request_body_bytes = 1048576 }
+routes = [
+    { method = "POST", path = "/uploads", max_request_body_bytes = 104857600 },
+    { method = "GET", path = "/files/{id}" },
+]
+"#,
+        )
+        .unwrap();
+
+        let limits = destination.limits.unwrap();
+        assert_eq!(limits.max_request_body_bytes, Some(1024 * 1024));
+        assert_eq!(limits.max_response_body_bytes, None);
+        assert_eq!(
+            destination.routes[0].max_request_body_bytes,
+            Some(100 * 1024 * 1024)
+        );
+        assert_eq!(destination.routes[1].max_request_body_bytes, None);
+    }
+
+    #[test]
+    fn destination_websocket_parses_and_validates() {
+        let mut destination: Destination = toml::from_str(
+            r#"
+name = "chat"
+url = "http://10.0.0.1:8080"
+websocket = { idle_timeout_ms = 30000, max_message_bytes = 65536 }
+"#,
+        )
+        .unwrap();
+
+        let websocket = destination.websocket.clone().unwrap();
+        assert_eq!(websocket.idle_timeout_ms, Some(30_000));
+        assert_eq!(websocket.max_frame_bytes, None);
+        assert_eq!(websocket.max_message_bytes, Some(64 * 1024));
+
+        destination.websocket = Some(DestinationWebSocket {
+            idle_timeout_ms: Some(0),
+            ..websocket
+        });
+        let config = CardinalConfig {
+            destinations: BTreeMap::from([("chat".to_string(), destination)]),
+            ..Default::default()
+        };
+
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn validate_config_rejects_grpc_service_on_two_destinations() {
+        let destination = |name: &str| -> Destination {
+            toml::from_str(&format!(
+                r#"
+name = "{name}"
+url = "http://10.0.0.1:50051"
+grpc = {{ services = ["helloworld.Greeter"] }}
+"#
+            ))
+            .unwrap()
+        };
+        let mut config = CardinalConfig {
+            destinations: BTreeMap::from([("greeter".to_string(), destination("greeter"))]),
+            ..Default::default()
+        };
+        config.server.address = "127.0.0.1:8080".into();
+        assert!(validate_config(&config).is_ok());
+
+        config
+            .destinations
+            .insert("legacy".to_string(), destination("legacy"));
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn validate_config_rejects_unknown_fallback_destination() {
+        let destination: Destination = toml::from_str(
+            r#"
+name = "posts"
+url = "http://10.0.0.1:8080"
+
+[circuit_breaker]
+failure_threshold = 5
+open_duration_ms = 30000
+fallback_destination = "missing"
+"#,
+        )
+        .unwrap();
+        let config = CardinalConfig {
+            destinations: BTreeMap::from([("posts".to_string(), destination)]),
+            ..Default::default()
+        };
+
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn destination_retry_defaults_to_connect_failures() {
+        let retry: DestinationRetry = toml::from_str(
+            r#"
+max_attempts = 3
+interval_ms = 100
+backoff_type = "Exponential"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            retry.retry_on,
+            vec![DestinationRetryCondition::ConnectFailure]
+        );
+        assert!(retry.retry_on_status.is_empty());
+        assert!(!retry.retry_non_idempotent);
+        assert_eq!(retry.budget_ms, None);
+
+        let retry: DestinationRetry = toml::from_str(
+            r#"
+max_attempts = 3
+interval_ms = 100
+backoff_type = "None"
+retry_on_status = [502, 503]
+retry_on = ["Timeout", "ConnectionError"]
+budget_ms = 2000
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(retry.retry_on_status, vec![502, 503]);
+        assert_eq!(
+            retry.retry_on,
+            vec![
+                DestinationRetryCondition::Timeout,
+                DestinationRetryCondition::ConnectionError
+            ]
+        );
+        assert_eq!(retry.budget_ms, Some(2000));
+    }
+
+    #[test]
+    fn server_tls_parses_certificates_and_defaults() {
+        let server: ServerConfig = toml::from_str(
+            r#"
+address = "0.0.0.0:443"
+force_path_parameter = false
+log_upstream_response = false
+global_request_middleware = []
+global_response_middleware = []
+
+[tls]
+min_version = "Tls1_3"
+
+[[tls.certificates]]
+cert_path = "certs/default.crt"
+key_path = "certs/default.key"
+
+[[tls.certificates]]
+cert_path = "certs/api.crt"
+key_path = "certs/api.key"
+server_names = ["api.example.com", "*.api.example.com"]
+"#,
+        )
+        .unwrap();
+
+        let tls = server.tls.unwrap();
+        assert_eq!(tls.certificates.len(), 2);
+        assert!(tls.certificates[0].server_names.is_empty());
+        assert_eq!(tls.min_version, Some(TlsVersion::Tls1_3));
+        assert_eq!(tls.alpn, TlsAlpn::H2H1);
+    }
+
+    #[test]
+    fn validate_config_rejects_tls_without_certificates() {
+        let mut config = CardinalConfig::default();
+        config.server.tls = Some(ServerTls {
+            certificates: vec![],
+            min_version: None,
+            alpn: TlsAlpn::H1,
+        });
+
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn server_listeners_parse_overrides() {
+        let server: ServerConfig = toml::from_str(
+            r#"
+address = "0.0.0.0:8080"
+force_path_parameter = true
+log_upstream_response = false
+global_request_middleware = ["auth"]
+global_response_middleware = []
+
+[[listeners]]
+name = "internal"
+address = "127.0.0.1:9090"
+destinations = ["posts"]
+force_path_parameter = false
+global_request_middleware = []
+"#,
+        )
+        .unwrap();
+
+        let listener = &server.listeners[0];
+        assert_eq!(listener.name, "internal");
+        assert_eq!(listener.force_path_parameter, Some(false));
+        assert_eq!(listener.global_request_middleware, Some(vec![]));
+        assert_eq!(listener.global_response_middleware, None);
+        assert!(listener.allows_destination("posts"));
+        assert!(!listener.allows_destination("auth"));
+    }
+
+    #[test]
+    fn validate_config_rejects_conflicting_listeners() {
+        let listener = ServerListener {
+            name: "admin".into(),
+            address: "127.0.0.1:9901".into(),
+            tls: None,
+            proxy_protocol: false,
+            destinations: None,
+            force_path_parameter: None,
+            global_request_middleware: None,
+            global_response_middleware: None,
+        };
+
+        let mut config = CardinalConfig::default();
+        config.server.listeners = vec![listener.clone(), listener.clone()];
+        assert!(validate_config(&config).is_err());
+
+        config.server.listeners = vec![ServerListener {
+            address: config.server.address.clone(),
+            ..listener.clone()
+        }];
+        assert!(validate_config(&config).is_err());
+
+        config.server.listeners = vec![ServerListener {
+            destinations: Some(vec!["missing".into()]),
+            ..listener
+        }];
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn validate_config_rejects_admin_on_proxy_address() {
+        let mut config = CardinalConfig::default();
+        config.server.admin = Some(ServerAdmin {
+            address: config.server.address.clone(),
+            token: None,
+            tls: None,
+            drain_timeout_ms: 30_000,
+        });
+
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn validate_config_rejects_template_access_log_without_template() {
+        let mut config = CardinalConfig::default();
+        config.server.access_log = Some(ServerAccessLog {
+            format: AccessLogFormat::Template,
+            template: None,
+            sink: AccessLogSink::Stdout,
+        });
+
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn server_access_log_sinks_deserialize() {
+        let access_log: ServerAccessLog = toml::from_str("").unwrap();
+        assert_eq!(access_log.format, AccessLogFormat::Json);
+        assert_eq!(access_log.sink, AccessLogSink::Stdout);
+
+        let access_log: ServerAccessLog = toml::from_str(
+            r#"
+            format = "Combined"
+            sink = { File = { path = "access.log", max_bytes = 1024 } }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(access_log.format, AccessLogFormat::Combined);
+        assert_eq!(
+            access_log.sink,
+            AccessLogSink::File {
+                path: "access.log".into(),
+                max_bytes: Some(1024),
+                max_files: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn server_request_id_defaults_and_header_validation() {
+        let request_id: ServerRequestId = toml::from_str("").unwrap();
+        assert_eq!(request_id.header, "x-request-id");
+        assert_eq!(request_id.format, RequestIdFormat::UuidV7);
+        assert!(request_id.trust_incoming);
+
+        let mut config = CardinalConfig::default();
+        config.server.request_id = Some(ServerRequestId {
+            header: "x request id".into(),
+            format: RequestIdFormat::Ulid,
+            trust_incoming: false,
+        });
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn server_forwarding_defaults_and_trusted_proxy_validation() {
+        let forwarding: ServerForwarding = toml::from_str("").unwrap();
+        assert_eq!(forwarding.x_forwarded_for, ForwardedForMode::Append);
+        assert!(forwarding.x_forwarded_proto && forwarding.x_forwarded_port);
+        assert!(!forwarding.x_real_ip && !forwarding.forwarded);
+
+        assert!(parse_network("10.0.0.0/8").is_some());
+        assert!(parse_network("2001:db8::1").is_some());
+
+        let mut config = CardinalConfig::default();
+        config.server.forwarding = Some(ServerForwarding {
+            trusted_proxies: vec!["10.0.0.0/33".into()],
+            ..forwarding
+        });
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn server_tracing_defaults_to_local_otlp_collector() {
+        let tracing: ServerTracing = toml::from_str("").unwrap();
+
+        assert_eq!(tracing.exporter, TracingExporter::Otlp);
+        assert_eq!(tracing.endpoint, "http://localhost:4318/v1/traces");
+        assert_eq!(tracing.service_name, "cardinal");
+
+        let tracing: ServerTracing = toml::from_str(r#"exporter = "Stdout""#).unwrap();
+        assert_eq!(tracing.exporter, TracingExporter::Stdout);
+    }
+
+    #[test]
+    fn server_metrics_defaults_path() {
+        let metrics: ServerMetrics = toml::from_str(r#"address = "127.0.0.1:9100""#).unwrap();
+
+        assert_eq!(metrics.path, "/metrics");
+    }
+
+    #[test]
+    fn server_reload_defaults() {
+        let reload: ServerReload = toml::from_str("").unwrap();
+
+        assert!(reload.watch);
+        assert!(reload.signal);
+        assert_eq!(reload.watch_interval_ms, 2_000);
+    }
 }
@@ -154,9 +154,7 @@ mod tests {
 
         let decoded = CZip::try_from(bytes.as_slice()).expect("failed to deserialize archive");
 
-        let decoded_v1 = match decoded {
-            CZip::V1(inner) => inner,
-        };
+        let CZip::V1(decoded_v1) = decoded;
 
         assert_eq!(decoded_v1.config(), &config);
         assert_eq!(decoded_v1.plugins(), &expected_plugins);
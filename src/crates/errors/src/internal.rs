@@ -19,4 +19,12 @@ pub enum CardinalInternalError {
     InvalidWasmModule(String),
     #[error("Request plugin did not complete {0}")]
     RequestPluginError(String),
+    #[error("Invalid TLS Configuration {0}")]
+    InvalidTlsConfiguration(String),
+    #[error("Invalid Tracing Configuration {0}")]
+    InvalidTracingConfiguration(String),
+    #[error("Invalid Access Log Configuration {0}")]
+    InvalidAccessLogConfiguration(String),
+    #[error("Invalid Forwarding Configuration {0}")]
+    InvalidForwardingConfiguration(String),
 }
@@ -1,6 +1,7 @@
 use crate::headers::CARDINAL_PARAMS_HEADER_BASE;
 use crate::request_context::RequestContext;
 use crate::runner::{MiddlewareResult, RequestMiddleware};
+use crate::utils::respond_error;
 use cardinal_base::context::CardinalContext;
 use cardinal_errors::CardinalError;
 use pingora::proxy::Session;
@@ -33,7 +34,7 @@ impl RequestMiddleware for RestrictedRouteMiddleware {
 
                 Ok(MiddlewareResult::Continue(HashMap::new()))
             } else {
-                let _ = session.respond_error(402).await;
+                let _ = respond_error(session, 402).await;
                 Ok(MiddlewareResult::Responded)
             }
         } else {
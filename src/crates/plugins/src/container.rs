@@ -55,6 +55,12 @@ impl PluginContainer {
         )]
     }
 
+    pub fn plugins(&self) -> impl Iterator<Item = (&str, &PluginHandler)> {
+        self.plugins
+            .iter()
+            .map(|(name, handler)| (name.as_str(), handler.as_ref()))
+    }
+
     pub fn add_plugin(&mut self, name: String, plugin: PluginHandler) {
         self.plugins.insert(name, Arc::new(plugin));
     }
@@ -1,9 +1,12 @@
 mod builtin;
 pub mod container;
 pub mod headers;
+pub mod metrics;
 pub mod plugin_executor;
 pub mod request_context;
 pub mod runner;
+pub mod trace;
 pub mod utils;
 
 pub const REQ_UTC_TIME: &str = "REQ_UTC_TIME";
+pub const REQUEST_ID: &str = "REQUEST_ID";
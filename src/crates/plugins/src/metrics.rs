@@ -0,0 +1,22 @@
+use prometheus::{register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec};
+use std::sync::LazyLock;
+
+/// Time spent running a single middleware, by plugin name and phase (`request`/`response`).
+pub static PLUGIN_DURATION_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "cardinal_plugin_duration_seconds",
+        "Middleware execution time",
+        &["plugin", "phase"]
+    )
+    .expect("cardinal_plugin_duration_seconds registers once")
+});
+
+/// Requests answered by a middleware instead of being forwarded upstream.
+pub static PLUGIN_SHORT_CIRCUITS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "cardinal_plugin_short_circuits_total",
+        "Requests short-circuited by a request middleware",
+        &["plugin"]
+    )
+    .expect("cardinal_plugin_short_circuits_total registers once")
+});
@@ -1,8 +1,10 @@
 use crate::plugin_executor::CardinalPluginExecutor;
 use crate::runner::PluginRunner;
+use crate::trace::RequestTrace;
 use crate::REQ_UTC_TIME;
 use cardinal_base::context::CardinalContext;
 use cardinal_base::destinations::container::DestinationWrapper;
+use cardinal_config::ServerListener;
 use cardinal_wasm_plugins::{ExecutionContext, SharedExecutionContext};
 use chrono::Utc;
 use parking_lot::RwLock;
@@ -16,6 +18,8 @@ pub struct RequestContext {
     pub plugin_runner: Arc<PluginRunner>,
     pub response_headers: Option<HashMap<String, String>>,
     pub shared_ctx: SharedExecutionContext,
+    /// Set when tracing is enabled, middleware runs are recorded as child spans.
+    pub trace: Option<RequestTrace>,
 }
 
 impl RequestContext {
@@ -32,9 +36,15 @@ impl RequestContext {
             plugin_runner: Arc::new(runner),
             response_headers: None,
             shared_ctx: Arc::new(RwLock::new(execution_context)),
+            trace: None,
         }
     }
 
+    pub fn apply_listener(&mut self, listener: &ServerListener) {
+        let runner = self.plugin_runner.as_ref().clone().with_listener(listener);
+        self.plugin_runner = Arc::new(runner);
+    }
+
     pub fn persistent_vars(&self) -> Arc<RwLock<HashMap<String, String>>> {
         self.shared_ctx.read().persistent_vars().clone()
     }
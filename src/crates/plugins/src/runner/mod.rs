@@ -1,13 +1,18 @@
+use crate::metrics::{PLUGIN_DURATION_SECONDS, PLUGIN_SHORT_CIRCUITS_TOTAL};
 use crate::plugin_executor::CardinalPluginExecutor;
 use crate::request_context::RequestContext;
 use async_trait::async_trait;
 use cardinal_base::context::CardinalContext;
+use cardinal_config::ServerListener;
 use cardinal_errors::internal::CardinalInternalError;
 use cardinal_errors::CardinalError;
+use opentelemetry::trace::{Span, Status};
+use opentelemetry::KeyValue;
 use pingora::http::ResponseHeader;
 use pingora::proxy::Session;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MiddlewareResult {
@@ -61,6 +66,17 @@ impl PluginRunner {
         }
     }
 
+    /// Replaces the server-wide global middleware with the listener's overrides, if any.
+    pub fn with_listener(mut self, listener: &ServerListener) -> Self {
+        if let Some(request) = &listener.global_request_middleware {
+            self.global_request = Arc::new(request.clone());
+        }
+        if let Some(response) = &listener.global_response_middleware {
+            self.global_response = Arc::new(response.clone());
+        }
+        self
+    }
+
     fn global_request_filters(&self) -> &[String] {
         &self.global_request
     }
@@ -81,6 +97,59 @@ impl PluginRunner {
             .map_err(|e| CardinalInternalError::RequestPluginError(format!("{e:?}")).into())
     }
 
+    async fn run_request_filter(
+        &self,
+        name: &str,
+        session: &mut Session,
+        req_ctx: &mut RequestContext,
+    ) -> Result<MiddlewareResult, CardinalError> {
+        let started = Instant::now();
+        let mut span = middleware_span(req_ctx, name, "request");
+        let run = self
+            .plugin_executor
+            .run_request_filter(name, session, req_ctx)
+            .await;
+        PLUGIN_DURATION_SECONDS
+            .with_label_values(&[name, "request"])
+            .observe(started.elapsed().as_secs_f64());
+
+        if let Ok(MiddlewareResult::Responded) = run {
+            PLUGIN_SHORT_CIRCUITS_TOTAL.with_label_values(&[name]).inc();
+        }
+        if let Some(span) = span.as_mut() {
+            match &run {
+                Ok(MiddlewareResult::Responded) => {
+                    span.set_attribute(KeyValue::new("cardinal.plugin.short_circuit", true))
+                }
+                Ok(MiddlewareResult::Continue(_)) => {}
+                Err(err) => span.set_status(Status::error(err.to_string())),
+            }
+            span.end();
+        }
+        run
+    }
+
+    async fn run_response_filter(
+        &self,
+        name: &str,
+        session: &mut Session,
+        req_ctx: &mut RequestContext,
+        response: &mut ResponseHeader,
+    ) {
+        let started = Instant::now();
+        let span = middleware_span(req_ctx, name, "response");
+        let _ = self
+            .plugin_executor
+            .run_response_filter(name, session, req_ctx, response)
+            .await;
+        PLUGIN_DURATION_SECONDS
+            .with_label_values(&[name, "response"])
+            .observe(started.elapsed().as_secs_f64());
+        if let Some(mut span) = span {
+            span.end();
+        }
+    }
+
     pub async fn run_request_filters(
         &self,
         session: &mut Session,
@@ -95,10 +164,7 @@ impl PluginRunner {
                 continue;
             }
 
-            let run = self
-                .plugin_executor
-                .run_request_filter(filter, session, req_ctx)
-                .await?;
+            let run = self.run_request_filter(filter, session, req_ctx).await?;
 
             match run {
                 MiddlewareResult::Continue(middleware_resp_headers) => {
@@ -119,7 +185,6 @@ impl PluginRunner {
             }
 
             let run = self
-                .plugin_executor
                 .run_request_filter(middleware_name, session, req_ctx)
                 .await?;
 
@@ -150,9 +215,7 @@ impl PluginRunner {
                 continue;
             }
 
-            let _ = self
-                .plugin_executor
-                .run_response_filter(filter, session, req_ctx, response)
+            self.run_response_filter(filter, session, req_ctx, response)
                 .await;
         }
 
@@ -170,10 +233,24 @@ impl PluginRunner {
                 continue;
             }
 
-            let _ = self
-                .plugin_executor
-                .run_response_filter(middleware_name, session, req_ctx, response)
+            self.run_response_filter(middleware_name, session, req_ctx, response)
                 .await;
         }
     }
 }
+
+fn middleware_span(
+    req_ctx: &RequestContext,
+    name: &str,
+    phase: &'static str,
+) -> Option<opentelemetry_sdk::trace::Span> {
+    req_ctx.trace.as_ref().map(|trace| {
+        trace.child(
+            format!("middleware {name}"),
+            vec![
+                KeyValue::new("cardinal.plugin.name", name.to_string()),
+                KeyValue::new("cardinal.plugin.phase", phase),
+            ],
+        )
+    })
+}
@@ -0,0 +1,48 @@
+use opentelemetry::trace::{SpanBuilder, SpanKind, TraceContextExt};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_sdk::trace::{SdkTracer, Span};
+use std::borrow::Cow;
+
+/// Trace of the request being proxied. Holds the request span, spans started through it
+/// become its children.
+#[derive(Clone)]
+pub struct RequestTrace {
+    tracer: SdkTracer,
+    context: Context,
+}
+
+impl RequestTrace {
+    pub fn new(tracer: SdkTracer, context: Context) -> Self {
+        Self { tracer, context }
+    }
+
+    pub fn tracer(&self) -> &SdkTracer {
+        &self.tracer
+    }
+
+    /// Context carrying the request span.
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Starts an internal child span of the request span.
+    pub fn child(&self, name: impl Into<Cow<'static, str>>, attributes: Vec<KeyValue>) -> Span {
+        self.child_with_kind(name, SpanKind::Internal, attributes)
+    }
+
+    pub fn child_with_kind(
+        &self,
+        name: impl Into<Cow<'static, str>>,
+        kind: SpanKind,
+        attributes: Vec<KeyValue>,
+    ) -> Span {
+        SpanBuilder::from_name(name)
+            .with_kind(kind)
+            .with_attributes(attributes)
+            .start_with_context(&self.tracer, &self.context)
+    }
+
+    pub fn set_attribute(&self, attribute: KeyValue) {
+        self.context.span().set_attribute(attribute);
+    }
+}
@@ -1,5 +1,16 @@
+use pingora::protocols::http::error_resp::gen_error_response;
+use pingora::proxy::Session;
 use std::collections::HashMap;
 
+/// Writes an empty error response. Unlike `Session::respond_error` it goes through the
+/// downstream modules, so headers they add (e.g. the request ID) are present.
+pub async fn respond_error(session: &mut Session, status: u16) -> pingora::Result<()> {
+    let response = gen_error_response(status);
+    session
+        .write_response_header(Box::new(response), true)
+        .await
+}
+
 /// Parse query string into a `HashMap<String, Vec<String>>`
 /// Keeps all values when a key appears multiple times.
 pub fn parse_query_string_multi(qs: &str) -> HashMap<String, Vec<String>> {
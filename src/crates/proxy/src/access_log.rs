@@ -0,0 +1,518 @@
+use crate::grpc::GrpcModule;
+use crate::metrics::ACCESS_LOG_DROPPED_TOTAL;
+use crate::req::ReqCtx;
+use cardinal_config::{AccessLogFormat, AccessLogSink, ServerAccessLog};
+use cardinal_errors::internal::CardinalInternalError;
+use cardinal_errors::CardinalError;
+use cardinal_plugins::{REQUEST_ID, REQ_UTC_TIME};
+use pingora::http::ResponseHeader;
+use pingora::proxy::Session;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::path::PathBuf;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use tracing::warn;
+
+/// Lines buffered for the writer thread before new ones are dropped.
+const QUEUE_CAPACITY: usize = 8192;
+
+/// Fields usable as `{placeholders}` in a template, same names as the JSON keys.
+const FIELDS: &[&str] = &[
+    "timestamp",
+    "client_ip",
+    "method",
+    "path",
+    "protocol",
+    "destination",
+    "upstream",
+    "status",
+    "grpc_status",
+    "bytes_in",
+    "bytes_out",
+    "duration_ms",
+    "upstream_duration_ms",
+    "retries",
+    "request_id",
+    "user_agent",
+    "referer",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessLogEntry {
+    pub timestamp: String,
+    pub client_ip: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub protocol: String,
+    pub destination: Option<String>,
+    pub upstream: Option<String>,
+    /// `0` when no response was written.
+    pub status: u16,
+    /// `grpc-status` of gRPC and gRPC-Web calls.
+    pub grpc_status: Option<String>,
+    /// Request body bytes read from the client.
+    pub bytes_in: usize,
+    /// Response body bytes written to the client.
+    pub bytes_out: usize,
+    pub duration_ms: f64,
+    pub upstream_duration_ms: Option<f64>,
+    pub retries: u32,
+    pub request_id: Option<String>,
+    pub user_agent: Option<String>,
+    pub referer: Option<String>,
+}
+
+impl AccessLogEntry {
+    pub(crate) fn from_request(session: &Session, ctx: &ReqCtx) -> Self {
+        let req = session.req_header();
+        let header = |name: &str| {
+            req.headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Self {
+            timestamp: ctx
+                .ctx_base
+                .metadata
+                .get(REQ_UTC_TIME)
+                .cloned()
+                .unwrap_or_default(),
+            client_ip: ctx.client_ip().map(|ip| ip.to_string()),
+            method: req.method.to_string(),
+            path: ctx
+                .request_uri
+                .clone()
+                .unwrap_or_else(|| req.uri.to_string()),
+            protocol: format!("{:?}", req.version),
+            destination: ctx
+                .ctx_base
+                .resolved_request
+                .as_ref()
+                .map(|req| req.backend.destination.name.clone()),
+            upstream: ctx.upstream_endpoint().map(|endpoint| endpoint.url.clone()),
+            status: session
+                .response_written()
+                .map(|resp| resp.status.as_u16())
+                .unwrap_or(0),
+            grpc_status: session
+                .downstream_modules_ctx
+                .get::<GrpcModule>()
+                .and_then(|module| module.status())
+                .map(str::to_string),
+            bytes_in: session.body_bytes_read(),
+            bytes_out: body_bytes_sent(session),
+            duration_ms: millis(ctx.ctx_base.req_instant.elapsed()),
+            upstream_duration_ms: ctx.upstream_duration.map(millis),
+            retries: ctx.retries,
+            request_id: ctx
+                .ctx_base
+                .metadata
+                .get(REQUEST_ID)
+                .cloned()
+                .or_else(|| header("x-request-id")),
+            user_agent: header("user-agent"),
+            referer: header("referer"),
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "timestamp" => Some(self.timestamp.clone()),
+            "client_ip" => self.client_ip.clone(),
+            "method" => Some(self.method.clone()),
+            "path" => Some(self.path.clone()),
+            "protocol" => Some(self.protocol.clone()),
+            "destination" => self.destination.clone(),
+            "upstream" => self.upstream.clone(),
+            "status" => Some(self.status.to_string()),
+            "grpc_status" => self.grpc_status.clone(),
+            "bytes_in" => Some(self.bytes_in.to_string()),
+            "bytes_out" => Some(self.bytes_out.to_string()),
+            "duration_ms" => Some(format!("{:.3}", self.duration_ms)),
+            "upstream_duration_ms" => self.upstream_duration_ms.map(|ms| format!("{ms:.3}")),
+            "retries" => Some(self.retries.to_string()),
+            "request_id" => self.request_id.clone(),
+            "user_agent" => self.user_agent.clone(),
+            "referer" => self.referer.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// Response body bytes sent to the client. Pingora counts the response header with the
+/// body on HTTP/1, so its serialized size is taken back out.
+fn body_bytes_sent(session: &Session) -> usize {
+    let sent = session.body_bytes_sent();
+    match session.response_written() {
+        Some(resp) if !session.is_http2() => sent.saturating_sub(h1_header_len(resp)),
+        _ => sent,
+    }
+}
+
+/// Size of `resp` as written on an HTTP/1 connection, status line and blank line included.
+fn h1_header_len(resp: &ResponseHeader) -> usize {
+    let mut headers = Vec::new();
+    resp.header_to_h1_wire(&mut headers);
+    let reason = resp.get_reason_phrase().map_or(0, str::len);
+    "HTTP/1.1 200 \r\n".len() + reason + headers.len() + "\r\n".len()
+}
+
+fn millis(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[derive(Debug, PartialEq)]
+enum TemplatePart {
+    Literal(String),
+    Field(String),
+}
+
+enum LineFormat {
+    Json,
+    Combined,
+    Template(Vec<TemplatePart>),
+}
+
+impl LineFormat {
+    fn render(&self, entry: &AccessLogEntry) -> String {
+        match self {
+            LineFormat::Json => serde_json::to_string(entry).unwrap_or_default(),
+            LineFormat::Combined => combined(entry),
+            LineFormat::Template(parts) => parts
+                .iter()
+                .map(|part| match part {
+                    TemplatePart::Literal(text) => text.clone(),
+                    TemplatePart::Field(name) => entry.field(name).unwrap_or_else(|| "-".into()),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn combined(entry: &AccessLogEntry) -> String {
+    let time = chrono::DateTime::parse_from_rfc3339(&entry.timestamp)
+        .map(|time| time.format("%d/%b/%Y:%H:%M:%S %z").to_string())
+        .unwrap_or_else(|_| entry.timestamp.clone());
+    let or_dash = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".into());
+    let bytes = match entry.bytes_out {
+        0 => "-".to_string(),
+        bytes => bytes.to_string(),
+    };
+
+    format!(
+        "{} - - [{}] \"{} {} {}\" {} {} \"{}\" \"{}\"",
+        or_dash(&entry.client_ip),
+        time,
+        entry.method,
+        entry.path,
+        entry.protocol,
+        entry.status,
+        bytes,
+        or_dash(&entry.referer),
+        or_dash(&entry.user_agent),
+    )
+}
+
+fn parse_template(template: &str) -> Result<Vec<TemplatePart>, CardinalError> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            parts.push(TemplatePart::Literal(rest[..start].to_string()));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| invalid_access_log(&format!("unclosed placeholder in {template}")))?;
+        let name = &rest[start + 1..start + end];
+        if !FIELDS.contains(&name) {
+            return Err(invalid_access_log(&format!("unknown field {{{name}}}")));
+        }
+        parts.push(TemplatePart::Field(name.to_string()));
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(TemplatePart::Literal(rest.to_string()));
+    }
+
+    Ok(parts)
+}
+
+/// Formats access log entries and hands them to a writer thread owning the sink, so
+/// request handling never blocks on I/O. Entries are dropped while the queue is full.
+#[derive(Clone)]
+pub struct AccessLogger {
+    format: Arc<LineFormat>,
+    sender: SyncSender<String>,
+}
+
+impl AccessLogger {
+    pub fn from_config(config: &ServerAccessLog) -> Result<Self, CardinalError> {
+        let format = match config.format {
+            AccessLogFormat::Json => LineFormat::Json,
+            AccessLogFormat::Combined => LineFormat::Combined,
+            AccessLogFormat::Template => {
+                let template = config
+                    .template
+                    .as_deref()
+                    .ok_or_else(|| invalid_access_log("the Template format needs a template"))?;
+                LineFormat::Template(parse_template(template)?)
+            }
+        };
+
+        let sink = SinkWriter::open(&config.sink)
+            .map_err(|e| invalid_access_log(&format!("failed to open sink: {e}")))?;
+        let (sender, receiver) = sync_channel(QUEUE_CAPACITY);
+        std::thread::Builder::new()
+            .name("access-log".into())
+            .spawn(move || write_lines(receiver, sink))?;
+
+        Ok(Self {
+            format: Arc::new(format),
+            sender,
+        })
+    }
+
+    pub fn log(&self, entry: &AccessLogEntry) {
+        match self.sender.try_send(self.format.render(entry)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => ACCESS_LOG_DROPPED_TOTAL.inc(),
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+}
+
+fn write_lines(receiver: Receiver<String>, mut sink: SinkWriter) {
+    while let Ok(line) = receiver.recv() {
+        let mut result = sink.write_line(&line);
+        while let Ok(line) = receiver.try_recv() {
+            result = result.and(sink.write_line(&line));
+        }
+        if let Err(err) = result.and(sink.flush()) {
+            warn!(%err, "Failed to write access log");
+        }
+    }
+}
+
+enum SinkWriter {
+    Stdout(io::Stdout),
+    File(RotatingFile),
+    Udp(UdpSocket),
+}
+
+impl SinkWriter {
+    fn open(config: &AccessLogSink) -> io::Result<Self> {
+        match config {
+            AccessLogSink::Stdout => Ok(SinkWriter::Stdout(io::stdout())),
+            AccessLogSink::File {
+                path,
+                max_bytes,
+                max_files,
+            } => RotatingFile::open(PathBuf::from(path), *max_bytes, *max_files)
+                .map(SinkWriter::File),
+            AccessLogSink::Udp { address } => {
+                let target = address.to_socket_addrs()?.next().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "address did not resolve")
+                })?;
+                let socket = if target.is_ipv4() {
+                    UdpSocket::bind(("0.0.0.0", 0))?
+                } else {
+                    UdpSocket::bind(("::", 0))?
+                };
+                socket.connect(target)?;
+                Ok(SinkWriter::Udp(socket))
+            }
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        match self {
+            SinkWriter::Stdout(stdout) => writeln!(stdout.lock(), "{line}"),
+            SinkWriter::File(file) => file.write_line(line),
+            SinkWriter::Udp(socket) => socket.send(line.as_bytes()).map(|_| ()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            SinkWriter::Stdout(stdout) => stdout.flush(),
+            SinkWriter::File(file) => file.file.flush(),
+            SinkWriter::Udp(_) => Ok(()),
+        }
+    }
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: Option<u64>,
+    max_files: u32,
+    file: BufWriter<File>,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_bytes: Option<u64>, max_files: u32) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files,
+            file: BufWriter::new(file),
+            size,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self
+            .max_bytes
+            .is_some_and(|max| self.size > 0 && self.size + len > max)
+        {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{line}")?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Shifts `path.N` to `path.N+1`, dropping the oldest, and starts a fresh file.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        let rotated = |index: u32| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{index}"));
+            PathBuf::from(name)
+        };
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                match std::fs::rename(rotated(index), rotated(index + 1)) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+            std::fs::rename(&self.path, rotated(1))?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.file = BufWriter::new(file);
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn invalid_access_log(reason: &str) -> CardinalError {
+    CardinalError::InternalError(CardinalInternalError::InvalidAccessLogConfiguration(
+        reason.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> AccessLogEntry {
+        AccessLogEntry {
+            timestamp: "2025-03-04T05:06:07+00:00".into(),
+            client_ip: Some("10.0.0.1".into()),
+            method: "GET".into(),
+            path: "/api/items?page=2".into(),
+            protocol: "HTTP/1.1".into(),
+            destination: Some("api".into()),
+            upstream: Some("http://127.0.0.1:9000".into()),
+            status: 200,
+            grpc_status: None,
+            bytes_in: 0,
+            bytes_out: 42,
+            duration_ms: 1.5,
+            upstream_duration_ms: None,
+            retries: 0,
+            request_id: None,
+            user_agent: Some("curl/8.0".into()),
+            referer: None,
+        }
+    }
+
+    #[test]
+    fn template_renders_fields_and_dashes_missing_ones() {
+        let format = LineFormat::Template(
+            parse_template("{method} {path} -> {destination} {status} id={request_id}").unwrap(),
+        );
+
+        assert_eq!(
+            format.render(&entry()),
+            "GET /api/items?page=2 -> api 200 id=-"
+        );
+    }
+
+    #[test]
+    fn template_rejects_unknown_and_unclosed_placeholders() {
+        assert!(parse_template("{nope}").is_err());
+        assert!(parse_template("{status").is_err());
+    }
+
+    #[test]
+    fn combined_log_format() {
+        assert_eq!(
+            LineFormat::Combined.render(&entry()),
+            r#"10.0.0.1 - - [04/Mar/2025:05:06:07 +0000] "GET /api/items?page=2 HTTP/1.1" 200 42 "-" "curl/8.0""#
+        );
+    }
+
+    #[test]
+    fn combined_log_format_dashes_empty_body() {
+        let entry = AccessLogEntry {
+            status: 304,
+            bytes_out: 0,
+            ..entry()
+        };
+        assert_eq!(
+            LineFormat::Combined.render(&entry),
+            r#"10.0.0.1 - - [04/Mar/2025:05:06:07 +0000] "GET /api/items?page=2 HTTP/1.1" 304 - "-" "curl/8.0""#
+        );
+    }
+
+    #[test]
+    fn h1_header_len_matches_the_wire() {
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        resp.insert_header("content-length", "5").unwrap();
+        let wire = "HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n";
+        assert_eq!(h1_header_len(&resp), wire.len());
+    }
+
+    #[test]
+    fn file_rotates_past_max_bytes() {
+        let dir = std::env::temp_dir().join(format!("cardinal-access-log-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("access.log");
+        let _ = std::fs::remove_file(&path);
+
+        let mut file = RotatingFile::open(path.clone(), Some(10), 2).unwrap();
+        for line in ["first", "second", "third", "fourth"] {
+            file.write_line(line).unwrap();
+        }
+        file.file.flush().unwrap();
+
+        let read = |suffix: &str| {
+            std::fs::read_to_string(format!("{}{suffix}", path.display())).unwrap_or_default()
+        };
+        assert_eq!(read(""), "fourth\n");
+        assert_eq!(read(".1"), "third\n");
+        assert_eq!(read(".2"), "second\n");
+        assert!(!dir.join("access.log.3").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,638 @@
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use cardinal_config::{DestinationCache, DestinationCacheKey, ServerCache};
+use cardinal_errors::CardinalError;
+use cardinal_plugins::utils::parse_query_string_multi;
+use parking_lot::RwLock;
+use pingora::cache::cache_control::{CacheControl, Cacheable, InterpretCacheControl};
+use pingora::cache::eviction::{lru, EvictionManager};
+use pingora::cache::filters::resp_cacheable;
+use pingora::cache::key::{CacheHashKey, CompactCacheKey, HashBinary};
+use pingora::cache::lock::CacheLock;
+use pingora::cache::storage::{HandleHit, HandleMiss, MissFinishType};
+use pingora::cache::trace::SpanHandle;
+use pingora::cache::{
+    CacheKey, CacheMeta, CacheMetaDefaults, CacheOptionOverrides, HitHandler, MissHandler,
+    NoCacheReason, PurgeType, RespCacheable, Storage, VarianceBuilder,
+};
+use pingora::http::{RequestHeader, ResponseHeader};
+use pingora::prelude::*;
+use pingora::proxy::Session;
+use std::any::Any;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{File, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::warn;
+
+const EVICTION_SHARDS: usize = 16;
+
+/// Prefix of the body directories created under `disk_path`, one per process.
+const DISK_DIR_PREFIX: &str = "cardinal-cache-";
+/// File locked by the process owning a body directory.
+const DISK_DIR_LOCK: &str = ".lock";
+
+/// Statuses cached for `default_ttl_secs` when the response carries no freshness information.
+const DEFAULT_TTL_STATUSES: [u16; 6] = [200, 203, 204, 301, 404, 410];
+
+/// Response store shared by every destination with a `cache`, plugged into pingora's HTTP
+/// cache: pingora runs the lookups, revalidation, stale serving and miss coalescing, this
+/// keeps the objects and evicts the least recently used ones beyond `max_size_bytes`.
+pub struct ResponseCache {
+    storage: CacheStorage,
+    eviction: lru::Manager<EVICTION_SHARDS>,
+    lock: CacheLock,
+    lock_timeout: Duration,
+}
+
+impl ResponseCache {
+    pub fn new(config: &ServerCache) -> Result<Self, CardinalError> {
+        let (dir, dir_lock) = match &config.disk_path {
+            Some(path) => {
+                let (dir, lock) = open_disk_dir(Path::new(path))?;
+                (Some(dir), Some(lock))
+            }
+            None => (None, None),
+        };
+
+        Ok(Self {
+            storage: CacheStorage {
+                dir,
+                _dir_lock: dir_lock,
+                objects: RwLock::new(HashMap::new()),
+            },
+            eviction: lru::Manager::with_capacity(config.max_size_bytes, 1024),
+            lock: CacheLock::new(Duration::from_millis(config.lock_timeout_ms)),
+            lock_timeout: Duration::from_millis(config.lock_timeout_ms),
+        })
+    }
+
+    /// Turns the cache on for a request of a destination with `config`.
+    pub(crate) fn enable(&'static self, session: &mut Session, config: &DestinationCache) {
+        let mut overrides = CacheOptionOverrides::default();
+        overrides.wait_timeout = Some(self.lock_timeout);
+        session.cache.enable(
+            &self.storage,
+            Some(&self.eviction),
+            None,
+            Some(&self.lock),
+            Some(overrides),
+        );
+        session
+            .cache
+            .set_max_file_size_bytes(config.max_object_bytes);
+    }
+
+    /// Drops the responses of `destination`, only those under `path_prefix` when given.
+    /// Returns the number of responses removed.
+    pub async fn purge(&self, destination: &str, path_prefix: Option<&str>) -> usize {
+        let purged = {
+            let mut objects = self.storage.objects.write();
+            let keys = objects
+                .iter()
+                .filter(|(_, object)| {
+                    object.destination == destination
+                        && path_prefix.is_none_or(|prefix| object.path.starts_with(prefix))
+                })
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>();
+            keys.into_iter()
+                .filter_map(|key| objects.remove(&key).map(|object| (key, object)))
+                .collect::<Vec<_>>()
+        };
+
+        for (key, object) in &purged {
+            self.eviction.remove(&object.compact_key);
+            self.storage.remove_body(key).await;
+        }
+        purged.len()
+    }
+}
+
+/// Key of a cacheable request: the destination as namespace, then the path and the configured
+/// query parameters, host and headers.
+pub(crate) fn cache_key(
+    destination: &str,
+    config: &DestinationCacheKey,
+    req: &RequestHeader,
+) -> CacheKey {
+    let mut primary = req.uri.path().to_string();
+    match (&config.query, req.uri.query()) {
+        (None, Some(query)) => {
+            primary.push('?');
+            primary.push_str(query);
+        }
+        (Some(names), Some(query)) => {
+            let params = parse_query_string_multi(query);
+            let selected = names
+                .iter()
+                .filter_map(|name| params.get(name).map(|values| (name, values)))
+                .flat_map(|(name, values)| {
+                    values.iter().map(move |value| format!("{name}={value}"))
+                })
+                .collect::<Vec<_>>();
+            if !selected.is_empty() {
+                primary.push('?');
+                primary.push_str(&selected.join("&"));
+            }
+        }
+        (_, None) => {}
+    }
+
+    if config.host {
+        let host = req
+            .headers
+            .get(http::header::HOST)
+            .and_then(|value| value.to_str().ok())
+            .or_else(|| req.uri.host())
+            .unwrap_or_default();
+        primary.push_str(&format!("\nhost: {}", host.to_ascii_lowercase()));
+    }
+    for name in &config.headers {
+        for value in req.headers.get_all(name.as_str()) {
+            primary.push_str(&format!("\n{}: ", name.to_ascii_lowercase()));
+            primary.push_str(&String::from_utf8_lossy(value.as_bytes()));
+        }
+    }
+
+    CacheKey::new(destination, primary, "")
+}
+
+/// Only `GET` responses are cached, `HEAD` and other methods go upstream.
+pub(crate) fn request_cacheable(req: &RequestHeader) -> bool {
+    req.method == http::Method::GET
+}
+
+/// Follows `Cache-Control` and `Expires`, falling back to `default_ttl_secs` for responses
+/// without freshness information. Responses setting cookies or varying on `*` are not cached.
+pub(crate) fn response_cacheable(
+    config: &DestinationCache,
+    req: &RequestHeader,
+    resp: &ResponseHeader,
+) -> RespCacheable {
+    if resp.headers.contains_key(http::header::SET_COOKIE) {
+        return RespCacheable::Uncacheable(NoCacheReason::Custom("set-cookie"));
+    }
+    if vary_headers(resp).any(|name| name == "*") {
+        return RespCacheable::Uncacheable(NoCacheReason::Custom("vary *"));
+    }
+
+    let cache_control = CacheControl::from_resp_headers(resp);
+    let authorization = req.headers.contains_key(http::header::AUTHORIZATION);
+    let defaults = CacheMetaDefaults::new(
+        |_| None,
+        config.stale_while_revalidate_secs,
+        config.stale_if_error_secs,
+    );
+    let cacheable = resp_cacheable(
+        cache_control.as_ref(),
+        resp.clone(),
+        authorization,
+        &defaults,
+    );
+
+    let Some(ttl) = config.default_ttl_secs else {
+        return cacheable;
+    };
+    let explicit = cache_control
+        .as_ref()
+        .is_some_and(|cc| cc.is_cacheable() != Cacheable::Default)
+        || resp.headers.contains_key(http::header::EXPIRES);
+    if !matches!(cacheable, RespCacheable::Uncacheable(_))
+        || explicit
+        || authorization
+        || !DEFAULT_TTL_STATUSES.contains(&resp.status.as_u16())
+    {
+        return cacheable;
+    }
+
+    let now = SystemTime::now();
+    RespCacheable::Cacheable(CacheMeta::new(
+        now + Duration::from_secs(ttl),
+        now,
+        config.stale_while_revalidate_secs,
+        config.stale_if_error_secs,
+        resp.clone(),
+    ))
+}
+
+/// Variant of a cached response selected by the request, from the response's `Vary` header.
+pub(crate) fn variance(meta: &CacheMeta, req: &RequestHeader) -> Option<HashBinary> {
+    let names = vary_headers(meta.response_header()).collect::<Vec<_>>();
+    let mut builder = VarianceBuilder::new();
+    for name in &names {
+        let value = req
+            .headers
+            .get(name.as_str())
+            .map(|value| value.as_bytes())
+            .unwrap_or_default();
+        builder.add_value(name, value);
+    }
+    builder.finalize()
+}
+
+fn vary_headers(resp: &ResponseHeader) -> impl Iterator<Item = String> + '_ {
+    resp.headers
+        .get_all(http::header::VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+}
+
+struct StoredObject {
+    destination: String,
+    path: String,
+    compact_key: CompactCacheKey,
+    meta: (Vec<u8>, Vec<u8>),
+    /// Empty when the body lives on disk.
+    body: Bytes,
+}
+
+/// Creates a body directory of this process under `disk_path` and removes those of processes
+/// that have exited, leaving everything else there alone. A directory is locked through its
+/// `.lock` file while its process runs, and only gets its final name once locked.
+fn open_disk_dir(disk_path: &Path) -> Result<(PathBuf, File), CardinalError> {
+    std::fs::create_dir_all(disk_path)?;
+    let name = format!("{DISK_DIR_PREFIX}{}", uuid::Uuid::now_v7());
+    let staging = disk_path.join(format!(".{name}"));
+    std::fs::create_dir(&staging)?;
+    let lock = File::create(staging.join(DISK_DIR_LOCK))?;
+    lock.try_lock().map_err(io::Error::from)?;
+    let dir = disk_path.join(name);
+    std::fs::rename(&staging, &dir)?;
+
+    for entry in std::fs::read_dir(disk_path)? {
+        let path = entry?.path();
+        let ours = path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .is_some_and(|name| name.starts_with(DISK_DIR_PREFIX));
+        if ours && path != dir && path.is_dir() && !disk_dir_in_use(&path) {
+            // Bodies are only indexed in memory, those of an exited process are unreachable.
+            if let Err(err) = std::fs::remove_dir_all(&path) {
+                warn!(%err, path = %path.display(), "Failed to remove stale cache directory");
+            }
+        }
+    }
+
+    Ok((dir, lock))
+}
+
+/// Whether the process owning a body directory still runs.
+fn disk_dir_in_use(dir: &Path) -> bool {
+    File::open(dir.join(DISK_DIR_LOCK))
+        .is_ok_and(|lock| matches!(lock.try_lock(), Err(TryLockError::WouldBlock)))
+}
+
+/// Objects keyed by their combined cache key hash. Metadata stays in memory, bodies go to
+/// `dir` when set.
+struct CacheStorage {
+    dir: Option<PathBuf>,
+    /// Held for the lifetime of the process, marks `dir` as in use.
+    _dir_lock: Option<File>,
+    objects: RwLock<HashMap<String, StoredObject>>,
+}
+
+impl CacheStorage {
+    fn body_path(&self, key: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(key))
+    }
+
+    async fn remove_body(&self, key: &str) {
+        if let Some(path) = self.body_path(key) {
+            if let Err(err) = tokio::fs::remove_file(&path).await {
+                warn!(%err, path = %path.display(), "Failed to remove cached body");
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for CacheStorage {
+    async fn lookup(
+        &'static self,
+        key: &CacheKey,
+        _trace: &SpanHandle,
+    ) -> Result<Option<(CacheMeta, HitHandler)>> {
+        let hash = key.combined();
+        let Some((meta, body)) = self
+            .objects
+            .read()
+            .get(&hash)
+            .map(|object| (object.meta.clone(), object.body.clone()))
+        else {
+            return Ok(None);
+        };
+
+        let meta = CacheMeta::deserialize(&meta.0, &meta.1)?;
+        let body = match self.body_path(&hash) {
+            Some(path) => match tokio::fs::read(&path).await {
+                Ok(body) => Bytes::from(body),
+                // Treat an unreadable body as a miss, the response gets stored again.
+                Err(err) => {
+                    warn!(%err, path = %path.display(), "Failed to read cached body");
+                    return Ok(None);
+                }
+            },
+            None => body,
+        };
+        let end = body.len();
+
+        Ok(Some((
+            meta,
+            Box::new(CachedBody {
+                body,
+                start: 0,
+                end,
+                done: false,
+            }),
+        )))
+    }
+
+    async fn get_miss_handler(
+        &'static self,
+        key: &CacheKey,
+        meta: &CacheMeta,
+        _trace: &SpanHandle,
+    ) -> Result<MissHandler> {
+        let primary = String::from_utf8_lossy(key.primary_key());
+        let path = primary
+            .split(['?', '\n'])
+            .next()
+            .unwrap_or_default()
+            .to_string();
+
+        Ok(Box::new(CacheWriter {
+            storage: self,
+            key: key.combined(),
+            object: Some(StoredObject {
+                destination: String::from_utf8_lossy(key.namespace()).into_owned(),
+                path,
+                compact_key: key.to_compact(),
+                meta: meta.serialize()?,
+                body: Bytes::new(),
+            }),
+            body: BytesMut::new(),
+        }))
+    }
+
+    async fn purge(
+        &'static self,
+        key: &CompactCacheKey,
+        _purge_type: PurgeType,
+        _trace: &SpanHandle,
+    ) -> Result<bool> {
+        let hash = key.combined();
+        let removed = self.objects.write().remove(&hash).is_some();
+        if removed {
+            self.remove_body(&hash).await;
+        }
+        Ok(removed)
+    }
+
+    async fn update_meta(
+        &'static self,
+        key: &CacheKey,
+        meta: &CacheMeta,
+        _trace: &SpanHandle,
+    ) -> Result<bool> {
+        let meta = meta.serialize()?;
+        match self.objects.write().get_mut(&key.combined()) {
+            Some(object) => {
+                object.meta = meta;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn as_any(&self) -> &(dyn Any + Send + Sync + 'static) {
+        self
+    }
+}
+
+struct CachedBody {
+    body: Bytes,
+    start: usize,
+    end: usize,
+    done: bool,
+}
+
+#[async_trait]
+impl HandleHit for CachedBody {
+    async fn read_body(&mut self) -> Result<Option<Bytes>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        Ok(Some(self.body.slice(self.start..self.end)))
+    }
+
+    async fn finish(
+        self: Box<Self>,
+        _storage: &'static (dyn Storage + Sync),
+        _key: &CacheKey,
+        _trace: &SpanHandle,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    fn seek(&mut self, start: usize, end: Option<usize>) -> Result<()> {
+        if start >= self.body.len() {
+            return Error::e_explain(
+                ErrorType::InternalError,
+                format!("seek start out of range {start} >= {}", self.body.len()),
+            );
+        }
+        self.start = start;
+        self.end = end.map_or(self.body.len(), |end| end.min(self.body.len()));
+        self.done = false;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &(dyn Any + Send + Sync) {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut (dyn Any + Send + Sync) {
+        self
+    }
+}
+
+/// Buffers a response body and stores the object once it is complete.
+struct CacheWriter {
+    storage: &'static CacheStorage,
+    key: String,
+    object: Option<StoredObject>,
+    body: BytesMut,
+}
+
+#[async_trait]
+impl HandleMiss for CacheWriter {
+    async fn write_body(&mut self, data: Bytes, _eof: bool) -> Result<()> {
+        self.body.extend_from_slice(&data);
+        Ok(())
+    }
+
+    async fn finish(mut self: Box<Self>) -> Result<MissFinishType> {
+        let body = std::mem::take(&mut self.body).freeze();
+        let size = body.len();
+        let Some(mut object) = self.object.take() else {
+            return Error::e_explain(ErrorType::InternalError, "cache object already stored");
+        };
+
+        match self.storage.body_path(&self.key) {
+            Some(path) => tokio::fs::write(&path, &body)
+                .await
+                .or_err(ErrorType::WriteError, "writing cached body")?,
+            None => object.body = body,
+        }
+        self.storage
+            .objects
+            .write()
+            .insert(self.key.clone(), object);
+
+        Ok(MissFinishType::Created(size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::Method;
+
+    fn request(uri: &str) -> RequestHeader {
+        let mut req = RequestHeader::build(Method::GET, uri.as_bytes(), None).unwrap();
+        req.insert_header("Host", "Shop.example.com").unwrap();
+        req.insert_header("Accept-Language", "de").unwrap();
+        req
+    }
+
+    fn response(headers: &[(&'static str, &str)]) -> ResponseHeader {
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        for (name, value) in headers {
+            resp.insert_header(*name, *value).unwrap();
+        }
+        resp
+    }
+
+    #[test]
+    fn cache_key_uses_configured_parts() {
+        let key = |config: &DestinationCacheKey, uri| {
+            let key = cache_key("shop", config, &request(uri));
+            String::from_utf8(key.primary_key().to_vec()).unwrap()
+        };
+
+        let mut config = DestinationCacheKey::default();
+        assert_eq!(
+            key(&config, "/items?page=2&utm=x"),
+            "/items?page=2&utm=x\nhost: shop.example.com"
+        );
+
+        config.host = false;
+        config.query = Some(vec!["page".into()]);
+        config.headers = vec!["Accept-Language".into()];
+        assert_eq!(
+            key(&config, "/items?utm=x&page=2"),
+            "/items?page=2\naccept-language: de"
+        );
+        assert_eq!(
+            key(&config, "/items?utm=y&page=2"),
+            key(&config, "/items?utm=x&page=2")
+        );
+    }
+
+    #[test]
+    fn response_cacheability_follows_headers_and_defaults() {
+        let req = request("/items");
+        let mut config = DestinationCache::default();
+        let cacheable = |config: &DestinationCache, resp: &ResponseHeader| {
+            matches!(
+                response_cacheable(config, &req, resp),
+                RespCacheable::Cacheable(_)
+            )
+        };
+
+        assert!(cacheable(
+            &config,
+            &response(&[("Cache-Control", "max-age=60")])
+        ));
+        assert!(!cacheable(&config, &response(&[])));
+        assert!(!cacheable(
+            &config,
+            &response(&[("Cache-Control", "max-age=60"), ("Set-Cookie", "a=b")])
+        ));
+        assert!(!cacheable(
+            &config,
+            &response(&[("Cache-Control", "max-age=60"), ("Vary", "*")])
+        ));
+
+        config.default_ttl_secs = Some(30);
+        assert!(cacheable(&config, &response(&[])));
+        assert!(!cacheable(
+            &config,
+            &response(&[("Cache-Control", "no-store")])
+        ));
+    }
+
+    #[test]
+    fn variance_follows_vary_header() {
+        let meta = |vary: &str| {
+            let now = SystemTime::now();
+            CacheMeta::new(now, now, 0, 0, response(&[("Vary", vary)]))
+        };
+        let mut english = request("/items");
+        english.insert_header("Accept-Language", "en").unwrap();
+
+        let by_language = meta("Accept-Language");
+        assert_ne!(
+            variance(&by_language, &request("/items")),
+            variance(&by_language, &english)
+        );
+        let by_encoding = meta("Accept-Encoding");
+        assert_eq!(
+            variance(&by_encoding, &request("/items")),
+            variance(&by_encoding, &english)
+        );
+    }
+
+    #[test]
+    fn disk_storage_only_removes_directories_of_exited_processes() {
+        let disk_path = std::env::temp_dir().join(format!("cardinal-{}", uuid::Uuid::now_v7()));
+        let stale = disk_path.join("cardinal-cache-1234");
+        std::fs::create_dir_all(&stale).unwrap();
+        std::fs::write(stale.join(".lock"), "").unwrap();
+        std::fs::write(stale.join("0123456789abcdef0123456789abcdef"), "body").unwrap();
+        std::fs::write(disk_path.join("data.db"), "keep").unwrap();
+
+        let config = ServerCache {
+            disk_path: Some(disk_path.to_string_lossy().into_owned()),
+            ..ServerCache::default()
+        };
+        let running = ResponseCache::new(&config).unwrap();
+        let running_dir = running.storage.dir.clone().unwrap();
+        assert!(running_dir.join(".lock").exists());
+        assert!(!stale.exists());
+        assert!(disk_path.join("data.db").exists());
+
+        // A second process sharing `disk_path` keeps the directory of the running one.
+        let second = ResponseCache::new(&config).unwrap();
+        assert_ne!(second.storage.dir.as_ref(), Some(&running_dir));
+        assert!(running_dir.exists());
+
+        drop(running);
+        let third = ResponseCache::new(&config).unwrap();
+        assert!(!running_dir.exists());
+        assert!(second.storage.dir.as_ref().unwrap().exists());
+        drop((second, third));
+        std::fs::remove_dir_all(disk_path).unwrap();
+    }
+}
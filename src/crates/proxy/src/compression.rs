@@ -0,0 +1,217 @@
+use cardinal_config::{CompressionAlgorithm, DestinationCompression};
+use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE};
+use http::Method;
+use pingora::http::{RequestHeader, ResponseHeader};
+use pingora::modules::http::compression::ResponseCompression;
+use pingora::proxy::Session;
+
+/// Picks the encoding for responses to `req`: the offered algorithm with the highest `q` in
+/// `Accept-Encoding`, ties going to the one listed first in `config`.
+pub(crate) fn negotiate(
+    config: &DestinationCompression,
+    req: &RequestHeader,
+) -> Option<CompressionAlgorithm> {
+    let accepted = req
+        .headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_coding)
+        .collect::<Vec<_>>();
+    let weight = |token: &str| {
+        accepted
+            .iter()
+            .find(|(coding, _)| coding == token)
+            .or_else(|| accepted.iter().find(|(coding, _)| coding == "*"))
+            .map_or(0.0, |(_, q)| *q)
+    };
+
+    let mut best = None;
+    for algorithm in &config.algorithms {
+        let q = weight(token(*algorithm));
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((*algorithm, q));
+        }
+    }
+    best.map(|(algorithm, _)| algorithm)
+}
+
+/// Whether a response qualifies: not encoded yet, of an allowed media type and not announced
+/// smaller than `min_size_bytes`. Responses of unknown length qualify.
+pub(crate) fn compressible(config: &DestinationCompression, resp: &ResponseHeader) -> bool {
+    if resp.headers.contains_key(CONTENT_ENCODING) {
+        return false;
+    }
+    let too_small = resp
+        .headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .is_some_and(|length| length < config.min_size_bytes);
+    if too_small {
+        return false;
+    }
+
+    let Some(media_type) = resp
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+    else {
+        return false;
+    };
+    config.content_types.iter().any(|allowed| {
+        let allowed = allowed.to_ascii_lowercase();
+        match allowed.strip_suffix("/*") {
+            Some(kind) => media_type
+                .split_once('/')
+                .is_some_and(|(media_kind, _)| media_kind == kind),
+            None => media_type == allowed,
+        }
+    })
+}
+
+/// Hands the negotiated encoding to pingora's compression module, which compresses the
+/// response body and sets `Content-Encoding` and `Vary`.
+pub(crate) fn enable(
+    session: &mut Session,
+    config: &DestinationCompression,
+    algorithm: CompressionAlgorithm,
+) {
+    let Some(module) = session
+        .downstream_modules_ctx
+        .get_mut::<ResponseCompression>()
+    else {
+        return;
+    };
+    // The module only reads `Accept-Encoding` when enabled and takes the first coding listed,
+    // feed it the negotiated one alone.
+    let Ok(mut req) = RequestHeader::build(Method::GET, b"/", None) else {
+        return;
+    };
+    if req
+        .insert_header(ACCEPT_ENCODING, token(algorithm))
+        .is_err()
+    {
+        return;
+    }
+    module.adjust_level(config.level);
+    module.request_filter(&req);
+}
+
+/// Sends the response as is after all.
+pub(crate) fn disable(session: &mut Session) {
+    if let Some(module) = session
+        .downstream_modules_ctx
+        .get_mut::<ResponseCompression>()
+    {
+        module.adjust_level(0);
+    }
+}
+
+fn token(algorithm: CompressionAlgorithm) -> &'static str {
+    match algorithm {
+        CompressionAlgorithm::Gzip => "gzip",
+        CompressionAlgorithm::Brotli => "br",
+        CompressionAlgorithm::Zstd => "zstd",
+    }
+}
+
+fn parse_coding(item: &str) -> Option<(String, f32)> {
+    let mut parts = item.split(';');
+    let coding = parts.next()?.trim().to_ascii_lowercase();
+    if coding.is_empty() {
+        return None;
+    }
+    let q = parts
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+        .map_or(Some(1.0), |(_, value)| value.trim().parse::<f32>().ok())?;
+    let coding = if coding == "x-gzip" {
+        "gzip".to_string()
+    } else {
+        coding
+    };
+    Some((coding, q))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(accept_encoding: &str) -> RequestHeader {
+        let mut req = RequestHeader::build(Method::GET, b"/", None).unwrap();
+        req.insert_header(ACCEPT_ENCODING, accept_encoding).unwrap();
+        req
+    }
+
+    fn response(headers: &[(&'static str, &str)]) -> ResponseHeader {
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        for (name, value) in headers {
+            resp.insert_header(*name, *value).unwrap();
+        }
+        resp
+    }
+
+    #[test]
+    fn negotiates_by_quality_then_configured_order() {
+        let config = DestinationCompression::default();
+        let negotiate = |accept_encoding| negotiate(&config, &request(accept_encoding));
+
+        assert_eq!(
+            negotiate("gzip, deflate, br, zstd"),
+            Some(CompressionAlgorithm::Brotli)
+        );
+        assert_eq!(
+            negotiate("br;q=0.5, gzip"),
+            Some(CompressionAlgorithm::Gzip)
+        );
+        assert_eq!(
+            negotiate("x-gzip, br;q=0"),
+            Some(CompressionAlgorithm::Gzip)
+        );
+        assert_eq!(negotiate("*"), Some(CompressionAlgorithm::Brotli));
+        assert_eq!(negotiate("identity, deflate"), None);
+
+        let config = DestinationCompression {
+            algorithms: vec![CompressionAlgorithm::Gzip],
+            ..DestinationCompression::default()
+        };
+        assert_eq!(super::negotiate(&config, &request("br, zstd")), None);
+    }
+
+    #[test]
+    fn compressible_checks_encoding_size_and_type() {
+        let config = DestinationCompression::default();
+
+        assert!(compressible(
+            &config,
+            &response(&[("Content-Type", "text/html; charset=utf-8")])
+        ));
+        assert!(compressible(
+            &config,
+            &response(&[
+                ("Content-Type", "application/json"),
+                ("Content-Length", "4096")
+            ])
+        ));
+        assert!(!compressible(
+            &config,
+            &response(&[
+                ("Content-Type", "application/json"),
+                ("Content-Length", "100")
+            ])
+        ));
+        assert!(!compressible(
+            &config,
+            &response(&[("Content-Type", "text/css"), ("Content-Encoding", "gzip")])
+        ));
+        assert!(!compressible(
+            &config,
+            &response(&[("Content-Type", "image/png")])
+        ));
+        assert!(!compressible(&config, &response(&[])));
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Shared flag telling the proxy to wind down client connections. While draining,
+/// responses close the downstream connection instead of keeping it alive, and
+/// [`DrainState::wait_idle`] waits for the requests still in flight.
+#[derive(Debug, Clone, Default)]
+pub struct DrainState {
+    draining: Arc<AtomicBool>,
+    in_flight: Arc<InFlight>,
+}
+
+#[derive(Debug, Default)]
+struct InFlight {
+    count: AtomicUsize,
+    idle: Notify,
+}
+
+impl DrainState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start(&self) {
+        self.draining.store(true, Ordering::Release);
+    }
+
+    pub fn resume(&self) {
+        self.draining.store(false, Ordering::Release);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    /// Counts a request as in flight until the returned guard is dropped.
+    pub fn track(&self) -> InFlightRequest {
+        self.in_flight.count.fetch_add(1, Ordering::AcqRel);
+        InFlightRequest {
+            in_flight: self.in_flight.clone(),
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.count.load(Ordering::Acquire)
+    }
+
+    /// Waits up to `timeout` for the requests in flight to finish. Returns `false` when some
+    /// are still running at the deadline.
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let idle = async {
+            loop {
+                let notified = self.in_flight.idle.notified();
+                if self.in_flight() == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        };
+        tokio::time::timeout(timeout, idle).await.is_ok()
+    }
+}
+
+/// Request counted by [`DrainState::track`].
+#[derive(Debug)]
+pub struct InFlightRequest {
+    in_flight: Arc<InFlight>,
+}
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        if self.in_flight.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.in_flight.idle.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn wait_idle_follows_requests_in_flight() {
+        let drain = DrainState::new();
+        assert!(drain.wait_idle(Duration::from_millis(10)).await);
+
+        let request = drain.track();
+        assert_eq!(drain.in_flight(), 1);
+        assert!(!drain.wait_idle(Duration::from_millis(10)).await);
+
+        let waiting = tokio::spawn({
+            let drain = drain.clone();
+            async move { drain.wait_idle(Duration::from_secs(5)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(request);
+        assert!(waiting.await.unwrap());
+        assert_eq!(drain.in_flight(), 0);
+    }
+}
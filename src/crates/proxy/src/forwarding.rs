@@ -0,0 +1,229 @@
+use cardinal_config::{parse_network, ForwardedForMode, ServerForwarding};
+use cardinal_errors::internal::CardinalInternalError;
+use cardinal_errors::CardinalError;
+use ipnet::IpNet;
+use pingora::http::RequestHeader;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+const X_FORWARDED_PORT: &str = "x-forwarded-port";
+const X_REAL_IP: &str = "x-real-ip";
+const FORWARDED: &str = "forwarded";
+
+/// Writes `X-Forwarded-*`, `X-Real-IP` and `Forwarded` on requests before they go upstream.
+#[derive(Clone)]
+pub struct ForwardingHeaders {
+    settings: Arc<ServerForwarding>,
+    trusted: Arc<Vec<IpNet>>,
+}
+
+/// The downstream connection a request arrived on.
+pub(crate) struct DownstreamConnection {
+    pub peer: IpAddr,
+    pub tls: bool,
+    pub port: Option<u16>,
+}
+
+impl ForwardingHeaders {
+    pub fn from_config(config: &ServerForwarding) -> Result<Self, CardinalError> {
+        let trusted = config
+            .trusted_proxies
+            .iter()
+            .map(|network| {
+                parse_network(network).ok_or_else(|| {
+                    CardinalError::InternalError(
+                        CardinalInternalError::InvalidForwardingConfiguration(format!(
+                            "invalid trusted proxy network {network}"
+                        )),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            settings: Arc::new(config.clone()),
+            trusted: Arc::new(trusted),
+        })
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|network| network.contains(&ip))
+    }
+
+    /// Right-most address of the chain that is not a trusted proxy, i.e. the first hop that
+    /// could not have been spoofed.
+    fn client_from_chain(&self, chain: &str, peer: IpAddr) -> IpAddr {
+        let hops = chain
+            .split(',')
+            .filter_map(|hop| hop.trim().parse::<IpAddr>().ok())
+            .collect::<Vec<_>>();
+
+        hops.iter()
+            .rev()
+            .find(|hop| !self.is_trusted(**hop))
+            .or(hops.first())
+            .copied()
+            .unwrap_or(peer)
+    }
+
+    pub(crate) fn apply(&self, req: &mut RequestHeader, conn: &DownstreamConnection) {
+        let trusted = self.is_trusted(conn.peer);
+        let proto = if conn.tls { "https" } else { "http" };
+        let host = header(req, "host");
+
+        let (chain, forwarded) = if trusted {
+            (header(req, X_FORWARDED_FOR), header(req, FORWARDED))
+        } else {
+            for name in [
+                X_FORWARDED_FOR,
+                X_FORWARDED_PROTO,
+                X_FORWARDED_PORT,
+                X_REAL_IP,
+                FORWARDED,
+            ] {
+                req.remove_header(name);
+            }
+            (None, None)
+        };
+        let client = match &chain {
+            Some(chain) => self.client_from_chain(chain, conn.peer),
+            None => conn.peer,
+        };
+
+        let forwarded_for = match (&self.settings.x_forwarded_for, chain) {
+            (ForwardedForMode::Append, Some(chain)) => format!("{chain}, {}", conn.peer),
+            (ForwardedForMode::Append, None) => conn.peer.to_string(),
+            (ForwardedForMode::Replace, _) => client.to_string(),
+        };
+        let _ = req.insert_header(X_FORWARDED_FOR, forwarded_for);
+
+        if self.settings.x_forwarded_proto && header(req, X_FORWARDED_PROTO).is_none() {
+            let _ = req.insert_header(X_FORWARDED_PROTO, proto);
+        }
+        if self.settings.x_forwarded_port && header(req, X_FORWARDED_PORT).is_none() {
+            if let Some(port) = conn.port {
+                let _ = req.insert_header(X_FORWARDED_PORT, port.to_string());
+            }
+        }
+        if self.settings.x_real_ip {
+            let _ = req.insert_header(X_REAL_IP, client.to_string());
+        }
+        if self.settings.forwarded {
+            let mut element = format!("for={};proto={proto}", forwarded_node(conn.peer));
+            if let Some(host) = host {
+                element.push_str(&format!(";host=\"{host}\""));
+            }
+            let value = match forwarded {
+                Some(previous) => format!("{previous}, {element}"),
+                None => element,
+            };
+            let _ = req.insert_header(FORWARDED, value);
+        }
+    }
+}
+
+/// All values of a header joined as one comma separated list.
+fn header(req: &RequestHeader, name: &str) -> Option<String> {
+    let values = req
+        .headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>();
+
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+/// RFC 7239 node: IPv6 addresses are bracketed and quoted.
+fn forwarded_node(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("\"[{ip}]\""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forwarding(mode: ForwardedForMode) -> ForwardingHeaders {
+        ForwardingHeaders::from_config(&ServerForwarding {
+            x_forwarded_for: mode,
+            x_forwarded_proto: true,
+            x_forwarded_port: true,
+            x_real_ip: true,
+            forwarded: true,
+            trusted_proxies: vec!["10.0.0.0/8".into()],
+        })
+        .unwrap()
+    }
+
+    fn request() -> RequestHeader {
+        let mut req = RequestHeader::build("GET", b"/", None).unwrap();
+        req.insert_header("host", "api.example.com").unwrap();
+        req.insert_header(X_FORWARDED_FOR, "203.0.113.7, 10.1.1.1")
+            .unwrap();
+        req.insert_header(X_FORWARDED_PROTO, "https").unwrap();
+        req.insert_header(X_REAL_IP, "1.2.3.4").unwrap();
+        req.insert_header(FORWARDED, "for=203.0.113.7").unwrap();
+        req
+    }
+
+    fn connection(peer: &str) -> DownstreamConnection {
+        DownstreamConnection {
+            peer: peer.parse().unwrap(),
+            tls: false,
+            port: Some(8080),
+        }
+    }
+
+    #[test]
+    fn untrusted_peer_headers_are_replaced() {
+        let mut req = request();
+        forwarding(ForwardedForMode::Append).apply(&mut req, &connection("198.51.100.2"));
+
+        assert_eq!(req.headers[X_FORWARDED_FOR], "198.51.100.2");
+        assert_eq!(req.headers[X_FORWARDED_PROTO], "http");
+        assert_eq!(req.headers[X_FORWARDED_PORT], "8080");
+        assert_eq!(req.headers[X_REAL_IP], "198.51.100.2");
+        assert_eq!(
+            req.headers[FORWARDED],
+            "for=198.51.100.2;proto=http;host=\"api.example.com\""
+        );
+    }
+
+    #[test]
+    fn trusted_peer_chain_is_extended() {
+        let mut req = request();
+        forwarding(ForwardedForMode::Append).apply(&mut req, &connection("10.2.2.2"));
+
+        assert_eq!(
+            req.headers[X_FORWARDED_FOR],
+            "203.0.113.7, 10.1.1.1, 10.2.2.2"
+        );
+        assert_eq!(req.headers[X_FORWARDED_PROTO], "https");
+        assert_eq!(req.headers[X_REAL_IP], "203.0.113.7");
+        assert_eq!(
+            req.headers[FORWARDED],
+            "for=203.0.113.7, for=10.2.2.2;proto=http;host=\"api.example.com\""
+        );
+    }
+
+    #[test]
+    fn replace_mode_sends_resolved_client_only() {
+        let mut req = request();
+        forwarding(ForwardedForMode::Replace).apply(&mut req, &connection("10.2.2.2"));
+
+        assert_eq!(req.headers[X_FORWARDED_FOR], "203.0.113.7");
+    }
+
+    #[test]
+    fn ipv6_forwarded_node_is_quoted() {
+        assert_eq!(
+            forwarded_node("2001:db8::1".parse().unwrap()),
+            "\"[2001:db8::1]\""
+        );
+    }
+}
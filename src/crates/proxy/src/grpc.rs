@@ -0,0 +1,420 @@
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::{BufMut, Bytes, BytesMut};
+use cardinal_base::destinations::container::grpc_call;
+use cardinal_config::Destination;
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING};
+use http::{HeaderMap, StatusCode, Version};
+use pingora::http::{RequestHeader, ResponseHeader};
+use pingora::modules::http::{HttpModule, HttpModuleBuilder, Module};
+use pingora::prelude::*;
+use std::any::Any;
+
+const GRPC: &str = "application/grpc";
+const GRPC_WEB: &str = "application/grpc-web";
+const GRPC_WEB_TEXT: &str = "application/grpc-web-text";
+const GRPC_STATUS: &str = "grpc-status";
+const GRPC_MESSAGE: &str = "grpc-message";
+
+/// Flag byte of the gRPC-Web frame carrying the trailers.
+const TRAILER_FRAME: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrpcProtocol {
+    /// gRPC over HTTP/2, forwarded as is.
+    Native,
+    /// gRPC-Web with binary frames.
+    Web,
+    /// gRPC-Web with base64 encoded frames.
+    WebText,
+}
+
+/// Downstream module following gRPC calls, recognised by their content type. It records the
+/// `grpc-status` of the call, turns responses that are not gRPC, such as errors from the gateway
+/// or a middleware, into gRPC errors, and translates gRPC-Web to native gRPC for destinations
+/// serving gRPC.
+#[derive(Debug, Default)]
+pub struct GrpcModule {
+    protocol: Option<GrpcProtocol>,
+    /// Content type suffix of the call, e.g. `+proto`.
+    suffix: String,
+    downstream_h1: bool,
+    translate: bool,
+    /// Response replaced by a gRPC error, its body is dropped.
+    rejected: bool,
+    /// Base64 characters of the request body not decoded yet.
+    pending: Vec<u8>,
+    status: Option<String>,
+}
+
+impl GrpcModule {
+    pub fn protocol(&self) -> Option<GrpcProtocol> {
+        self.protocol
+    }
+
+    /// `grpc-status` of the call, from the response headers or trailers.
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    /// Sends a gRPC-Web call to the upstream as native gRPC.
+    pub(crate) fn translate(&mut self) {
+        self.translate = matches!(
+            self.protocol,
+            Some(GrpcProtocol::Web | GrpcProtocol::WebText)
+        );
+    }
+
+    /// Rewrites the copy of the request sent upstream, already HTTP/2, when translating.
+    pub(crate) fn upstream_request(&self, req: &mut RequestHeader) -> Result<()> {
+        if !self.translate {
+            return Ok(());
+        }
+        req.insert_header(CONTENT_TYPE, format!("{GRPC}{}", self.suffix))?;
+        // Required by gRPC over HTTP/2 to detect proxies dropping trailers.
+        req.insert_header("te", "trailers")?;
+        // The end of a gRPC request is flagged on its last DATA frame, even an empty one.
+        req.set_send_end_stream(false);
+        if self.protocol == Some(GrpcProtocol::WebText) {
+            req.remove_header(&CONTENT_LENGTH);
+        }
+        Ok(())
+    }
+
+    fn decode_text(&mut self, chunk: &[u8], end_of_stream: bool) -> Result<Bytes> {
+        self.pending
+            .extend(chunk.iter().filter(|b| !b.is_ascii_whitespace()));
+        let complete = self.pending.len() / 4 * 4;
+        if end_of_stream && complete != self.pending.len() {
+            return Error::e_explain(ErrorType::HTTPStatus(400), "truncated gRPC-Web text body");
+        }
+
+        // Each chunk is encoded on its own, padding may show up in the middle of the body.
+        let mut decoded = Vec::with_capacity(complete / 4 * 3);
+        let mut start = 0;
+        for end in (4..=complete).step_by(4) {
+            if end == complete || self.pending[end - 1] == b'=' {
+                STANDARD
+                    .decode_vec(&self.pending[start..end], &mut decoded)
+                    .or_else(|_| {
+                        Error::e_explain(ErrorType::HTTPStatus(400), "invalid gRPC-Web text body")
+                    })?;
+                start = end;
+            }
+        }
+        self.pending.drain(..complete);
+        Ok(decoded.into())
+    }
+
+    fn web_content_type(&self) -> String {
+        match self.protocol {
+            Some(GrpcProtocol::WebText) => format!("{GRPC_WEB_TEXT}{}", self.suffix),
+            Some(GrpcProtocol::Web) => format!("{GRPC_WEB}{}", self.suffix),
+            _ => format!("{GRPC}{}", self.suffix),
+        }
+    }
+
+    /// Replaces a response that is not gRPC with a trailers-only gRPC error.
+    fn reject(&mut self, resp: &mut ResponseHeader) -> Result<()> {
+        let status = status_for_http(resp.status);
+        let message = resp.status.canonical_reason().unwrap_or("Unknown");
+        resp.set_status(StatusCode::OK)?;
+        resp.insert_header(CONTENT_TYPE, self.web_content_type())?;
+        resp.insert_header(GRPC_STATUS, status.to_string())?;
+        resp.insert_header(GRPC_MESSAGE, message)?;
+        resp.remove_header(&TRANSFER_ENCODING);
+        resp.set_content_length(0)?;
+        self.status = Some(status.to_string());
+        self.rejected = true;
+        Ok(())
+    }
+
+    fn encode_trailers(&self, trailers: &HeaderMap) -> Result<Bytes> {
+        let mut block = BytesMut::new();
+        for (name, value) in trailers {
+            block.put_slice(name.as_ref());
+            block.put_u8(b':');
+            block.put_slice(value.as_bytes());
+            block.put_slice(b"\r\n");
+        }
+        let len = u32::try_from(block.len())
+            .or_else(|_| Error::e_explain(ErrorType::InternalError, "gRPC trailers too large"))?;
+
+        let mut frame = BytesMut::with_capacity(5 + block.len());
+        frame.put_u8(TRAILER_FRAME);
+        frame.put_u32(len);
+        frame.put_slice(&block);
+        Ok(match self.protocol {
+            Some(GrpcProtocol::WebText) => STANDARD.encode(frame).into(),
+            _ => frame.freeze(),
+        })
+    }
+}
+
+#[async_trait]
+impl HttpModule for GrpcModule {
+    async fn request_header_filter(&mut self, req: &mut RequestHeader) -> Result<()> {
+        let Some(content_type) = req
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_ascii_lowercase)
+        else {
+            return Ok(());
+        };
+        // Longest prefix first, the web ones start like the native one.
+        let detected = [
+            (GRPC_WEB_TEXT, GrpcProtocol::WebText),
+            (GRPC_WEB, GrpcProtocol::Web),
+            (GRPC, GrpcProtocol::Native),
+        ]
+        .into_iter()
+        .find_map(|(prefix, protocol)| {
+            let suffix = content_type.strip_prefix(prefix)?;
+            (suffix.is_empty() || suffix.starts_with(['+', ';'])).then_some((protocol, suffix))
+        });
+
+        if let Some((protocol, suffix)) = detected {
+            self.protocol = Some(protocol);
+            self.suffix = suffix.to_string();
+            self.downstream_h1 = req.version <= Version::HTTP_11;
+        }
+        Ok(())
+    }
+
+    async fn request_body_filter(
+        &mut self,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+    ) -> Result<()> {
+        if !self.translate || self.protocol != Some(GrpcProtocol::WebText) {
+            return Ok(());
+        }
+        let chunk = body.take().unwrap_or_default();
+        *body = Some(self.decode_text(&chunk, end_of_stream)?);
+        Ok(())
+    }
+
+    async fn response_header_filter(
+        &mut self,
+        resp: &mut ResponseHeader,
+        _end_of_stream: bool,
+    ) -> Result<()> {
+        if self.protocol.is_none() || resp.status.is_informational() {
+            return Ok(());
+        }
+        let is_grpc = resp
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.to_ascii_lowercase().starts_with(GRPC));
+        if !is_grpc {
+            return self.reject(resp);
+        }
+
+        if let Some(status) = resp.headers.get(GRPC_STATUS) {
+            self.status = status.to_str().ok().map(str::to_string);
+        }
+        if self.translate {
+            resp.insert_header(CONTENT_TYPE, self.web_content_type())?;
+            // Trailers are sent in the body, whose length is not known upfront.
+            resp.remove_header(&CONTENT_LENGTH);
+            if self.downstream_h1 {
+                resp.insert_header(TRANSFER_ENCODING, "chunked")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn response_body_filter(
+        &mut self,
+        body: &mut Option<Bytes>,
+        _end_of_stream: bool,
+    ) -> Result<()> {
+        if self.rejected {
+            *body = None;
+        } else if self.translate && self.protocol == Some(GrpcProtocol::WebText) {
+            if let Some(chunk) = body.as_mut().filter(|chunk| !chunk.is_empty()) {
+                *chunk = STANDARD.encode(&chunk).into();
+            }
+        }
+        Ok(())
+    }
+
+    fn response_trailer_filter(
+        &mut self,
+        trailers: &mut Option<Box<HeaderMap>>,
+    ) -> Result<Option<Bytes>> {
+        let Some(trailers) = trailers.as_ref() else {
+            return Ok(None);
+        };
+        if let Some(status) = trailers.get(GRPC_STATUS) {
+            self.status = status.to_str().ok().map(str::to_string);
+        }
+        if !self.translate || self.rejected {
+            return Ok(None);
+        }
+        self.encode_trailers(trailers).map(Some)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+pub struct GrpcModuleBuilder;
+
+impl HttpModuleBuilder for GrpcModuleBuilder {
+    fn init(&self) -> Module {
+        Box::<GrpcModule>::default()
+    }
+}
+
+/// Service and method of a call to one of the services `destination` routes.
+pub(crate) fn routed_call<'a>(
+    destination: &Destination,
+    req: &'a RequestHeader,
+) -> Option<(&'a str, &'a str)> {
+    let services = &destination.grpc.as_ref()?.services;
+    grpc_call(req).filter(|(service, _)| services.iter().any(|known| known == service))
+}
+
+/// gRPC status for an HTTP error, as mapped by the gRPC HTTP/2 protocol spec.
+fn status_for_http(status: StatusCode) -> u8 {
+    match status.as_u16() {
+        400 => 13,
+        401 => 16,
+        403 => 7,
+        404 => 12,
+        413 | 431 => 8,
+        429 | 502..=504 => 14,
+        _ => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::Method;
+
+    async fn module(content_type: &str) -> GrpcModule {
+        let mut module = GrpcModule::default();
+        let mut req = RequestHeader::build(Method::POST, b"/pkg.Svc/Call", None).unwrap();
+        req.insert_header(CONTENT_TYPE, content_type).unwrap();
+        module.request_header_filter(&mut req).await.unwrap();
+        module
+    }
+
+    fn grpc_response() -> ResponseHeader {
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        resp.insert_header(CONTENT_TYPE, "application/grpc+proto")
+            .unwrap();
+        resp.insert_header(CONTENT_LENGTH, "12").unwrap();
+        resp
+    }
+
+    #[tokio::test]
+    async fn detects_protocol_and_suffix() {
+        let text = module("application/grpc-web-text+proto").await;
+        assert_eq!(text.protocol(), Some(GrpcProtocol::WebText));
+        assert_eq!(text.suffix, "+proto");
+        assert_eq!(
+            module("application/grpc-web").await.protocol(),
+            Some(GrpcProtocol::Web)
+        );
+        assert_eq!(
+            module("Application/gRPC").await.protocol(),
+            Some(GrpcProtocol::Native)
+        );
+        assert_eq!(module("application/grpcx").await.protocol(), None);
+        assert_eq!(module("application/json").await.protocol(), None);
+    }
+
+    #[tokio::test]
+    async fn translates_binary_grpc_web() {
+        let mut module = module("application/grpc-web+proto").await;
+        module.translate();
+
+        let mut upstream = RequestHeader::build(Method::POST, b"/pkg.Svc/Call", None).unwrap();
+        upstream.set_version(Version::HTTP_2);
+        module.upstream_request(&mut upstream).unwrap();
+        assert_eq!(upstream.headers[CONTENT_TYPE], "application/grpc+proto");
+        assert_eq!(upstream.headers["te"], "trailers");
+        assert_eq!(upstream.send_end_stream(), Some(false));
+
+        let mut resp = grpc_response();
+        module
+            .response_header_filter(&mut resp, false)
+            .await
+            .unwrap();
+        assert_eq!(resp.headers[CONTENT_TYPE], "application/grpc-web+proto");
+        assert!(resp.headers.get(CONTENT_LENGTH).is_none());
+
+        let mut trailers = HeaderMap::new();
+        trailers.insert(GRPC_STATUS, "0".parse().unwrap());
+        let frame = module
+            .response_trailer_filter(&mut Some(Box::new(trailers)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(&frame[..], b"\x80\x00\x00\x00\x0fgrpc-status:0\r\n");
+        assert_eq!(module.status(), Some("0"));
+    }
+
+    #[tokio::test]
+    async fn decodes_and_encodes_grpc_web_text() {
+        let mut module = module("application/grpc-web-text").await;
+        module.translate();
+
+        // "hello" then "!" encoded separately, split across chunks.
+        let mut body = Some(Bytes::from_static(b"aGVsb"));
+        module.request_body_filter(&mut body, false).await.unwrap();
+        assert_eq!(body.as_deref(), Some(&b"hel"[..]));
+        let mut body = Some(Bytes::from_static(b"G8=IQ=="));
+        module.request_body_filter(&mut body, true).await.unwrap();
+        assert_eq!(body.as_deref(), Some(&b"lo!"[..]));
+
+        let mut body = Some(Bytes::from_static(b"\x00\x00\x00\x00\x01a"));
+        module.response_body_filter(&mut body, false).unwrap();
+        assert_eq!(body.as_deref(), Some(&b"AAAAAAFh"[..]));
+
+        let mut body = Some(Bytes::from_static(b"QQ"));
+        let truncated = module.request_body_filter(&mut body, true).await;
+        assert!(truncated.is_err());
+    }
+
+    #[tokio::test]
+    async fn turns_gateway_errors_into_grpc_errors() {
+        let mut module = module("application/grpc-web-text").await;
+        let mut resp = ResponseHeader::build(403, None).unwrap();
+        resp.insert_header(CONTENT_TYPE, "text/plain").unwrap();
+        resp.set_content_length(9).unwrap();
+
+        module
+            .response_header_filter(&mut resp, false)
+            .await
+            .unwrap();
+        assert_eq!(resp.status, StatusCode::OK);
+        assert_eq!(resp.headers[CONTENT_TYPE], "application/grpc-web-text");
+        assert_eq!(resp.headers[GRPC_STATUS], "7");
+        assert_eq!(resp.headers[GRPC_MESSAGE], "Forbidden");
+        assert_eq!(resp.headers[CONTENT_LENGTH], "0");
+        assert_eq!(module.status(), Some("7"));
+
+        let mut body = Some(Bytes::from_static(b"forbidden"));
+        module.response_body_filter(&mut body, true).unwrap();
+        assert!(body.is_none());
+
+        let mut plain = GrpcModule::default();
+        let mut resp = ResponseHeader::build(404, None).unwrap();
+        plain
+            .response_header_filter(&mut resp, false)
+            .await
+            .unwrap();
+        assert_eq!(resp.status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,372 @@
+use crate::utils::requests::{destination_host_header, parse_origin, upstream_host_header};
+use cardinal_base::context::{CardinalContext, ContextHandle};
+use cardinal_base::destinations::balancer::UpstreamEndpoint;
+use cardinal_base::destinations::container::{DestinationContainer, DestinationWrapper};
+use cardinal_base::destinations::upstream_tls::UpstreamTls;
+use cardinal_config::HealthCheck;
+use pingora::connectors::http::Connector;
+use pingora::http::RequestHeader;
+use pingora::prelude::HttpPeer;
+use pingora::server::ShutdownWatch;
+use pingora::services::background::BackgroundService;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Background service that actively probes every upstream endpoint of destinations
+/// configured with a `health_check`, flipping their health flag once enough consecutive
+/// probes disagree with it.
+/// Probes are restarted against the new destinations whenever the context is reloaded.
+pub struct HealthCheckService {
+    context: ContextHandle,
+    connector: Arc<Connector>,
+}
+
+impl HealthCheckService {
+    pub fn new(context: Arc<CardinalContext>) -> Self {
+        Self::from_handle(ContextHandle::new(context))
+    }
+
+    pub fn from_handle(context: ContextHandle) -> Self {
+        Self {
+            context,
+            connector: Arc::new(Connector::new(None)),
+        }
+    }
+
+    async fn spawn_probes(
+        &self,
+        context: &CardinalContext,
+        shutdown: &ShutdownWatch,
+    ) -> Vec<JoinHandle<()>> {
+        let container = match context.get::<DestinationContainer>().await {
+            Ok(container) => container,
+            Err(err) => {
+                warn!(%err, "Health checks disabled, destination container unavailable");
+                return Vec::new();
+            }
+        };
+
+        let mut probes = Vec::new();
+        for wrapper in container.destinations() {
+            let Some(check) = wrapper.destination.health_check.clone() else {
+                continue;
+            };
+
+            for endpoint in wrapper.upstreams.endpoints() {
+                probes.push(tokio::spawn(run_probe_loop(
+                    self.connector.clone(),
+                    wrapper.clone(),
+                    endpoint.clone(),
+                    check.clone(),
+                    shutdown.clone(),
+                )));
+            }
+        }
+
+        probes
+    }
+}
+
+#[async_trait::async_trait]
+impl BackgroundService for HealthCheckService {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        let mut reloads = self.context.subscribe();
+
+        loop {
+            let context = reloads.borrow_and_update().clone();
+            let probes = self.spawn_probes(&context, &shutdown).await;
+
+            let reloaded = tokio::select! {
+                changed = reloads.changed() => changed.is_ok(),
+                _ = shutdown.changed() => false,
+            };
+
+            for probe in probes {
+                probe.abort();
+            }
+            if !reloaded {
+                break;
+            }
+        }
+    }
+}
+
+async fn run_probe_loop(
+    connector: Arc<Connector>,
+    wrapper: Arc<DestinationWrapper>,
+    endpoint: Arc<UpstreamEndpoint>,
+    check: HealthCheck,
+    mut shutdown: ShutdownWatch,
+) {
+    let interval = Duration::from_millis(check.interval_ms.max(1));
+    let destination = &wrapper.destination.name;
+    // Same `Host` as proxied requests, the origin's one when the client's is preserved.
+    let host = destination_host_header(&wrapper.destination, &endpoint.url)
+        .ok()
+        .flatten();
+    let mut streak = 0;
+
+    loop {
+        let result = check_endpoint(
+            &connector,
+            &endpoint.url,
+            &check,
+            host.as_deref(),
+            wrapper.destination.sni.as_deref(),
+            wrapper.upstream_tls.as_deref(),
+        )
+        .await;
+
+        if record_probe(&endpoint, &check, result.is_ok(), &mut streak) {
+            match result {
+                Ok(()) => {
+                    info!(backend_id = %destination, upstream = %endpoint.url, "Upstream became healthy")
+                }
+                Err(reason) => {
+                    warn!(backend_id = %destination, upstream = %endpoint.url, %reason, "Upstream became unhealthy")
+                }
+            }
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown.changed() => break,
+        }
+    }
+}
+
+/// Counts probe results disagreeing with the endpoint state in `streak` and flips the state
+/// once the threshold of the new state is reached. Returns `true` when it flipped.
+fn record_probe(
+    endpoint: &UpstreamEndpoint,
+    check: &HealthCheck,
+    healthy: bool,
+    streak: &mut u32,
+) -> bool {
+    if healthy == endpoint.is_healthy() {
+        *streak = 0;
+        return false;
+    }
+
+    *streak += 1;
+    let threshold = if healthy {
+        check.healthy_threshold
+    } else {
+        check.unhealthy_threshold
+    };
+    if *streak < threshold {
+        return false;
+    }
+    *streak = 0;
+    endpoint.set_healthy(healthy)
+}
+
+/// Sends a single `GET` probe to `origin` and checks the response status. `host` overrides
+/// the origin as `Host`. HTTPS probes use the destination `sni` and upstream TLS settings,
+/// like proxied requests.
+pub async fn check_endpoint(
+    connector: &Connector,
+    origin: &str,
+    check: &HealthCheck,
+    host: Option<&str>,
+    sni: Option<&str>,
+    tls: Option<&UpstreamTls>,
+) -> Result<(), String> {
+    let timeout = Duration::from_millis(check.timeout_ms);
+    let probe = probe(connector, origin, check, host, sni, tls, timeout);
+    match tokio::time::timeout(timeout, probe).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {}ms", check.timeout_ms)),
+    }
+}
+
+async fn probe(
+    connector: &Connector,
+    origin: &str,
+    check: &HealthCheck,
+    host_header: Option<&str>,
+    sni: Option<&str>,
+    tls: Option<&UpstreamTls>,
+    timeout: Duration,
+) -> Result<(), String> {
+    let (host, port, is_tls) = parse_origin(origin).map_err(|e| e.to_string())?;
+    let host_header = match host_header {
+        Some(host_header) => host_header.to_string(),
+        None => upstream_host_header(origin).map_err(|e| e.to_string())?,
+    };
+
+    let sni = sni.map(str::to_string).unwrap_or_else(|| host.clone());
+    let mut peer = HttpPeer::new(format!("{host}:{port}"), is_tls, sni);
+    if let (true, Some(tls)) = (is_tls, tls) {
+        tls.apply(&mut peer);
+    }
+    peer.options.total_connection_timeout = Some(timeout);
+    peer.options.read_timeout = Some(timeout);
+
+    let mut req =
+        RequestHeader::build("GET", check.path.as_bytes(), None).map_err(|e| e.to_string())?;
+    req.insert_header("Host", host_header)
+        .map_err(|e| e.to_string())?;
+
+    let (mut session, _reused) = connector
+        .get_http_session(&peer)
+        .await
+        .map_err(|e| e.to_string())?;
+    session
+        .write_request_header(Box::new(req))
+        .await
+        .map_err(|e| e.to_string())?;
+    session
+        .finish_request_body()
+        .await
+        .map_err(|e| e.to_string())?;
+    session.set_read_timeout(Some(timeout));
+    session
+        .read_response_header()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let status = session
+        .response_header()
+        .map(|resp| resp.status.as_u16())
+        .ok_or_else(|| "missing response header".to_string())?;
+
+    while session
+        .read_response_body()
+        .await
+        .map_err(|e| e.to_string())?
+        .is_some()
+    {}
+    session.shutdown().await;
+
+    if status == check.expect_status {
+        Ok(())
+    } else {
+        Err(format!(
+            "expected status {}, got {status}",
+            check.expect_status
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    async fn spawn_status_server(status_line: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let response = format!("HTTP/1.1 {status_line}\r\ncontent-length: 0\r\n\r\n");
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        addr
+    }
+
+    fn check(expect_status: u16) -> HealthCheck {
+        HealthCheck {
+            path: "/health".into(),
+            interval_ms: 100,
+            timeout_ms: 500,
+            expect_status,
+            healthy_threshold: 2,
+            unhealthy_threshold: 3,
+        }
+    }
+
+    #[tokio::test]
+    async fn check_endpoint_accepts_expected_status() {
+        let addr = spawn_status_server("200 OK").await;
+        let connector = Connector::new(None);
+
+        assert!(
+            check_endpoint(&connector, &addr, &check(200), None, None, None)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn check_endpoint_rejects_unexpected_status() {
+        let addr = spawn_status_server("503 Service Unavailable").await;
+        let connector = Connector::new(None);
+
+        let err = check_endpoint(&connector, &addr, &check(200), None, None, None)
+            .await
+            .unwrap_err();
+        assert!(err.contains("503"));
+    }
+
+    #[tokio::test]
+    async fn check_endpoint_fails_when_unreachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        let connector = Connector::new(None);
+
+        assert!(
+            check_endpoint(&connector, &addr, &check(200), None, None, None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn state_flips_after_consecutive_probes() {
+        let endpoint = UpstreamEndpoint::new("127.0.0.1:1", 1);
+        let check = check(200);
+        let mut streak = 0;
+
+        assert!(!record_probe(&endpoint, &check, false, &mut streak));
+        assert!(!record_probe(&endpoint, &check, false, &mut streak));
+        assert!(!record_probe(&endpoint, &check, true, &mut streak));
+        assert!(endpoint.is_healthy());
+        for _ in 0..2 {
+            assert!(!record_probe(&endpoint, &check, false, &mut streak));
+        }
+        assert!(record_probe(&endpoint, &check, false, &mut streak));
+        assert!(!endpoint.is_healthy());
+
+        assert!(!record_probe(&endpoint, &check, true, &mut streak));
+        assert!(record_probe(&endpoint, &check, true, &mut streak));
+        assert!(endpoint.is_healthy());
+    }
+
+    #[tokio::test]
+    async fn check_endpoint_sends_host_override() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (seen_tx, seen_rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let read = stream.read(&mut buf).await.unwrap();
+            let _ = seen_tx.send(String::from_utf8_lossy(&buf[..read]).to_string());
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await;
+        });
+        let connector = Connector::new(None);
+
+        check_endpoint(
+            &connector,
+            &addr,
+            &check(200),
+            Some("posts.internal"),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let request = seen_rx.await.unwrap().to_ascii_lowercase();
+        assert!(request.contains("host: posts.internal\r\n"), "{request}");
+    }
+}
@@ -1,28 +1,73 @@
+pub mod access_log;
+pub mod cache;
+pub mod compression;
 pub mod context_provider;
+pub mod drain;
+pub mod forwarding;
+pub mod grpc;
+pub mod health;
+pub mod limits;
+pub mod metrics;
+pub mod mirror;
+pub mod proxy_protocol;
 pub mod req;
+pub mod request_id;
 pub mod retry;
+pub mod telemetry;
+pub mod tls;
+pub mod upgrade;
 mod utils;
 
+use crate::access_log::{AccessLogEntry, AccessLogger};
+use crate::cache::ResponseCache;
 use crate::context_provider::CardinalContextProvider;
+use crate::drain::DrainState;
+use crate::forwarding::{DownstreamConnection, ForwardingHeaders};
+use crate::grpc::{GrpcModule, GrpcModuleBuilder};
+use crate::limits::BodyLimits;
+use crate::metrics::{
+    record_grpc, record_request, record_retry, record_upstream_connected, record_upstream_response,
+};
+use crate::mirror::MirroredRequest;
+use crate::proxy_protocol::{resolve_addresses, ProxiedConnections};
 use crate::req::ReqCtx;
-use crate::retry::RetryState;
+use crate::request_id::{RequestIdModule, RequestIdModuleBuilder};
+use crate::retry::{RetryPolicy, RetryReason, RetryState};
+use crate::telemetry::{
+    finish_request, finish_upstream, inject_upstream, start_request, start_upstream,
+};
+use crate::upgrade::UpgradeSession;
+use crate::utils::circuit_breaker::{admit_backend, record_upstream_error, record_upstream_status};
 use crate::utils::requests::{
-    compose_upstream_url, execution_context_from_request, parse_origin, rewrite_request_path,
-    set_upstream_host_headers,
+    compose_upstream_url, destination_host_header, execution_context_from_request, parse_origin,
+    rewrite_request_path, set_upstream_host_headers,
 };
 use bytes::Bytes;
-use cardinal_base::context::CardinalContext;
+use cardinal_base::context::{CardinalContext, ContextHandle};
 use cardinal_base::destinations::container::DestinationContainer;
+use cardinal_config::{ServerListener, ServerRequestId};
 use cardinal_plugins::plugin_executor::CardinalPluginExecutor;
 use cardinal_plugins::request_context::RequestContext;
 use cardinal_plugins::runner::MiddlewareResult;
+use cardinal_plugins::utils::respond_error;
+use cardinal_plugins::REQUEST_ID;
+use opentelemetry::trace::Span as _;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::trace::SdkTracer;
+use pingora::cache::key::HashBinary;
+use pingora::cache::{CacheKey, CacheMeta, NoCacheReason, RespCacheable};
+use pingora::connectors::http::Connector;
+use pingora::http::RequestHeader;
 use pingora::http::ResponseHeader;
+use pingora::modules::http::compression::ResponseCompressionBuilder;
+use pingora::modules::http::HttpModules;
 use pingora::prelude::*;
 use pingora::protocols::Digest;
+use pingora::proxy::FailToProxy;
 use pingora::upstreams::peer::Peer;
 use std::sync::Arc;
-use std::time::Duration;
-use tracing::{debug, error, info, warn};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 pub mod pingora {
     pub use pingora::*;
@@ -40,18 +85,23 @@ pub enum HealthCheckStatus {
 
 #[derive(Clone)]
 pub struct StaticContextProvider {
-    context: Arc<CardinalContext>,
+    context: ContextHandle,
 }
 
 impl StaticContextProvider {
     pub fn new(context: Arc<CardinalContext>) -> Self {
-        Self { context }
+        Self::from_handle(ContextHandle::new(context))
+    }
+
+    /// Resolves whichever context is current in `handle`, following reloads.
+    pub fn from_handle(handle: ContextHandle) -> Self {
+        Self { context: handle }
     }
 }
 
 impl CardinalContextProvider for StaticContextProvider {
     fn resolve(&self, _session: &Session, _ctx: &mut ReqCtx) -> Option<Arc<CardinalContext>> {
-        Some(self.context.clone())
+        Some(self.context.current())
     }
 }
 
@@ -61,6 +111,15 @@ impl CardinalPluginExecutor for StaticContextProvider {}
 pub struct CardinalProxy {
     provider: Arc<dyn CardinalContextProvider>,
     plugin_executor: Arc<dyn CardinalPluginExecutor>,
+    listener: Option<Arc<ServerListener>>,
+    drain: DrainState,
+    tracer: Option<SdkTracer>,
+    access_log: Option<AccessLogger>,
+    request_id: Option<ServerRequestId>,
+    forwarding: Option<ForwardingHeaders>,
+    proxy_protocol: Option<ProxiedConnections>,
+    mirror_connector: Arc<Connector>,
+    cache: Option<&'static ResponseCache>,
 }
 
 impl CardinalProxy {
@@ -75,9 +134,65 @@ impl CardinalProxy {
         Self {
             provider,
             plugin_executor,
+            listener: None,
+            drain: DrainState::default(),
+            tracer: None,
+            access_log: None,
+            request_id: None,
+            forwarding: None,
+            proxy_protocol: None,
+            mirror_connector: Arc::new(Connector::new(None)),
+            cache: None,
         }
     }
 
+    /// Scopes this proxy to an additional listener: its destination allow-list, routing
+    /// mode and global middleware overrides apply to every request it serves.
+    pub fn with_listener(mut self, listener: ServerListener) -> Self {
+        self.listener = Some(Arc::new(listener));
+        self
+    }
+
+    pub fn with_drain(mut self, drain: DrainState) -> Self {
+        self.drain = drain;
+        self
+    }
+
+    pub fn with_access_log(mut self, access_log: AccessLogger) -> Self {
+        self.access_log = Some(access_log);
+        self
+    }
+
+    /// Accepts or generates a request ID per request, forwarded upstream and echoed back.
+    pub fn with_request_id(mut self, request_id: ServerRequestId) -> Self {
+        self.request_id = Some(request_id);
+        self
+    }
+
+    /// Adds client and scheme forwarding headers to upstream requests.
+    pub fn with_forwarding(mut self, forwarding: ForwardingHeaders) -> Self {
+        self.forwarding = Some(forwarding);
+        self
+    }
+
+    /// Takes client addresses from the PROXY headers read by a [`proxy_protocol::ProxyProtocolRelay`].
+    pub fn with_proxy_protocol(mut self, connections: ProxiedConnections) -> Self {
+        self.proxy_protocol = Some(connections);
+        self
+    }
+
+    /// Serves cacheable responses of destinations with a `cache` from `cache`.
+    pub fn with_cache(mut self, cache: &'static ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Records a span per request, continuing incoming W3C trace context.
+    pub fn with_tracer(mut self, tracer: SdkTracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
     pub fn builder(context: Arc<CardinalContext>) -> CardinalProxyBuilder {
         CardinalProxyBuilder::new(context)
     }
@@ -121,60 +236,63 @@ impl CardinalProxyBuilder {
     }
 }
 
-#[async_trait::async_trait]
-impl ProxyHttp for CardinalProxy {
-    type CTX = ReqCtx;
-
-    fn new_ctx(&self) -> Self::CTX {
-        self.provider.ctx()
-    }
+impl CardinalProxy {
+    /// Registers a failed attempt and decides whether the destination retry policy allows
+    /// another one. The current endpoint is excluded from the next peer selection.
+    fn should_retry(&self, session: &Session, ctx: &mut ReqCtx, reason: RetryReason) -> bool {
+        let Some(retry_config) = ctx.req_unsafe().backend.destination.retry.clone() else {
+            return false;
+        };
 
-    async fn early_request_filter(&self, _session: &mut Session, _ctx: &mut Self::CTX) -> Result<()>
-    where
-        Self::CTX: Send + Sync,
-    {
-        self.provider.early_request_filter(_session, _ctx).await
-    }
+        let policy = RetryPolicy::from(&retry_config);
+        if !policy.allows(reason, &session.req_header().method) {
+            return false;
+        }
 
-    async fn logging(&self, _session: &mut Session, _e: Option<&Error>, ctx: &mut Self::CTX)
-    where
-        Self::CTX: Send + Sync,
-    {
-        self.provider.logging(_session, _e, ctx);
-    }
+        let mut retry_state = ctx
+            .retry_state
+            .take()
+            .unwrap_or_else(|| RetryState::from(retry_config));
+        retry_state.register_attempt();
+        if !retry_state.can_retry()
+            || !policy.within_budget(ctx.ctx_base.req_instant, retry_state.next_delay)
+        {
+            return false;
+        }
 
-    async fn request_body_filter(
-        &self,
-        _session: &mut Session,
-        _body: &mut Option<Bytes>,
-        _end_of_stream: bool,
-        _ctx: &mut Self::CTX,
-    ) -> Result<()>
-    where
-        Self::CTX: Send + Sync,
-    {
-        self.provider
-            .request_body_filter(_session, _body, _end_of_stream, _ctx)
-            .await
+        ctx.retry_state = Some(retry_state);
+        if let Some(lease) = ctx.upstream.take() {
+            ctx.failed_upstreams.push(lease.endpoint().clone());
+        }
+        warn!(backend_id = %ctx.req_unsafe().backend.destination.name, ?reason, "Retrying upstream request");
+        record_retry(ctx, reason);
+        ctx.retries += 1;
+        ctx.body_limits.reset_request();
+        true
     }
 
-    fn response_body_filter(
+    /// Resolves the destination and upstream of a request and runs its request middleware.
+    async fn route_request(
         &self,
-        _session: &mut Session,
-        _body: &mut Option<Bytes>,
-        _end_of_stream: bool,
-        _ctx: &mut Self::CTX,
-    ) -> Result<Option<Duration>>
-    where
-        Self::CTX: Send + Sync,
-    {
-        self.provider
-            .response_body_filter(_session, _body, _end_of_stream, _ctx)
-    }
-
-    async fn request_filter(&self, session: &mut Session, ctx: &mut Self::CTX) -> Result<bool> {
+        session: &mut Session,
+        ctx: &mut ReqCtx,
+        request_id: Option<String>,
+    ) -> Result<bool> {
         let path = session.req_header().uri.path().to_string();
         info!(%path, "Request received");
+        ctx.request_uri = Some(session.req_header().uri.to_string());
+
+        if let Some(tracer) = &self.tracer {
+            let trace = start_request(tracer, session, ctx.client_ip());
+            if let Some(id) = &request_id {
+                trace.set_attribute(KeyValue::new("cardinal.request_id", id.clone()));
+            }
+            ctx.trace = Some(trace);
+        }
+
+        if self.drain.is_draining() {
+            session.set_keepalive(None);
+        }
 
         match self.provider.health_check(session) {
             HealthCheckStatus::None => {}
@@ -205,48 +323,189 @@ impl ProxyHttp for CardinalProxy {
                 } else {
                     warn!(%path, status = status_code, "Health check failed");
                 }
-                let _ = session.respond_error(status_code).await;
+                let _ = respond_error(session, status_code).await;
                 return Ok(true);
             }
         }
 
-        let context = match self.provider.resolve(session, ctx) {
+        let mut resolve_span = ctx
+            .trace
+            .as_ref()
+            .map(|trace| trace.child("resolve_context", vec![]));
+        let context = self.provider.resolve(session, ctx);
+        if let Some(span) = resolve_span.as_mut() {
+            span.end();
+        }
+        let context = match context {
             Some(ctx) => ctx,
             None => {
                 warn!(%path, "No context found for request host");
-                let _ = session.respond_error(421).await;
+                let _ = respond_error(session, 421).await;
                 return Ok(true);
             }
         };
+        let server_limits = context.config.server.limits.as_ref();
+        if server_limits
+            .is_some_and(|limits| !limits::headers_allowed(limits, session.req_header()))
+        {
+            warn!(%path, "Request headers exceed the configured limits, returning 431");
+            let _ = respond_error(session, 431).await;
+            return Ok(true);
+        }
+
+        let mut match_span = ctx
+            .trace
+            .as_ref()
+            .map(|trace| trace.child("match_destination", vec![]));
 
         let destination_container = context
             .get::<DestinationContainer>()
             .await
             .map_err(|_| Error::new_str("Destination Container is not present"))?;
 
-        let force_path = context.config.server.force_path_parameter;
-        let backend =
-            match destination_container.get_backend_for_request(session.req_header(), force_path) {
-                Some(b) => b,
-                None => {
-                    warn!(%path, "No matching backend, returning 404");
-                    let _ = session.respond_error(404).await;
-                    return Ok(true);
-                }
-            };
+        // Prefer the listener as defined by the resolved context so reloads apply to it.
+        let listener = self.listener.as_deref().map(|listener| {
+            context
+                .config
+                .server
+                .listeners
+                .iter()
+                .find(|current| current.name == listener.name)
+                .unwrap_or(listener)
+        });
+        let force_path = listener
+            .and_then(|listener| listener.force_path_parameter)
+            .unwrap_or(context.config.server.force_path_parameter);
+        let matched = destination_container.get_backend_for_request(
+            session.req_header(),
+            force_path,
+            ctx.client_ip(),
+        );
+        if let Some(span) = match_span.as_mut() {
+            if let Some(backend) = &matched {
+                span.set_attribute(KeyValue::new(
+                    "cardinal.destination",
+                    backend.destination.name.clone(),
+                ));
+            }
+            span.end();
+        }
+        let backend = match matched {
+            Some(b) => b,
+            None => {
+                warn!(%path, "No matching backend, returning 404");
+                let _ = respond_error(session, 404).await;
+                return Ok(true);
+            }
+        };
 
-        let destination_name = backend.destination.name.clone();
-        let _ = set_upstream_host_headers(session, &backend);
-        info!(backend_id = %destination_name, "Routing to backend");
+        let routed_name = backend.destination.name.clone();
+        if let Some(listener) = listener {
+            if !listener.allows_destination(&routed_name) {
+                warn!(%path, backend_id = %routed_name, listener = %listener.name, "Destination not exposed on listener, returning 404");
+                let _ = respond_error(session, 404).await;
+                return Ok(true);
+            }
+        }
+        let backend = match admit_backend(&destination_container, backend, ctx) {
+            Some(b) => b,
+            None => {
+                warn!(backend_id = %routed_name, "Circuit breaker open, returning 503");
+                let _ = respond_error(session, 503).await;
+                return Ok(true);
+            }
+        };
 
-        rewrite_request_path(session.req_header_mut(), &destination_name, force_path);
+        let destination_name = backend.destination.name.clone();
+        let client_ip = ctx.client_ip();
+        ctx.upstream_hash = backend
+            .upstreams
+            .request_hash(session.req_header(), client_ip);
+        let endpoint = match backend.upstreams.select(ctx.upstream_hash) {
+            Some(endpoint) => endpoint,
+            None => {
+                warn!(backend_id = %destination_name, "No healthy upstream available");
+                let _ = respond_error(session, 503).await;
+                return Ok(true);
+            }
+        };
+        if let (Some(forwarding), Some(peer)) = (&self.forwarding, client_ip) {
+            let conn = DownstreamConnection {
+                peer,
+                tls: session
+                    .digest()
+                    .is_some_and(|digest| digest.ssl_digest.is_some()),
+                port: ctx.server_addr.map(|addr| addr.port()),
+            };
+            forwarding.apply(session.req_header_mut(), &conn);
+        }
+        // Once switched, the connection carries no HTTP bodies to cache, limit, mirror or compress.
+        // gRPC destinations are reached over HTTP/2, which has no upgrades.
+        let grpc = backend.destination.grpc.is_some();
+        ctx.upgrade =
+            UpgradeSession::requested(session.req_header(), backend.destination.websocket.as_ref())
+                .filter(|_| !grpc);
+        let upgrading = ctx.upgrade.is_some();
+        if let Some(idle) = ctx.upgrade.as_ref().and_then(UpgradeSession::idle_timeout) {
+            session.set_read_timeout(Some(idle));
+        }
+        // Keyed on the path the client requested, purges name the same paths.
+        ctx.cache_key = match (&self.cache, &backend.destination.cache) {
+            (Some(_), Some(cache))
+                if !upgrading && cache::request_cacheable(session.req_header()) =>
+            {
+                Some(cache::cache_key(
+                    &destination_name,
+                    &cache.key,
+                    session.req_header(),
+                ))
+            }
+            _ => None,
+        };
+        // Calls routed by service keep their `/package.Service/Method` path.
+        if grpc::routed_call(&backend.destination, session.req_header()).is_none() {
+            rewrite_request_path(session.req_header_mut(), &routed_name, force_path);
+        }
+        if let Some(module) = session
+            .downstream_modules_ctx
+            .get_mut::<GrpcModule>()
+            .filter(|_| grpc)
+        {
+            module.translate();
+        }
+        if !upgrading {
+            ctx.body_limits = BodyLimits::resolve(server_limits, &backend, session.req_header());
+        }
+        if !ctx.body_limits.request_length_allowed(session.req_header()) {
+            warn!(backend_id = %destination_name, "Request body exceeds the configured limit, returning 413");
+            let _ = respond_error(session, 413).await;
+            return Ok(true);
+        }
+        // Copied before the upstream Host is set, the shadow gets its own.
+        if !upgrading {
+            ctx.mirror =
+                MirroredRequest::sample(&destination_container, &backend, session.req_header());
+        }
+        let _ = set_upstream_host_headers(session, &backend.destination, &endpoint.url);
+        if let (Some(config), false) = (&backend.destination.compression, upgrading) {
+            ctx.compression = compression::negotiate(config, session.req_header());
+            if let Some(algorithm) = ctx.compression {
+                compression::enable(session, config, algorithm);
+            }
+        }
+        info!(backend_id = %destination_name, upstream = %endpoint.url, "Routing to backend");
+        ctx.upstream = Some(endpoint.acquire());
 
         let mut request_state = RequestContext::new(
             context.clone(),
             backend,
-            execution_context_from_request(session),
+            execution_context_from_request(session, request_id),
             self.plugin_executor.clone(),
         );
+        if let Some(listener) = listener {
+            request_state.apply_listener(listener);
+        }
+        request_state.trace = ctx.trace.clone();
 
         let plugin_runner = request_state.plugin_runner.clone();
 
@@ -258,7 +517,7 @@ impl ProxyHttp for CardinalProxy {
             Ok(filter_result) => filter_result,
             Err(err) => {
                 error!(%err, "Error running request filters");
-                let _ = session.respond_error(500).await;
+                let _ = respond_error(session, 500).await;
                 return Ok(true);
             }
         };
@@ -278,37 +537,296 @@ impl ProxyHttp for CardinalProxy {
             MiddlewareResult::Responded => Ok(true),
         }
     }
+}
 
-    fn fail_to_connect(
+#[async_trait::async_trait]
+impl ProxyHttp for CardinalProxy {
+    type CTX = ReqCtx;
+
+    fn new_ctx(&self) -> Self::CTX {
+        self.provider.ctx()
+    }
+
+    fn init_downstream_modules(&self, modules: &mut HttpModules) {
+        // Disabled until a destination with `compression` enables it for the request.
+        modules.add_module(ResponseCompressionBuilder::enable(0));
+        modules.add_module(Box::new(GrpcModuleBuilder));
+        if let Some(request_id) = &self.request_id {
+            modules.add_module(Box::new(RequestIdModuleBuilder::new(request_id.clone())));
+        }
+    }
+
+    async fn early_request_filter(&self, _session: &mut Session, _ctx: &mut Self::CTX) -> Result<()>
+    where
+        Self::CTX: Send + Sync,
+    {
+        _ctx.in_flight = Some(self.drain.track());
+        (_ctx.client_addr, _ctx.server_addr) =
+            resolve_addresses(_session, self.proxy_protocol.as_ref())?;
+        self.provider.early_request_filter(_session, _ctx).await
+    }
+
+    async fn logging(&self, _session: &mut Session, _e: Option<&Error>, ctx: &mut Self::CTX)
+    where
+        Self::CTX: Send + Sync,
+    {
+        let _span = ctx.log_span().entered();
+        if let Some(e) = _e {
+            record_upstream_error(ctx, e);
+        }
+        record_request(_session, ctx);
+        record_grpc(_session, ctx);
+        if let Some(access_log) = &self.access_log {
+            access_log.log(&AccessLogEntry::from_request(_session, ctx));
+        }
+        finish_request(_session, ctx, _e.is_some());
+        self.provider.logging(_session, _e, ctx);
+    }
+
+    async fn request_body_filter(
+        &self,
+        _session: &mut Session,
+        _body: &mut Option<Bytes>,
+        _end_of_stream: bool,
+        _ctx: &mut Self::CTX,
+    ) -> Result<()>
+    where
+        Self::CTX: Send + Sync,
+    {
+        let span = _ctx.log_span();
+        if let Some(chunk) = _body.as_ref() {
+            if !_ctx.body_limits.push_request(chunk.len()) {
+                let _span = span.entered();
+                warn!("Request body exceeds the configured limit, aborting");
+                return Error::e_explain(ErrorType::HTTPStatus(413), "request body too large");
+            }
+            if !_ctx
+                .upgrade
+                .as_mut()
+                .is_none_or(|upgrade| upgrade.push_client(chunk))
+            {
+                let _span = span.entered();
+                warn!("WebSocket frame from the client exceeds the configured limit, closing");
+                return Error::e_explain(ErrorType::Custom("WebSocketLimit"), "frame too large");
+            }
+        }
+        self.provider
+            .request_body_filter(_session, _body, _end_of_stream, _ctx)
+            .instrument(span.clone())
+            .await?;
+
+        if let Some(mirror) = _ctx.mirror.as_mut() {
+            let _span = span.entered();
+            if !_body.as_ref().is_none_or(|chunk| mirror.push_body(chunk)) {
+                debug!("Request body exceeds the mirror limit, not mirroring");
+                _ctx.mirror = None;
+            } else if _end_of_stream {
+                if let Some(mirror) = _ctx.mirror.take() {
+                    mirror.spawn(self.mirror_connector.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn response_body_filter(
         &self,
         _session: &mut Session,
+        _body: &mut Option<Bytes>,
+        _end_of_stream: bool,
+        _ctx: &mut Self::CTX,
+    ) -> Result<Option<Duration>>
+    where
+        Self::CTX: Send + Sync,
+    {
+        let _span = _ctx.log_span().entered();
+        if let Some(chunk) = _body.as_ref() {
+            if !_ctx.body_limits.push_response(chunk.len()) {
+                warn!("Response body exceeds the configured limit, aborting");
+                return Error::e_explain(ErrorType::HTTPStatus(502), "response body too large");
+            }
+            if !_ctx
+                .upgrade
+                .as_mut()
+                .is_none_or(|upgrade| upgrade.push_upstream(chunk))
+            {
+                warn!("WebSocket frame from the upstream exceeds the configured limit, closing");
+                return Error::e_explain(ErrorType::Custom("WebSocketLimit"), "frame too large");
+            }
+        }
+        self.provider
+            .response_body_filter(_session, _body, _end_of_stream, _ctx)
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut Self::CTX) -> Result<bool> {
+        let request_id = session
+            .downstream_modules_ctx
+            .get::<RequestIdModule>()
+            .and_then(|module| module.id())
+            .map(str::to_string);
+        if let Some(id) = &request_id {
+            ctx.set(REQUEST_ID, id);
+            ctx.log_span = Some(info_span!("request", request_id = %id));
+        }
+
+        let span = ctx.log_span();
+        self.route_request(session, ctx, request_id)
+            .instrument(span)
+            .await
+    }
+
+    fn request_cache_filter(&self, session: &mut Session, ctx: &mut Self::CTX) -> Result<()> {
+        let (Some(cache), Some(_)) = (self.cache, &ctx.cache_key) else {
+            return Ok(());
+        };
+        if let Some(config) = &ctx.req_unsafe().backend.destination.cache {
+            cache.enable(session, config);
+        }
+        Ok(())
+    }
+
+    fn cache_key_callback(&self, session: &Session, ctx: &mut Self::CTX) -> Result<CacheKey> {
+        Ok(ctx
+            .cache_key
+            .clone()
+            .unwrap_or_else(|| CacheKey::default(session.req_header())))
+    }
+
+    fn response_cache_filter(
+        &self,
+        session: &Session,
+        resp: &ResponseHeader,
+        ctx: &mut Self::CTX,
+    ) -> Result<RespCacheable> {
+        Ok(match &ctx.req_unsafe().backend.destination.cache {
+            Some(config) => cache::response_cacheable(config, session.req_header(), resp),
+            None => RespCacheable::Uncacheable(NoCacheReason::Custom("cache disabled")),
+        })
+    }
+
+    fn cache_vary_filter(
+        &self,
+        meta: &CacheMeta,
+        _ctx: &mut Self::CTX,
+        req: &RequestHeader,
+    ) -> Option<HashBinary> {
+        cache::variance(meta, req)
+    }
+
+    fn should_serve_stale(
+        &self,
+        _session: &mut Session,
+        _ctx: &mut Self::CTX,
+        error: Option<&Error>,
+    ) -> bool {
+        // Whether the cached response allows it is checked by pingora against its metadata;
+        // `None` is the stale-while-revalidate case.
+        error.is_none_or(|e| e.esource() == &ErrorSource::Upstream)
+    }
+
+    async fn fail_to_proxy(
+        &self,
+        session: &mut Session,
+        e: &Error,
+        ctx: &mut Self::CTX,
+    ) -> FailToProxy
+    where
+        Self::CTX: Send + Sync,
+    {
+        let code = match e.etype() {
+            ErrorType::HTTPStatus(code) => *code,
+            _ => match e.esource() {
+                ErrorSource::Upstream => 502,
+                ErrorSource::Downstream => match e.etype() {
+                    ErrorType::WriteError | ErrorType::ReadError | ErrorType::ConnectionClosed => 0,
+                    _ => 400,
+                },
+                ErrorSource::Internal | ErrorSource::Unset => 500,
+            },
+        };
+        if code > 0 {
+            let span = ctx.log_span();
+            if let Err(err) = respond_error(session, code).instrument(span).await {
+                error!(%err, "Failed to send error response downstream");
+            }
+        }
+
+        FailToProxy {
+            error_code: code,
+            can_reuse_downstream: false,
+        }
+    }
+
+    fn fail_to_connect(
+        &self,
+        session: &mut Session,
         _peer: &HttpPeer,
         ctx: &mut Self::CTX,
         mut e: Box<Error>,
     ) -> Box<Error> {
-        let backend_config = ctx.req_unsafe().backend.destination.retry.clone();
-        if let Some(mut retry_state) = ctx.retry_state.take() {
-            retry_state.register_attempt();
-            if retry_state.can_retry() {
-                e.set_retry(true);
-                ctx.retry_state = Some(retry_state);
-            } else {
-                ctx.retry_state = None;
-            }
-        } else if let Some(retry_config) = backend_config {
-            let mut retry_state = RetryState::from(retry_config);
-            retry_state.register_attempt();
-            if retry_state.can_retry() {
-                e.set_retry(true);
-                ctx.retry_state = Some(retry_state);
-            } else {
-                ctx.retry_state = None;
+        let _span = ctx.log_span().entered();
+        finish_upstream(ctx, None, Some(&e.to_string()));
+        if self.should_retry(session, ctx, RetryReason::ConnectFailure) {
+            e.set_retry(true);
+        }
+
+        e
+    }
+
+    fn error_while_proxy(
+        &self,
+        peer: &HttpPeer,
+        session: &mut Session,
+        e: Box<Error>,
+        ctx: &mut Self::CTX,
+        client_reused: bool,
+    ) -> Box<Error> {
+        let _span = ctx.log_span().entered();
+        let mut e = e.more_context(format!("Peer: {peer}"));
+        if matches!(e.etype(), ErrorType::HTTPStatus(_)) && e.retry() {
+            // Raised by upstream_response_filter, the retry was already accounted for.
+            return e;
+        }
+        finish_upstream(ctx, None, Some(&e.to_string()));
+
+        e.retry
+            .decide_reuse(client_reused && !session.as_ref().retry_buffer_truncated());
+        if !e.retry() && !session.as_ref().retry_buffer_truncated() {
+            if let Some(reason) = RetryReason::from_proxy_error(&e) {
+                if self.should_retry(session, ctx, reason) {
+                    e.set_retry(true);
+                }
             }
         }
 
         e
     }
 
+    fn upstream_response_filter(
+        &self,
+        session: &mut Session,
+        upstream_response: &mut ResponseHeader,
+        ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        let _span = ctx.log_span().entered();
+        let status = upstream_response.status.as_u16();
+        record_upstream_response(ctx, status);
+        ctx.upstream_duration = ctx
+            .upstream_connect_started
+            .map(|started| started.elapsed());
+        finish_upstream(ctx, Some(status), None);
+        if session.as_ref().retry_buffer_truncated()
+            || !self.should_retry(session, ctx, RetryReason::Status(status))
+        {
+            return Ok(());
+        }
+
+        // Nothing has been sent downstream yet, failing here makes pingora pick a new peer.
+        let mut e = Error::explain(ErrorType::HTTPStatus(status), "Retryable upstream status");
+        e.set_retry(true);
+        Err(e)
+    }
+
     async fn upstream_peer(
         &self,
         _session: &mut Session,
@@ -320,10 +838,30 @@ impl ProxyHttp for CardinalProxy {
                 return Err(Error::new_str("Retry attempts exhausted"));
             }
         }
+        let _span = ctx.log_span().entered();
 
+        if ctx.upstream.is_none() {
+            // The previous endpoint failed to connect; pick another one for the retry.
+            let endpoint = ctx
+                .req_unsafe()
+                .backend
+                .upstreams
+                .select_excluding(ctx.upstream_hash, &ctx.failed_upstreams)
+                .ok_or_else(|| Error::new_str("No healthy upstream available"))?;
+            let destination = &ctx.req_unsafe().backend.destination;
+            if let Ok(Some(host_header)) = destination_host_header(destination, &endpoint.url) {
+                let _ = _session.req_header_mut().insert_header("Host", host_header);
+            }
+            ctx.upstream = Some(endpoint.acquire());
+        }
+
+        let endpoint = ctx
+            .upstream_endpoint()
+            .cloned()
+            .ok_or_else(|| Error::new_str("No upstream endpoint selected"))?;
         let backend = &ctx.req_unsafe().backend;
         // Determine origin parts for TLS and SNI
-        let (host, port, is_tls) = parse_origin(&backend.destination.url)
+        let (host, port, is_tls) = parse_origin(&endpoint.url)
             .map_err(|_| Error::new_str("Origin could not be parsed "))?;
         let hostport = format!("{host}:{port}");
 
@@ -336,13 +874,25 @@ impl ProxyHttp for CardinalProxy {
             .unwrap_or("/");
         let upstream_url = compose_upstream_url(is_tls, &host, port, path_and_query);
 
-        info!(%upstream_url, backend_id = %&backend.destination.name, is_tls, sni = %host, "Forwarding to upstream");
+        let sni = backend
+            .destination
+            .sni
+            .clone()
+            .unwrap_or_else(|| host.clone());
+        info!(%upstream_url, backend_id = %&backend.destination.name, is_tls, %sni, "Forwarding to upstream");
+        let method = _session.req_header().method.to_string();
         debug!(upstream_origin = %hostport, "Connecting to upstream origin");
 
-        let mut peer = HttpPeer::new(&hostport, is_tls, host);
+        let mut peer = HttpPeer::new(&hostport, is_tls, sni);
+        let upgrade = ctx.upgrade.as_ref();
         if let Some(opts) = peer.get_mut_peer_options() {
-            // Allow both HTTP/1.1 and HTTP/2 so plain HTTP backends keep working.
-            opts.set_http_version(2, 1);
+            // Allow both HTTP/1.1 and HTTP/2 so plain HTTP backends keep working, upgrades
+            // only exist in HTTP/1.1 and gRPC only in HTTP/2, as h2c on plaintext upstreams.
+            match (upgrade, &backend.destination.grpc) {
+                (Some(_), _) => opts.set_http_version(1, 1),
+                (None, Some(_)) => opts.set_http_version(2, 2),
+                (None, None) => opts.set_http_version(2, 1),
+            }
             if let Some(timeout) = &backend.destination.timeout {
                 opts.idle_timeout = timeout
                     .idle
@@ -361,11 +911,35 @@ impl ProxyHttp for CardinalProxy {
                     .as_ref()
                     .map(|idle| Duration::from_millis(*idle));
             }
+            if let Some(idle) = upgrade.and_then(UpgradeSession::idle_timeout) {
+                opts.read_timeout = Some(idle);
+            }
+        }
+        if let (true, Some(tls)) = (is_tls, &backend.upstream_tls) {
+            tls.apply(&mut peer);
         }
         let peer = Box::new(peer);
+        start_upstream(ctx, &method, &upstream_url);
+        ctx.upstream_connect_started = Some(Instant::now());
         Ok(peer)
     }
 
+    async fn upstream_request_filter(
+        &self,
+        _session: &mut Session,
+        upstream_request: &mut RequestHeader,
+        ctx: &mut Self::CTX,
+    ) -> Result<()>
+    where
+        Self::CTX: Send + Sync,
+    {
+        inject_upstream(ctx, upstream_request);
+        if let Some(module) = _session.downstream_modules_ctx.get::<GrpcModule>() {
+            module.upstream_request(upstream_request)?;
+        }
+        Ok(())
+    }
+
     async fn connected_to_upstream(
         &self,
         _session: &mut Session,
@@ -376,10 +950,12 @@ impl ProxyHttp for CardinalProxy {
         _digest: Option<&Digest>,
         ctx: &mut Self::CTX,
     ) -> Result<()> {
-        ctx.retry_state = None;
+        let _span = ctx.log_span().entered();
         let backend_id = ctx.req_unsafe().backend.destination.name.to_string();
 
         info!(backend_id, reused, peer = %peer, "Connected to upstream");
+        record_upstream_connected(ctx, reused);
+        ctx.upstream_request_started = Some(Instant::now());
         Ok(())
     }
 
@@ -389,12 +965,18 @@ impl ProxyHttp for CardinalProxy {
         upstream_response: &mut ResponseHeader,
         ctx: &mut Self::CTX,
     ) -> Result<()> {
+        if !ctx.body_limits.response_length_allowed(upstream_response) {
+            let _span = ctx.log_span().entered();
+            warn!("Response body exceeds the configured limit, returning 502");
+            return Error::e_explain(ErrorType::HTTPStatus(502), "response body too large");
+        }
         if let Some(resp_headers) = ctx.req_unsafe_mut().response_headers.take() {
             for (key, val) in resp_headers {
                 let _ = upstream_response.insert_header(key, val);
             }
         }
 
+        let span = ctx.log_span();
         {
             // Run response filters first
             {
@@ -412,10 +994,32 @@ impl ProxyHttp for CardinalProxy {
                         },
                         upstream_response,
                     )
+                    .instrument(span.clone())
                     .await;
             }
+            let _span = span.entered();
 
             ctx.set("status", upstream_response.status.as_str());
+            record_upstream_status(ctx, upstream_response.status.as_u16());
+            if ctx.upgrade.is_some() {
+                let destination = ctx.req_unsafe().backend.destination.name.clone();
+                if let Some(upgrade) = ctx.upgrade.as_mut() {
+                    upgrade.response(&destination, upstream_response);
+                }
+            }
+            if ctx.compression.is_some() {
+                let compressible = ctx
+                    .req_unsafe()
+                    .backend
+                    .destination
+                    .compression
+                    .as_ref()
+                    .is_some_and(|config| compression::compressible(config, upstream_response));
+                if !compressible {
+                    compression::disable(session);
+                    ctx.compression = None;
+                }
+            }
 
             // Safe to get another mutable reference now
             let req = ctx.req_unsafe_mut();
@@ -0,0 +1,172 @@
+use cardinal_base::destinations::container::DestinationWrapper;
+use cardinal_config::ServerLimits;
+use http::header::CONTENT_LENGTH;
+use http::HeaderMap;
+use pingora::http::{RequestHeader, ResponseHeader};
+
+/// Body size limits resolved for a request, with the bytes seen so far in each direction.
+#[derive(Debug, Default)]
+pub struct BodyLimits {
+    max_request: Option<usize>,
+    max_response: Option<usize>,
+    request_bytes: usize,
+    response_bytes: usize,
+}
+
+impl BodyLimits {
+    /// Takes each limit from the matched route, then the destination, then the server.
+    pub(crate) fn resolve(
+        server: Option<&ServerLimits>,
+        backend: &DestinationWrapper,
+        req: &RequestHeader,
+    ) -> Self {
+        let destination = backend.destination.limits.as_ref();
+        let route = backend
+            .router
+            .max_request_body_bytes(req.method.as_str(), req.uri.path());
+
+        Self {
+            max_request: route
+                .or_else(|| destination.and_then(|limits| limits.max_request_body_bytes))
+                .or_else(|| server.and_then(|limits| limits.max_request_body_bytes)),
+            max_response: destination
+                .and_then(|limits| limits.max_response_body_bytes)
+                .or_else(|| server.and_then(|limits| limits.max_response_body_bytes)),
+            ..Self::default()
+        }
+    }
+
+    /// Whether the announced request body fits, checked before going upstream.
+    pub(crate) fn request_length_allowed(&self, req: &RequestHeader) -> bool {
+        within(self.max_request, content_length(&req.headers))
+    }
+
+    /// Counts a request body chunk, `false` once the body exceeds the limit.
+    pub(crate) fn push_request(&mut self, len: usize) -> bool {
+        self.request_bytes += len;
+        within(self.max_request, Some(self.request_bytes))
+    }
+
+    /// Forgets the request bytes counted so far. A retry replays the buffered body through
+    /// the body filter, which would otherwise count it twice.
+    pub(crate) fn reset_request(&mut self) {
+        self.request_bytes = 0;
+    }
+
+    pub(crate) fn response_length_allowed(&self, resp: &ResponseHeader) -> bool {
+        within(self.max_response, content_length(&resp.headers))
+    }
+
+    /// Counts a response body chunk, `false` once the body exceeds the limit.
+    pub(crate) fn push_response(&mut self, len: usize) -> bool {
+        self.response_bytes += len;
+        within(self.max_response, Some(self.response_bytes))
+    }
+}
+
+/// Whether the request headers stay within the server's count and size limits.
+pub(crate) fn headers_allowed(limits: &ServerLimits, req: &RequestHeader) -> bool {
+    let count_allowed = limits
+        .max_request_headers
+        .is_none_or(|max| req.headers.len() <= max);
+    let size_allowed = limits.max_request_header_bytes.is_none_or(|max| {
+        let size = req
+            .headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum::<usize>();
+        size <= max
+    });
+    count_allowed && size_allowed
+}
+
+fn within(max: Option<usize>, len: Option<usize>) -> bool {
+    match (max, len) {
+        (Some(max), Some(len)) => len <= max,
+        _ => true,
+    }
+}
+
+fn content_length(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cardinal_config::{Destination, DestinationLimits};
+    use http::Method;
+
+    fn backend(limits: Option<DestinationLimits>) -> DestinationWrapper {
+        let mut destination: Destination = serde_json::from_value(
+            serde_json::json!({ "name": "uploads", "url": "http://10.0.0.1:8080" }),
+        )
+        .unwrap();
+        destination.limits = limits;
+        DestinationWrapper::new(destination, None)
+    }
+
+    fn request(path: &str) -> RequestHeader {
+        RequestHeader::build(Method::POST, path.as_bytes(), None).unwrap()
+    }
+
+    #[test]
+    fn destination_limits_override_server_limits() {
+        let server = ServerLimits {
+            max_request_body_bytes: Some(1000),
+            max_response_body_bytes: Some(2000),
+            ..ServerLimits::default()
+        };
+        let destination = DestinationLimits {
+            max_request_body_bytes: Some(100),
+            max_response_body_bytes: None,
+        };
+
+        let limits = BodyLimits::resolve(Some(&server), &backend(None), &request("/other"));
+        assert_eq!(limits.max_request, Some(1000));
+        assert_eq!(limits.max_response, Some(2000));
+
+        let backend = backend(Some(destination));
+        let limits = BodyLimits::resolve(Some(&server), &backend, &request("/other"));
+        assert_eq!(limits.max_request, Some(100));
+        assert_eq!(limits.max_response, Some(2000));
+    }
+
+    #[test]
+    fn counts_body_bytes_against_the_limit() {
+        let destination = DestinationLimits {
+            max_request_body_bytes: Some(10),
+            max_response_body_bytes: None,
+        };
+        let mut limits = BodyLimits::resolve(None, &backend(Some(destination)), &request("/files"));
+        let mut req = request("/files");
+        req.insert_header("Content-Length", "11").unwrap();
+
+        assert!(!limits.request_length_allowed(&req));
+        assert!(limits.push_request(6));
+        assert!(limits.push_request(4));
+        assert!(!limits.push_request(1));
+        limits.reset_request();
+        assert!(limits.push_request(10));
+        assert!(limits.push_response(usize::MAX / 2));
+    }
+
+    #[test]
+    fn checks_header_count_and_size() {
+        let mut req = request("/");
+        req.insert_header("X-One", "aaaa").unwrap();
+        req.insert_header("X-Two", "bbbb").unwrap();
+        let limits = |max_request_headers, max_request_header_bytes| ServerLimits {
+            max_request_headers,
+            max_request_header_bytes,
+            ..ServerLimits::default()
+        };
+
+        assert!(headers_allowed(&limits(Some(2), Some(18)), &req));
+        assert!(!headers_allowed(&limits(Some(1), None), &req));
+        assert!(!headers_allowed(&limits(None, Some(17)), &req));
+    }
+}
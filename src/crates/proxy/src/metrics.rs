@@ -0,0 +1,227 @@
+use crate::grpc::{routed_call, GrpcModule};
+use crate::req::ReqCtx;
+use crate::retry::RetryReason;
+use http::Method;
+use pingora::proxy::Session;
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge_vec,
+    HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+};
+use std::sync::LazyLock;
+
+const NO_DESTINATION: &str = "none";
+/// Label of gRPC services and methods the destination does not list, of calls that ended
+/// without a `grpc-status`, and of non-standard HTTP methods.
+const UNKNOWN: &str = "other";
+
+pub static REQUESTS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "cardinal_requests_total",
+        "Requests handled by the proxy",
+        &["destination", "method", "status"]
+    )
+    .expect("cardinal_requests_total registers once")
+});
+
+pub static REQUEST_DURATION_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "cardinal_request_duration_seconds",
+        "Time from receiving the request until it was logged",
+        &["destination", "method"]
+    )
+    .expect("cardinal_request_duration_seconds registers once")
+});
+
+pub static UPSTREAM_CONNECT_DURATION_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "cardinal_upstream_connect_duration_seconds",
+        "Time spent establishing new upstream connections",
+        &["destination"]
+    )
+    .expect("cardinal_upstream_connect_duration_seconds registers once")
+});
+
+pub static UPSTREAM_RESPONSE_DURATION_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "cardinal_upstream_response_duration_seconds",
+        "Time from upstream connection until the upstream response header arrived",
+        &["destination", "status"]
+    )
+    .expect("cardinal_upstream_response_duration_seconds registers once")
+});
+
+pub static UPSTREAM_RETRIES_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "cardinal_upstream_retries_total",
+        "Upstream attempts retried by the destination retry policy",
+        &["destination", "reason"]
+    )
+    .expect("cardinal_upstream_retries_total registers once")
+});
+
+pub static GRPC_REQUESTS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "cardinal_grpc_requests_total",
+        "gRPC and gRPC-Web calls by the grpc-status they ended with",
+        &["destination", "service", "method", "grpc_status"]
+    )
+    .expect("cardinal_grpc_requests_total registers once")
+});
+
+pub static UPGRADED_CONNECTIONS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "cardinal_upgraded_connections",
+        "Upgraded connections, such as WebSockets, currently open",
+        &["destination", "protocol"]
+    )
+    .expect("cardinal_upgraded_connections registers once")
+});
+
+pub static UPGRADED_CONNECTIONS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "cardinal_upgraded_connections_total",
+        "Connections upgraded by a 101 Switching Protocols response",
+        &["destination", "protocol"]
+    )
+    .expect("cardinal_upgraded_connections_total registers once")
+});
+
+pub static ACCESS_LOG_DROPPED_TOTAL: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "cardinal_access_log_dropped_total",
+        "Access log lines dropped because the writer fell behind"
+    )
+    .expect("cardinal_access_log_dropped_total registers once")
+});
+
+fn destination(ctx: &ReqCtx) -> &str {
+    ctx.ctx_base
+        .resolved_request
+        .as_ref()
+        .map(|req| req.backend.destination.name.as_str())
+        .unwrap_or(NO_DESTINATION)
+}
+
+pub(crate) fn record_request(session: &Session, ctx: &ReqCtx) {
+    let destination = destination(ctx);
+    let method = method_label(&session.req_header().method);
+    let status = session
+        .response_written()
+        .map(|resp| resp.status.as_u16())
+        .unwrap_or(0)
+        .to_string();
+
+    REQUESTS_TOTAL
+        .with_label_values(&[destination, method, &status])
+        .inc();
+    REQUEST_DURATION_SECONDS
+        .with_label_values(&[destination, method])
+        .observe(ctx.ctx_base.req_instant.elapsed().as_secs_f64());
+}
+
+/// Clients may send any extension method, only the standard ones get a series of their own.
+fn method_label(method: &Method) -> &str {
+    match *method {
+        Method::GET
+        | Method::HEAD
+        | Method::POST
+        | Method::PUT
+        | Method::DELETE
+        | Method::PATCH
+        | Method::OPTIONS
+        | Method::CONNECT
+        | Method::TRACE => method.as_str(),
+        _ => UNKNOWN,
+    }
+}
+
+pub(crate) fn record_grpc(session: &Session, ctx: &ReqCtx) {
+    let Some(module) = session
+        .downstream_modules_ctx
+        .get::<GrpcModule>()
+        .filter(|module| module.protocol().is_some())
+    else {
+        return;
+    };
+    let (service, method) = ctx
+        .ctx_base
+        .resolved_request
+        .as_ref()
+        .and_then(|req| routed_call(&req.backend.destination, session.req_header()))
+        .unwrap_or((UNKNOWN, UNKNOWN));
+
+    GRPC_REQUESTS_TOTAL
+        .with_label_values(&[
+            destination(ctx),
+            service,
+            method,
+            module.status().unwrap_or(UNKNOWN),
+        ])
+        .inc();
+}
+
+pub(crate) fn record_upstream_connected(ctx: &ReqCtx, reused: bool) {
+    if reused {
+        return;
+    }
+
+    if let Some(started) = ctx.upstream_connect_started {
+        UPSTREAM_CONNECT_DURATION_SECONDS
+            .with_label_values(&[destination(ctx)])
+            .observe(started.elapsed().as_secs_f64());
+    }
+}
+
+pub(crate) fn record_upstream_response(ctx: &ReqCtx, status: u16) {
+    if let Some(started) = ctx.upstream_request_started {
+        UPSTREAM_RESPONSE_DURATION_SECONDS
+            .with_label_values(&[destination(ctx), &status.to_string()])
+            .observe(started.elapsed().as_secs_f64());
+    }
+}
+
+pub(crate) fn record_retry(ctx: &ReqCtx, reason: RetryReason) {
+    let reason = match reason {
+        RetryReason::ConnectFailure => "connect_failure",
+        RetryReason::Timeout => "timeout",
+        RetryReason::ConnectionError => "connection_error",
+        RetryReason::Status(_) => "status",
+    };
+
+    UPSTREAM_RETRIES_TOTAL
+        .with_label_values(&[destination(ctx), reason])
+        .inc();
+}
+
+/// Counts an upgraded connection as open until dropped.
+pub(crate) struct OpenConnection(IntGauge);
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+pub(crate) fn record_upgrade(destination: &str, protocol: &str) -> OpenConnection {
+    UPGRADED_CONNECTIONS_TOTAL
+        .with_label_values(&[destination, protocol])
+        .inc();
+    let open = UPGRADED_CONNECTIONS.with_label_values(&[destination, protocol]);
+    open.inc();
+    OpenConnection(open)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extension_methods_share_one_label() {
+        assert_eq!(method_label(&Method::PATCH), "PATCH");
+        assert_eq!(method_label(&Method::from_bytes(b"FOO1").unwrap()), UNKNOWN);
+        assert_eq!(
+            method_label(&Method::from_bytes(b"PROPFIND").unwrap()),
+            UNKNOWN
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use crate::utils::requests::{apply_upstream_host_headers, parse_origin};
+use bytes::{Bytes, BytesMut};
+use cardinal_base::destinations::container::{DestinationContainer, DestinationWrapper};
+use http::Version;
+use pingora::connectors::http::Connector;
+use pingora::http::RequestHeader;
+use pingora::prelude::HttpPeer;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Upper bound for a mirrored exchange, so a stuck shadow cannot pile up tasks.
+const MIRROR_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Copy of a downstream request bound for a shadow destination, sent once its body is read.
+pub struct MirroredRequest {
+    target: Arc<DestinationWrapper>,
+    header: RequestHeader,
+    body: BytesMut,
+    max_body_bytes: usize,
+}
+
+impl MirroredRequest {
+    /// Copies `req` when `backend` mirrors and the next request is sampled.
+    pub(crate) fn sample(
+        container: &DestinationContainer,
+        backend: &DestinationWrapper,
+        req: &RequestHeader,
+    ) -> Option<Self> {
+        let mirror = backend.mirror.as_ref()?;
+        if !mirror.sample() {
+            return None;
+        }
+        let target = container.get_destination(mirror.destination())?;
+
+        Some(Self {
+            target,
+            header: req.clone(),
+            body: BytesMut::new(),
+            max_body_bytes: mirror.max_body_bytes(),
+        })
+    }
+
+    /// Buffers a body chunk, `false` once the body outgrew the cap and the copy is dropped.
+    pub(crate) fn push_body(&mut self, chunk: &[u8]) -> bool {
+        if self.body.len() + chunk.len() > self.max_body_bytes {
+            return false;
+        }
+        self.body.extend_from_slice(chunk);
+        true
+    }
+
+    /// Sends the copy in the background and discards the response.
+    pub(crate) fn spawn(self, connector: Arc<Connector>) {
+        tokio::spawn(async move {
+            let destination = self.target.destination.name.clone();
+            match tokio::time::timeout(MIRROR_TIMEOUT, self.send(&connector)).await {
+                Ok(Ok(status)) => debug!(backend_id = %destination, status, "Mirror responded"),
+                Ok(Err(reason)) => warn!(backend_id = %destination, %reason, "Mirror failed"),
+                Err(_) => warn!(backend_id = %destination, "Mirror timed out"),
+            }
+        });
+    }
+
+    async fn send(self, connector: &Connector) -> Result<u16, String> {
+        let Self {
+            target,
+            mut header,
+            body,
+            ..
+        } = self;
+        let endpoint = target
+            .upstreams
+            .select(None)
+            .ok_or_else(|| "no healthy upstream".to_string())?;
+        let (host, port, is_tls) = parse_origin(&endpoint.url).map_err(|e| e.to_string())?;
+
+        let sni = target.destination.sni.clone().unwrap_or(host.clone());
+        let mut peer = HttpPeer::new(format!("{host}:{port}"), is_tls, sni);
+        if let (true, Some(tls)) = (is_tls, &target.upstream_tls) {
+            tls.apply(&mut peer);
+        }
+        if let Some(timeout) = &target.destination.timeout {
+            peer.options.total_connection_timeout = timeout.connect.map(Duration::from_millis);
+            peer.options.read_timeout = timeout.read.map(Duration::from_millis);
+        }
+
+        apply_upstream_host_headers(&mut header, &target.destination, &endpoint.url)
+            .map_err(|e| e.to_string())?;
+        // The copy goes over HTTP/1.1 with the buffered body, whatever the client used.
+        header.set_version(Version::HTTP_11);
+        header.remove_header("Transfer-Encoding");
+        if body.is_empty() {
+            header.remove_header("Content-Length");
+        } else {
+            header
+                .insert_header("Content-Length", body.len())
+                .map_err(|e| e.to_string())?;
+        }
+
+        let (mut session, _reused) = connector
+            .get_http_session(&peer)
+            .await
+            .map_err(|e| e.to_string())?;
+        session
+            .write_request_header(Box::new(header))
+            .await
+            .map_err(|e| e.to_string())?;
+        if !body.is_empty() {
+            session
+                .write_request_body(Bytes::from(body), true)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        session
+            .finish_request_body()
+            .await
+            .map_err(|e| e.to_string())?;
+        session
+            .read_response_header()
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = session
+            .response_header()
+            .map(|resp| resp.status.as_u16())
+            .ok_or_else(|| "missing response header".to_string())?;
+        while session
+            .read_response_body()
+            .await
+            .map_err(|e| e.to_string())?
+            .is_some()
+        {}
+        connector.release_http_session(session, &peer, None).await;
+
+        Ok(status)
+    }
+}
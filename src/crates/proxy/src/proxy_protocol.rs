@@ -0,0 +1,440 @@
+use cardinal_errors::internal::CardinalInternalError;
+use cardinal_errors::CardinalError;
+use parking_lot::Mutex;
+use pingora::apps::ServerApp;
+use pingora::prelude::*;
+use pingora::protocols::l4::socket::SocketAddr as PingoraSocketAddr;
+use pingora::server::{Fds, ListenFds, ShutdownWatch};
+use pingora::services::listening::Service;
+use pingora::services::Service as ServiceTrait;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::fd::IntoRawFd;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex as AsyncMutex;
+use tracing::{debug, warn};
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+/// Longest v1 header, CRLF included.
+const V1_MAX_LEN: usize = 107;
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+const V2_HEADER_LEN: usize = 16;
+/// Time a client has to send its PROXY header before the connection is dropped.
+const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Original client and server addresses announced by the load balancer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxiedAddresses {
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ParsedHeader {
+    /// More bytes are needed.
+    Incomplete,
+    /// `addresses` is `None` for `LOCAL`/`UNKNOWN` headers, e.g. load balancer health checks.
+    Complete {
+        len: usize,
+        addresses: Option<ProxiedAddresses>,
+    },
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid PROXY header: {reason}"),
+    )
+}
+
+fn parse_header(buf: &[u8]) -> io::Result<ParsedHeader> {
+    let shared = buf.len().min(V2_SIGNATURE.len());
+    if buf[..shared] == V2_SIGNATURE[..shared] {
+        return parse_v2(buf);
+    }
+    let shared = buf.len().min(V1_PREFIX.len());
+    if buf[..shared] == V1_PREFIX[..shared] {
+        return parse_v1(buf);
+    }
+    Err(invalid("missing signature"))
+}
+
+fn parse_v1(buf: &[u8]) -> io::Result<ParsedHeader> {
+    let window = &buf[..buf.len().min(V1_MAX_LEN)];
+    let Some(end) = window.windows(2).position(|w| w == b"\r\n") else {
+        return if buf.len() >= V1_MAX_LEN {
+            Err(invalid("v1 line too long"))
+        } else {
+            Ok(ParsedHeader::Incomplete)
+        };
+    };
+
+    let line = std::str::from_utf8(&buf[..end]).map_err(|_| invalid("v1 line is not ASCII"))?;
+    let parts = line.split(' ').collect::<Vec<_>>();
+    let addresses = match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => None,
+        ["PROXY", "TCP4" | "TCP6", source, destination, source_port, destination_port] => {
+            let address = |ip: &str, port: &str| -> io::Result<SocketAddr> {
+                let ip = ip.parse::<IpAddr>().map_err(|_| invalid("v1 address"))?;
+                let port = port.parse::<u16>().map_err(|_| invalid("v1 port"))?;
+                Ok(SocketAddr::new(ip, port))
+            };
+            Some(ProxiedAddresses {
+                source: address(source, source_port)?,
+                destination: address(destination, destination_port)?,
+            })
+        }
+        _ => return Err(invalid("v1 line")),
+    };
+
+    Ok(ParsedHeader::Complete {
+        len: end + 2,
+        addresses,
+    })
+}
+
+fn parse_v2(buf: &[u8]) -> io::Result<ParsedHeader> {
+    if buf.len() < V2_HEADER_LEN {
+        return Ok(ParsedHeader::Incomplete);
+    }
+    if buf[12] >> 4 != 2 {
+        return Err(invalid("v2 version"));
+    }
+    let len = V2_HEADER_LEN + u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    if buf.len() < len {
+        return Ok(ParsedHeader::Incomplete);
+    }
+
+    let body = &buf[V2_HEADER_LEN..len];
+    let port = |at: usize| u16::from_be_bytes([body[at], body[at + 1]]);
+    let addresses = match (buf[12] & 0x0f, buf[13] >> 4) {
+        // LOCAL: sent by the load balancer itself, keep the connection addresses.
+        (0, _) => None,
+        (1, 1) if body.len() >= 12 => {
+            let ip = |at: usize| {
+                IpAddr::V4(Ipv4Addr::new(
+                    body[at],
+                    body[at + 1],
+                    body[at + 2],
+                    body[at + 3],
+                ))
+            };
+            Some(ProxiedAddresses {
+                source: SocketAddr::new(ip(0), port(8)),
+                destination: SocketAddr::new(ip(4), port(10)),
+            })
+        }
+        (1, 2) if body.len() >= 36 => {
+            let ip = |at: usize| {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(&body[at..at + 16]);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            };
+            Some(ProxiedAddresses {
+                source: SocketAddr::new(ip(0), port(32)),
+                destination: SocketAddr::new(ip(16), port(34)),
+            })
+        }
+        // UNSPEC or UNIX sockets carry no usable address.
+        (1, 0 | 3) => None,
+        _ => return Err(invalid("v2 command or address family")),
+    };
+
+    Ok(ParsedHeader::Complete { len, addresses })
+}
+
+/// Addresses announced on relayed connections, keyed by the relay's end of the loopback
+/// connection, i.e. the client address pingora sees.
+#[derive(Clone, Default)]
+pub struct ProxiedConnections {
+    connections: Arc<Mutex<HashMap<SocketAddr, ProxiedAddresses>>>,
+}
+
+impl ProxiedConnections {
+    pub fn get(&self, relay: &SocketAddr) -> Option<ProxiedAddresses> {
+        self.connections.lock().get(relay).copied()
+    }
+
+    fn insert(&self, relay: SocketAddr, addresses: ProxiedAddresses) {
+        self.connections.lock().insert(relay, addresses);
+    }
+
+    fn remove(&self, relay: &SocketAddr) {
+        self.connections.lock().remove(relay);
+    }
+}
+
+/// Client and local addresses of the connection behind `session`. Behind a relay, these are the
+/// addresses from the PROXY header, and connections the relay did not open are refused.
+pub(crate) fn resolve_addresses(
+    session: &Session,
+    connections: Option<&ProxiedConnections>,
+) -> Result<(Option<SocketAddr>, Option<SocketAddr>)> {
+    let inet = |addr: Option<&PingoraSocketAddr>| addr.and_then(|addr| addr.as_inet()).copied();
+    let client = inet(session.client_addr());
+    let server = inet(session.server_addr());
+
+    let Some(connections) = connections else {
+        return Ok((client, server));
+    };
+    match client.and_then(|relay| connections.get(&relay)) {
+        Some(proxied) => Ok((Some(proxied.source), Some(proxied.destination))),
+        None => Error::e_explain(
+            ErrorType::HTTPStatus(400),
+            "connection did not come through the PROXY protocol relay",
+        ),
+    }
+}
+
+/// Accepts connections on a public address, strips their PROXY header and relays them to the
+/// proxy service, which cannot read the header itself. The service listens on a loopback socket
+/// bound here and handed to pingora as an inherited listener, so its port is never released.
+pub struct ProxyProtocolRelay {
+    public: std::net::TcpListener,
+    internal: std::net::TcpListener,
+    internal_address: SocketAddr,
+    connections: ProxiedConnections,
+}
+
+impl ProxyProtocolRelay {
+    /// Binds `address` and the loopback listener of the proxy service.
+    pub fn bind(address: &str) -> Result<Self, CardinalError> {
+        let failed = |e: io::Error| {
+            CardinalError::InternalError(CardinalInternalError::FailedToInitiateServer(format!(
+                "PROXY protocol listener {address}: {e}"
+            )))
+        };
+
+        let public = std::net::TcpListener::bind(address).map_err(failed)?;
+        public.set_nonblocking(true).map_err(failed)?;
+        let internal = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(failed)?;
+        internal.set_nonblocking(true).map_err(failed)?;
+        let internal_address = internal.local_addr().map_err(failed)?;
+
+        Ok(Self {
+            public,
+            internal,
+            internal_address,
+            connections: ProxiedConnections::default(),
+        })
+    }
+
+    /// Loopback address the proxy service must listen on.
+    pub fn internal_address(&self) -> String {
+        self.internal_address.to_string()
+    }
+
+    /// Addresses of the relayed connections, for the proxy behind the relay.
+    pub fn connections(&self) -> ProxiedConnections {
+        self.connections.clone()
+    }
+
+    /// Runs `service`, listening on [`Self::internal_address`], behind the relay.
+    pub fn serve<A>(self, service: Service<A>) -> ProxyProtocolService<A> {
+        ProxyProtocolService {
+            relay: Some(self),
+            service,
+        }
+    }
+}
+
+/// Listening service running behind a [`ProxyProtocolRelay`].
+pub struct ProxyProtocolService<A> {
+    relay: Option<ProxyProtocolRelay>,
+    service: Service<A>,
+}
+
+#[async_trait::async_trait]
+impl<A: ServerApp + Send + Sync + 'static> ServiceTrait for ProxyProtocolService<A> {
+    async fn start_service(
+        &mut self,
+        fds: Option<ListenFds>,
+        shutdown: ShutdownWatch,
+        listeners_per_fd: usize,
+    ) {
+        let Some(relay) = self.relay.take() else {
+            return;
+        };
+        // Pingora takes listeners found in the table instead of binding their address.
+        let fds = fds.unwrap_or_else(|| Arc::new(AsyncMutex::new(Fds::new())));
+        fds.lock()
+            .await
+            .add(relay.internal_address(), relay.internal.into_raw_fd());
+
+        tokio::join!(
+            self.service
+                .start_service(Some(fds), shutdown.clone(), listeners_per_fd),
+            accept_loop(
+                relay.public,
+                relay.internal_address,
+                relay.connections,
+                shutdown
+            ),
+        );
+    }
+
+    fn name(&self) -> &str {
+        self.service.name()
+    }
+
+    fn threads(&self) -> Option<usize> {
+        self.service.threads
+    }
+}
+
+async fn accept_loop(
+    listener: std::net::TcpListener,
+    internal: SocketAddr,
+    connections: ProxiedConnections,
+    mut shutdown: ShutdownWatch,
+) {
+    let listener = match TcpListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(err) => {
+            warn!(%err, "Failed to start PROXY protocol listener");
+            return;
+        }
+    };
+
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((client, peer)) => {
+                    let connections = connections.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = relay(client, internal, connections).await {
+                            debug!(%err, %peer, "PROXY protocol connection closed");
+                        }
+                    });
+                }
+                Err(err) => warn!(%err, "Failed to accept PROXY protocol connection"),
+            },
+        }
+    }
+}
+
+async fn relay(
+    mut client: TcpStream,
+    internal: SocketAddr,
+    connections: ProxiedConnections,
+) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(V1_MAX_LEN);
+    let (len, addresses) = tokio::time::timeout(HEADER_TIMEOUT, async {
+        let mut chunk = [0u8; 512];
+        loop {
+            let read = client.read(&mut chunk).await?;
+            if read == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+            buf.extend_from_slice(&chunk[..read]);
+            if let ParsedHeader::Complete { len, addresses } = parse_header(&buf)? {
+                return Ok((len, addresses));
+            }
+        }
+    })
+    .await
+    .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+
+    let addresses = match addresses {
+        Some(addresses) => addresses,
+        None => ProxiedAddresses {
+            source: client.peer_addr()?,
+            destination: client.local_addr()?,
+        },
+    };
+
+    let mut upstream = TcpStream::connect(internal).await?;
+    let relay_addr = upstream.local_addr()?;
+    connections.insert(relay_addr, addresses);
+
+    let result = async {
+        upstream.write_all(&buf[len..]).await?;
+        tokio::io::copy_bidirectional(&mut client, &mut upstream).await
+    }
+    .await;
+    connections.remove(&relay_addr);
+    result.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(buf: &[u8]) -> (usize, Option<ProxiedAddresses>) {
+        match parse_header(buf).unwrap() {
+            ParsedHeader::Complete { len, addresses } => (len, addresses),
+            ParsedHeader::Incomplete => panic!("header should be complete"),
+        }
+    }
+
+    #[test]
+    fn parses_v1_tcp4_and_keeps_payload() {
+        let buf = b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 443\r\nGET / HTTP/1.1\r\n";
+        let (len, addresses) = complete(buf);
+
+        assert_eq!(&buf[len..], b"GET / HTTP/1.1\r\n");
+        assert_eq!(
+            addresses,
+            Some(ProxiedAddresses {
+                source: "203.0.113.7:51234".parse().unwrap(),
+                destination: "10.0.0.1:443".parse().unwrap(),
+            })
+        );
+        assert_eq!(complete(b"PROXY UNKNOWN\r\n"), (15, None));
+    }
+
+    #[test]
+    fn waits_for_complete_header() {
+        assert_eq!(parse_header(b"PRO").unwrap(), ParsedHeader::Incomplete);
+        assert_eq!(
+            parse_header(b"PROXY TCP4 1.2.3.4").unwrap(),
+            ParsedHeader::Incomplete
+        );
+        assert_eq!(
+            parse_header(&V2_SIGNATURE[..5]).unwrap(),
+            ParsedHeader::Incomplete
+        );
+    }
+
+    #[test]
+    fn rejects_missing_or_malformed_header() {
+        assert!(parse_header(b"GET / HTTP/1.1\r\n").is_err());
+        assert!(parse_header(b"PROXY TCP4 nope 10.0.0.1 1 2\r\n").is_err());
+        assert!(parse_header(&[b'P'; V1_MAX_LEN + 1]).is_err());
+    }
+
+    #[test]
+    fn parses_v2_inet_and_local() {
+        let mut buf = V2_SIGNATURE.to_vec();
+        buf.extend_from_slice(&[0x21, 0x11, 0, 12]);
+        buf.extend_from_slice(&[203, 0, 113, 7, 10, 0, 0, 1]);
+        buf.extend_from_slice(&51234u16.to_be_bytes());
+        buf.extend_from_slice(&443u16.to_be_bytes());
+        buf.extend_from_slice(b"payload");
+
+        let (len, addresses) = complete(&buf);
+        assert_eq!(&buf[len..], b"payload");
+        assert_eq!(
+            addresses.unwrap().source,
+            "203.0.113.7:51234".parse::<SocketAddr>().unwrap()
+        );
+
+        let mut local = V2_SIGNATURE.to_vec();
+        local.extend_from_slice(&[0x20, 0x00, 0, 0]);
+        assert_eq!(complete(&local), (16, None));
+    }
+
+    #[test]
+    fn relay_keeps_the_internal_port() {
+        let relay = ProxyProtocolRelay::bind("127.0.0.1:0").unwrap();
+        let internal = relay.internal_address();
+
+        assert!(internal.starts_with("127.0.0.1:"));
+        assert!(std::net::TcpListener::bind(&internal).is_err());
+    }
+}
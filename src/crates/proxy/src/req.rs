@@ -1,10 +1,63 @@
+use crate::drain::InFlightRequest;
+use crate::limits::BodyLimits;
+use crate::mirror::MirroredRequest;
 use crate::retry::RetryState;
+use crate::upgrade::UpgradeSession;
+use cardinal_base::destinations::balancer::{UpstreamEndpoint, UpstreamLease};
+use cardinal_base::destinations::circuit_breaker::CircuitPermit;
+use cardinal_config::CompressionAlgorithm;
 use cardinal_plugins::request_context::{RequestContext, RequestContextBase};
+use cardinal_plugins::trace::RequestTrace;
+use opentelemetry_sdk::trace::Span;
+use pingora::cache::CacheKey;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Default)]
 pub struct ReqCtx {
     pub ctx_base: RequestContextBase,
     pub retry_state: Option<RetryState>,
+    /// Endpoint currently selected for this request; holds its connection slot.
+    pub upstream: Option<UpstreamLease>,
+    /// Affinity hash used for consistent-hash selection, reused on retries.
+    pub upstream_hash: Option<u64>,
+    /// Endpoints that failed to connect and should be skipped on retry.
+    pub failed_upstreams: Vec<Arc<UpstreamEndpoint>>,
+    /// Circuit breaker admission, reported once the upstream outcome is known.
+    pub circuit_permit: Option<CircuitPermit>,
+    /// Set when a peer is handed to pingora, used for the connect latency metric.
+    pub upstream_connect_started: Option<Instant>,
+    /// Set once connected, used for the upstream response latency metric.
+    pub upstream_request_started: Option<Instant>,
+    /// Path and query as received, before the destination prefix was stripped.
+    pub request_uri: Option<String>,
+    /// Time from handing the peer to pingora until the upstream response header arrived.
+    pub upstream_duration: Option<Duration>,
+    /// Upstream attempts retried so far.
+    pub retries: u32,
+    /// Request span, present when tracing is enabled.
+    pub trace: Option<RequestTrace>,
+    /// Span of the upstream attempt in flight.
+    pub upstream_span: Option<Span>,
+    /// `tracing` span carrying the request ID, entered by every proxy hook.
+    pub log_span: Option<tracing::Span>,
+    /// Client address, as announced in the PROXY header on listeners that expect one.
+    pub client_addr: Option<SocketAddr>,
+    /// Address the client connected to, as announced in the PROXY header when present.
+    pub server_addr: Option<SocketAddr>,
+    /// Copy for the shadow destination, sent once the request body has been read.
+    pub mirror: Option<MirroredRequest>,
+    /// Response cache key, set when the destination caches and the request may be served from it.
+    pub cache_key: Option<CacheKey>,
+    /// Encoding negotiated for the response, dropped when the response does not qualify.
+    pub compression: Option<CompressionAlgorithm>,
+    /// Body size limits of the request and the bytes counted against them.
+    pub body_limits: BodyLimits,
+    /// Set when the client asks to upgrade the connection, e.g. to WebSocket.
+    pub upgrade: Option<UpgradeSession>,
+    /// Counts the request against a drain until the context is dropped.
+    pub in_flight: Option<InFlightRequest>,
 }
 
 impl ReqCtx {
@@ -20,6 +73,18 @@ impl ReqCtx {
         self.ctx_base.set_resolved_request(resolved_request);
     }
 
+    pub fn upstream_endpoint(&self) -> Option<&Arc<UpstreamEndpoint>> {
+        self.upstream.as_ref().map(|lease| lease.endpoint())
+    }
+
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.client_addr.map(|addr| addr.ip())
+    }
+
+    pub fn log_span(&self) -> tracing::Span {
+        self.log_span.clone().unwrap_or_else(tracing::Span::none)
+    }
+
     pub fn set(&mut self, key: &str, value: &str) {
         self.ctx_base.set(key, value);
     }
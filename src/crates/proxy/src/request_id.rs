@@ -0,0 +1,166 @@
+use async_trait::async_trait;
+use cardinal_config::{RequestIdFormat, ServerRequestId};
+use pingora::http::{RequestHeader, ResponseHeader};
+use pingora::modules::http::{HttpModule, HttpModuleBuilder, Module};
+use pingora::prelude::*;
+use std::any::Any;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Longest incoming ID kept as is, anything longer is replaced by a generated one.
+const MAX_INCOMING_LEN: usize = 200;
+
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Generates a new time-ordered request ID.
+pub fn generate(format: &RequestIdFormat) -> String {
+    let id = Uuid::now_v7();
+    match format {
+        RequestIdFormat::UuidV7 => id.to_string(),
+        RequestIdFormat::Ulid => encode_ulid(id.as_u128()),
+    }
+}
+
+/// Crockford base32 of the 128-bit value, the leading 48 bits being the millisecond
+/// timestamp in both UUIDv7 and ULID.
+fn encode_ulid(value: u128) -> String {
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+fn is_acceptable(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_INCOMING_LEN
+        && value.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Downstream module assigning the request ID before any proxy hook runs. The ID is
+/// written on the downstream request, so it is forwarded upstream, and on every response
+/// header sent downstream, including errors generated by the gateway.
+pub struct RequestIdModule {
+    settings: Arc<ServerRequestId>,
+    id: Option<String>,
+}
+
+impl RequestIdModule {
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+}
+
+#[async_trait]
+impl HttpModule for RequestIdModule {
+    async fn request_header_filter(&mut self, req: &mut RequestHeader) -> Result<()> {
+        let incoming = self
+            .settings
+            .trust_incoming
+            .then(|| req.headers.get(self.settings.header.as_str()))
+            .flatten()
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| is_acceptable(value))
+            .map(str::to_string);
+        let id = incoming.unwrap_or_else(|| generate(&self.settings.format));
+
+        req.insert_header(self.settings.header.clone(), &id)?;
+        self.id = Some(id);
+        Ok(())
+    }
+
+    async fn response_header_filter(
+        &mut self,
+        resp: &mut ResponseHeader,
+        _end_of_stream: bool,
+    ) -> Result<()> {
+        if let Some(id) = &self.id {
+            resp.insert_header(self.settings.header.clone(), id)?;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+pub struct RequestIdModuleBuilder {
+    settings: Arc<ServerRequestId>,
+}
+
+impl RequestIdModuleBuilder {
+    pub fn new(settings: ServerRequestId) -> Self {
+        Self {
+            settings: Arc::new(settings),
+        }
+    }
+}
+
+impl HttpModuleBuilder for RequestIdModuleBuilder {
+    fn init(&self) -> Module {
+        Box::new(RequestIdModule {
+            settings: self.settings.clone(),
+            id: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(trust_incoming: bool) -> Module {
+        RequestIdModuleBuilder::new(ServerRequestId {
+            header: "x-request-id".into(),
+            format: RequestIdFormat::UuidV7,
+            trust_incoming,
+        })
+        .init()
+    }
+
+    #[tokio::test]
+    async fn keeps_incoming_id_and_echoes_it() {
+        let mut module = module(true);
+        let mut req = RequestHeader::build("GET", b"/", None).unwrap();
+        req.insert_header("x-request-id", "abc-123").unwrap();
+
+        module.request_header_filter(&mut req).await.unwrap();
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        module
+            .response_header_filter(&mut resp, false)
+            .await
+            .unwrap();
+
+        assert_eq!(req.headers["x-request-id"], "abc-123");
+        assert_eq!(resp.headers["x-request-id"], "abc-123");
+    }
+
+    #[tokio::test]
+    async fn replaces_untrusted_or_invalid_ids() {
+        for (trust_incoming, incoming) in [(false, "abc-123"), (true, "has space")] {
+            let mut module = module(trust_incoming);
+            let mut req = RequestHeader::build("GET", b"/", None).unwrap();
+            req.insert_header("x-request-id", incoming).unwrap();
+
+            module.request_header_filter(&mut req).await.unwrap();
+
+            let id = req.headers["x-request-id"].to_str().unwrap();
+            assert!(Uuid::parse_str(id).is_ok_and(|id| id.get_version_num() == 7));
+        }
+    }
+
+    #[test]
+    fn ulid_is_26_crockford_characters_ordered_by_time() {
+        let first = generate(&RequestIdFormat::Ulid);
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let second = generate(&RequestIdFormat::Ulid);
+
+        assert_eq!(first.len(), 26);
+        assert!(first.bytes().all(|b| CROCKFORD.contains(&b)));
+        assert!(first[..10] < second[..10]);
+    }
+}
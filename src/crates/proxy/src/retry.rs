@@ -1,4 +1,6 @@
-use cardinal_config::{DestinationRetry, DestinationRetryBackoffType};
+use cardinal_config::{DestinationRetry, DestinationRetryBackoffType, DestinationRetryCondition};
+use http::Method;
+use pingora::{Error, ErrorType};
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 
@@ -97,6 +99,85 @@ impl RetryState {
     }
 }
 
+/// Why an upstream attempt is being considered for a retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryReason {
+    ConnectFailure,
+    Timeout,
+    ConnectionError,
+    Status(u16),
+}
+
+impl RetryReason {
+    /// Classifies an error raised after the upstream connection was established.
+    pub fn from_proxy_error(error: &Error) -> Option<Self> {
+        match error.etype() {
+            ErrorType::ReadTimedout | ErrorType::WriteTimedout => Some(RetryReason::Timeout),
+            ErrorType::ConnectionClosed | ErrorType::ReadError | ErrorType::WriteError => {
+                Some(RetryReason::ConnectionError)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the upstream may already have processed the request.
+    fn request_sent(&self) -> bool {
+        !matches!(self, RetryReason::ConnectFailure)
+    }
+}
+
+/// Which failures of a destination are worth retrying.
+pub struct RetryPolicy {
+    pub retry_on_status: Vec<u16>,
+    pub conditions: Vec<DestinationRetryCondition>,
+    pub retry_non_idempotent: bool,
+    pub budget: Option<Duration>,
+}
+
+impl From<&DestinationRetry> for RetryPolicy {
+    fn from(value: &DestinationRetry) -> Self {
+        RetryPolicy {
+            retry_on_status: value.retry_on_status.clone(),
+            conditions: value.retry_on.clone(),
+            retry_non_idempotent: value.retry_non_idempotent,
+            budget: value.budget_ms.map(Duration::from_millis),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn allows(&self, reason: RetryReason, method: &Method) -> bool {
+        let matches = match reason {
+            RetryReason::ConnectFailure => self
+                .conditions
+                .contains(&DestinationRetryCondition::ConnectFailure),
+            RetryReason::Timeout => self
+                .conditions
+                .contains(&DestinationRetryCondition::Timeout),
+            RetryReason::ConnectionError => self
+                .conditions
+                .contains(&DestinationRetryCondition::ConnectionError),
+            RetryReason::Status(status) => self.retry_on_status.contains(&status),
+        };
+
+        matches && (!reason.request_sent() || self.retry_non_idempotent || is_idempotent(method))
+    }
+
+    /// Whether waiting `next_delay` still fits in the budget of a request started at `started_at`.
+    pub fn within_budget(&self, started_at: Instant, next_delay: Duration) -> bool {
+        self.budget
+            .map(|budget| started_at.elapsed() + next_delay <= budget)
+            .unwrap_or(true)
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE | Method::TRACE
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -217,6 +298,7 @@ mod tests {
             interval_ms: 200,
             backoff_type: DestinationRetryBackoffType::Linear,
             max_interval: Some(150),
+            ..Default::default()
         };
 
         let state = RetryState::from(retry);
@@ -381,6 +463,7 @@ mod tests {
             interval_ms: 50,
             backoff_type: DestinationRetryBackoffType::Linear,
             max_interval: None,
+            ..Default::default()
         };
 
         let state = RetryState::from(retry);
@@ -395,6 +478,7 @@ mod tests {
             interval_ms: 100,
             backoff_type: DestinationRetryBackoffType::Exponential,
             max_interval: Some(250),
+            ..Default::default()
         };
 
         let mut state = RetryState::from(retry);
@@ -424,6 +508,7 @@ mod tests {
             interval_ms: 10,
             backoff_type: DestinationRetryBackoffType::Linear,
             max_interval: Some(10),
+            ..Default::default()
         };
 
         let mut state = RetryState::from(retry);
@@ -446,6 +531,7 @@ mod tests {
             interval_ms: u64::MAX / 4,
             backoff_type: DestinationRetryBackoffType::Exponential,
             max_interval: None,
+            ..Default::default()
         };
 
         let mut state = RetryState::from(retry);
@@ -465,6 +551,7 @@ mod tests {
             interval_ms: 90,
             backoff_type: DestinationRetryBackoffType::Exponential,
             max_interval: Some(200),
+            ..Default::default()
         };
 
         let mut state = RetryState::from(retry);
@@ -497,4 +584,71 @@ mod tests {
             ]
         );
     }
+
+    fn policy(retry_on_status: Vec<u16>, retry_non_idempotent: bool) -> RetryPolicy {
+        RetryPolicy::from(&DestinationRetry {
+            max_attempts: 3,
+            interval_ms: 10,
+            backoff_type: DestinationRetryBackoffType::None,
+            max_interval: None,
+            retry_on_status,
+            retry_on: vec![
+                DestinationRetryCondition::ConnectFailure,
+                DestinationRetryCondition::Timeout,
+            ],
+            retry_non_idempotent,
+            budget_ms: Some(100),
+        })
+    }
+
+    #[test]
+    fn policy_retries_listed_statuses_for_idempotent_methods_only() {
+        let policy = policy(vec![503], false);
+
+        assert!(policy.allows(RetryReason::Status(503), &Method::GET));
+        assert!(!policy.allows(RetryReason::Status(500), &Method::GET));
+        assert!(!policy.allows(RetryReason::Status(503), &Method::POST));
+        assert!(!policy.allows(RetryReason::Timeout, &Method::POST));
+        assert!(!policy.allows(RetryReason::ConnectionError, &Method::GET));
+    }
+
+    #[test]
+    fn policy_retries_connect_failures_for_non_idempotent_methods() {
+        let policy = policy(vec![], false);
+        assert!(policy.allows(RetryReason::ConnectFailure, &Method::POST));
+        assert!(policy.allows(RetryReason::ConnectFailure, &Method::PATCH));
+    }
+
+    #[test]
+    fn policy_can_retry_non_idempotent_methods() {
+        let policy = policy(vec![502], true);
+        assert!(policy.allows(RetryReason::Status(502), &Method::POST));
+        assert!(policy.allows(RetryReason::Timeout, &Method::PATCH));
+    }
+
+    #[test]
+    fn policy_budget_limits_next_attempt() {
+        let policy = policy(vec![], false);
+        let started = Instant::now();
+
+        assert!(policy.within_budget(started, Duration::from_millis(50)));
+        assert!(!policy.within_budget(started, Duration::from_millis(150)));
+    }
+
+    #[test]
+    fn retry_reason_classifies_proxy_errors() {
+        let timeout = Error::new(ErrorType::ReadTimedout);
+        let closed = Error::new(ErrorType::ConnectionClosed);
+        let other = Error::new(ErrorType::InternalError);
+
+        assert_eq!(
+            RetryReason::from_proxy_error(&timeout),
+            Some(RetryReason::Timeout)
+        );
+        assert_eq!(
+            RetryReason::from_proxy_error(&closed),
+            Some(RetryReason::ConnectionError)
+        );
+        assert_eq!(RetryReason::from_proxy_error(&other), None);
+    }
 }
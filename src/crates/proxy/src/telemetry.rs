@@ -0,0 +1,224 @@
+use crate::req::ReqCtx;
+use cardinal_config::{ServerTracing, TracingExporter};
+use cardinal_errors::internal::CardinalInternalError;
+use cardinal_errors::CardinalError;
+use cardinal_plugins::trace::RequestTrace;
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
+use opentelemetry::trace::{Span as _, SpanBuilder, SpanKind, Status, TraceContextExt};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use pingora::http::RequestHeader;
+use pingora::proxy::Session;
+use pingora::server::ShutdownWatch;
+use pingora::services::background::BackgroundService;
+use std::net::IpAddr;
+use tracing::warn;
+
+/// Builds the tracer provider for the configured exporter.
+pub fn tracer_provider(config: &ServerTracing) -> Result<SdkTracerProvider, CardinalError> {
+    let resource = Resource::builder()
+        .with_service_name(config.service_name.clone())
+        .build();
+    let builder = SdkTracerProvider::builder().with_resource(resource);
+
+    let builder = match config.exporter {
+        TracingExporter::Otlp => {
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_endpoint(&config.endpoint)
+                .build()
+                .map_err(|e| {
+                    CardinalError::InternalError(
+                        CardinalInternalError::InvalidTracingConfiguration(e.to_string()),
+                    )
+                })?;
+            builder.with_batch_exporter(exporter)
+        }
+        TracingExporter::Stdout => {
+            builder.with_simple_exporter(opentelemetry_stdout::SpanExporter::default())
+        }
+    };
+
+    Ok(builder.build())
+}
+
+/// Flushes buffered spans and shuts the tracer provider down with the server.
+pub struct TraceExportService {
+    provider: SdkTracerProvider,
+}
+
+impl TraceExportService {
+    pub fn new(provider: SdkTracerProvider) -> Self {
+        Self { provider }
+    }
+}
+
+#[async_trait::async_trait]
+impl BackgroundService for TraceExportService {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        let _ = shutdown.changed().await;
+
+        let provider = self.provider.clone();
+        let shutdown = tokio::task::spawn_blocking(move || provider.shutdown()).await;
+        if let Ok(Err(err)) = shutdown {
+            warn!(%err, "Failed to flush traces on shutdown");
+        }
+    }
+}
+
+struct RequestHeaderExtractor<'a>(&'a RequestHeader);
+
+impl Extractor for RequestHeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .headers
+            .get(key)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.headers.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+#[derive(Default)]
+struct HeaderCollector(Vec<(String, String)>);
+
+impl Injector for HeaderCollector {
+    fn set(&mut self, key: &str, value: String) {
+        self.0.push((key.to_string(), value));
+    }
+}
+
+/// Starts the request span, continuing the trace of an incoming `traceparent`.
+pub(crate) fn start_request(
+    tracer: &SdkTracer,
+    session: &Session,
+    client: Option<IpAddr>,
+) -> RequestTrace {
+    let req = session.req_header();
+    let parent = TraceContextPropagator::new().extract(&RequestHeaderExtractor(req));
+
+    let mut attributes = vec![
+        KeyValue::new("http.request.method", req.method.to_string()),
+        KeyValue::new("url.path", req.uri.path().to_string()),
+    ];
+    if let Some(client) = client {
+        attributes.push(KeyValue::new("client.address", client.to_string()));
+    }
+
+    let span = SpanBuilder::from_name(req.method.to_string())
+        .with_kind(SpanKind::Server)
+        .with_attributes(attributes)
+        .start_with_context(tracer, &parent);
+
+    RequestTrace::new(tracer.clone(), parent.with_span(span))
+}
+
+/// Ends the request span with the status written downstream.
+pub(crate) fn finish_request(session: &Session, ctx: &mut ReqCtx, failed: bool) {
+    if let Some(mut span) = ctx.upstream_span.take() {
+        span.end();
+    }
+    let Some(trace) = ctx.trace.take() else {
+        return;
+    };
+
+    let span = trace.context().span();
+    let status = session.response_written().map(|resp| resp.status.as_u16());
+    if let Some(status) = status {
+        span.set_attribute(KeyValue::new("http.response.status_code", status as i64));
+    }
+    if let Some(req) = ctx.ctx_base.resolved_request.as_ref() {
+        span.set_attribute(KeyValue::new(
+            "cardinal.destination",
+            req.backend.destination.name.clone(),
+        ));
+    }
+    if failed || status.is_some_and(|status| status >= 500) {
+        span.set_status(Status::error(""));
+    }
+    span.end();
+}
+
+/// Starts the span of one upstream attempt, ending the previous attempt's if still open.
+pub(crate) fn start_upstream(ctx: &mut ReqCtx, method: &str, url: &str) {
+    let Some(trace) = ctx.trace.as_ref() else {
+        return;
+    };
+
+    let mut attributes = vec![
+        KeyValue::new("http.request.method", method.to_string()),
+        KeyValue::new("url.full", url.to_string()),
+    ];
+    let resends = ctx.retry_state.as_ref().map_or(0, |r| r.current_attempt);
+    if resends > 0 {
+        attributes.push(KeyValue::new("http.request.resend_count", resends as i64));
+    }
+
+    let span = trace.child_with_kind(format!("upstream {method}"), SpanKind::Client, attributes);
+    if let Some(mut previous) = ctx.upstream_span.replace(span) {
+        previous.end();
+    }
+}
+
+/// Ends the current upstream attempt span, marking it failed when `error` is set.
+pub(crate) fn finish_upstream(ctx: &mut ReqCtx, status: Option<u16>, error: Option<&str>) {
+    let Some(mut span) = ctx.upstream_span.take() else {
+        return;
+    };
+
+    if let Some(status) = status {
+        span.set_attribute(KeyValue::new("http.response.status_code", status as i64));
+    }
+    if let Some(error) = error {
+        span.set_status(Status::error(error.to_string()));
+    } else if status.is_some_and(|status| status >= 500) {
+        span.set_status(Status::error(""));
+    }
+    span.end();
+}
+
+/// Writes `traceparent`/`tracestate` for the current upstream attempt.
+pub(crate) fn inject_upstream(ctx: &ReqCtx, upstream_request: &mut RequestHeader) {
+    let Some(span) = ctx.upstream_span.as_ref() else {
+        return;
+    };
+
+    let context = Context::new().with_remote_span_context(span.span_context().clone());
+    let mut headers = HeaderCollector::default();
+    TraceContextPropagator::new().inject_context(&context, &mut headers);
+    for (key, value) in headers.0 {
+        let _ = upstream_request.insert_header(key, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_incoming_traceparent() {
+        let mut req = RequestHeader::build("GET", b"/", None).unwrap();
+        req.insert_header(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .unwrap();
+        req.insert_header("tracestate", "vendor=value").unwrap();
+
+        let context = TraceContextPropagator::new().extract(&RequestHeaderExtractor(&req));
+        let span = context.span();
+        let span_context = span.span_context();
+
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(span_context.span_id().to_string(), "00f067aa0ba902b7");
+        assert_eq!(span_context.trace_state().header(), "vendor=value");
+    }
+}
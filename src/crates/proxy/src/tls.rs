@@ -0,0 +1,175 @@
+use cardinal_config::{ServerTls, TlsAlpn, TlsCertificate, TlsVersion};
+use cardinal_errors::internal::CardinalInternalError;
+use cardinal_errors::CardinalError;
+use pingora::listeners::tls::TlsSettings;
+use pingora::listeners::{TlsAccept, ALPN};
+use pingora::protocols::tls::TlsRef;
+use pingora::tls::ext;
+use pingora::tls::pkey::{PKey, Private};
+use pingora::tls::ssl::{NameType, SslVersion};
+use pingora::tls::x509::X509;
+use tracing::warn;
+
+struct LoadedCertificate {
+    server_names: Vec<String>,
+    leaf: X509,
+    chain: Vec<X509>,
+    key: PKey<Private>,
+}
+
+/// Picks the listener certificate from the SNI sent by the client during the handshake.
+pub struct SniCertificateResolver {
+    certificates: Vec<LoadedCertificate>,
+}
+
+impl SniCertificateResolver {
+    pub fn from_config(tls: &ServerTls) -> Result<Self, CardinalError> {
+        if tls.certificates.is_empty() {
+            return Err(invalid_tls("at least one certificate is required"));
+        }
+
+        let certificates = tls
+            .certificates
+            .iter()
+            .map(load_certificate)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { certificates })
+    }
+
+    fn select(&self, server_name: Option<&str>) -> &LoadedCertificate {
+        server_name
+            .map(|name| name.to_ascii_lowercase())
+            .and_then(|name| {
+                let exact = self
+                    .certificates
+                    .iter()
+                    .find(|c| c.server_names.contains(&name));
+                exact.or_else(|| {
+                    self.certificates.iter().find(|c| {
+                        c.server_names
+                            .iter()
+                            .any(|pattern| matches_wildcard(pattern, &name))
+                    })
+                })
+            })
+            .unwrap_or(&self.certificates[0])
+    }
+}
+
+#[async_trait::async_trait]
+impl TlsAccept for SniCertificateResolver {
+    async fn certificate_callback(&self, ssl: &mut TlsRef) {
+        let server_name = ssl.servername(NameType::HOST_NAME).map(str::to_string);
+        let certificate = self.select(server_name.as_deref());
+
+        if let Err(err) = ext::ssl_use_certificate(ssl, &certificate.leaf) {
+            warn!(%err, "Failed to use TLS certificate");
+            return;
+        }
+        for intermediate in &certificate.chain {
+            if let Err(err) = ext::ssl_add_chain_cert(ssl, intermediate) {
+                warn!(%err, "Failed to add TLS chain certificate");
+            }
+        }
+        if let Err(err) = ext::ssl_use_private_key(ssl, &certificate.key) {
+            warn!(%err, "Failed to use TLS private key");
+        }
+    }
+}
+
+/// Builds listener TLS settings: SNI certificate selection, minimum version and ALPN.
+pub fn tls_settings(tls: &ServerTls) -> Result<TlsSettings, CardinalError> {
+    let resolver = SniCertificateResolver::from_config(tls)?;
+    let mut settings =
+        TlsSettings::with_callbacks(Box::new(resolver)).map_err(|e| invalid_tls(&e.to_string()))?;
+
+    if let Some(min_version) = &tls.min_version {
+        let version = match min_version {
+            TlsVersion::Tls1_2 => SslVersion::TLS1_2,
+            TlsVersion::Tls1_3 => SslVersion::TLS1_3,
+        };
+        settings
+            .set_min_proto_version(Some(version))
+            .map_err(|e| invalid_tls(&e.to_string()))?;
+    }
+
+    settings.set_alpn(match tls.alpn {
+        TlsAlpn::H1 => ALPN::H1,
+        TlsAlpn::H2 => ALPN::H2,
+        TlsAlpn::H2H1 => ALPN::H2H1,
+    });
+
+    Ok(settings)
+}
+
+fn load_certificate(config: &TlsCertificate) -> Result<LoadedCertificate, CardinalError> {
+    let cert_pem = std::fs::read(&config.cert_path)?;
+    let key_pem = std::fs::read(&config.key_path)?;
+
+    let mut certs = X509::stack_from_pem(&cert_pem)
+        .map_err(|e| invalid_tls(&format!("{}: {e}", config.cert_path)))?
+        .into_iter();
+    let leaf = certs
+        .next()
+        .ok_or_else(|| invalid_tls(&format!("{} contains no certificate", config.cert_path)))?;
+    let key = PKey::private_key_from_pem(&key_pem)
+        .map_err(|e| invalid_tls(&format!("{}: {e}", config.key_path)))?;
+
+    Ok(LoadedCertificate {
+        server_names: config
+            .server_names
+            .iter()
+            .map(|n| n.to_ascii_lowercase())
+            .collect(),
+        leaf,
+        chain: certs.collect(),
+        key,
+    })
+}
+
+/// `*.example.com` matches exactly one extra label, e.g. `api.example.com`.
+fn matches_wildcard(pattern: &str, name: &str) -> bool {
+    let Some(suffix) = pattern.strip_prefix("*.") else {
+        return false;
+    };
+
+    name.strip_suffix(suffix)
+        .and_then(|prefix| prefix.strip_suffix('.'))
+        .map(|label| !label.is_empty() && !label.contains('.'))
+        .unwrap_or(false)
+}
+
+fn invalid_tls(reason: &str) -> CardinalError {
+    CardinalError::InternalError(CardinalInternalError::InvalidTlsConfiguration(
+        reason.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_matches_single_label() {
+        assert!(matches_wildcard("*.example.com", "api.example.com"));
+        assert!(!matches_wildcard("*.example.com", "example.com"));
+        assert!(!matches_wildcard("*.example.com", "a.b.example.com"));
+        assert!(!matches_wildcard("api.example.com", "api.example.com"));
+    }
+
+    #[test]
+    fn missing_certificate_file_is_reported() {
+        let tls = ServerTls {
+            certificates: vec![TlsCertificate {
+                cert_path: "does-not-exist.crt".into(),
+                key_path: "does-not-exist.key".into(),
+                server_names: vec![],
+            }],
+            min_version: None,
+            alpn: TlsAlpn::H1,
+        };
+
+        assert!(SniCertificateResolver::from_config(&tls).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::metrics::{record_upgrade, OpenConnection};
+use cardinal_config::DestinationWebSocket;
+use http::header::UPGRADE;
+use http::{StatusCode, Version};
+use pingora::http::{RequestHeader, ResponseHeader};
+use std::time::Duration;
+
+const WEBSOCKET: &str = "websocket";
+
+/// Connection upgrade asked for by the client. Once the upstream switches protocols the
+/// exchange is no longer HTTP, WebSocket frames are checked against the destination limits.
+pub struct UpgradeSession {
+    protocol: String,
+    websocket: Option<DestinationWebSocket>,
+    client: FrameMeter,
+    upstream: FrameMeter,
+    open: Option<OpenConnection>,
+}
+
+impl UpgradeSession {
+    /// `None` unless `req` asks for an upgrade.
+    pub(crate) fn requested(
+        req: &RequestHeader,
+        websocket: Option<&DestinationWebSocket>,
+    ) -> Option<Self> {
+        // Same test as pingora, which only switches HTTP/1.1 connections.
+        if req.version != Version::HTTP_11 {
+            return None;
+        }
+        let protocol = req
+            .headers
+            .get(UPGRADE)?
+            .to_str()
+            .ok()
+            .and_then(|value| value.split(',').next())
+            .map(|value| value.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        let websocket = websocket.filter(|_| protocol == WEBSOCKET).cloned();
+
+        Some(Self {
+            protocol,
+            websocket,
+            client: FrameMeter::default(),
+            upstream: FrameMeter::default(),
+            open: None,
+        })
+    }
+
+    /// Idle timeout of the connection, when a WebSocket one is configured.
+    pub(crate) fn idle_timeout(&self) -> Option<Duration> {
+        self.websocket
+            .as_ref()
+            .and_then(|websocket| websocket.idle_timeout_ms)
+            .map(Duration::from_millis)
+    }
+
+    /// Counts the connection as open when the upstream switched protocols.
+    pub(crate) fn response(&mut self, destination: &str, resp: &ResponseHeader) {
+        if resp.status == StatusCode::SWITCHING_PROTOCOLS && self.open.is_none() {
+            self.open = Some(record_upgrade(destination, &self.protocol));
+        }
+    }
+
+    /// Follows the frames sent by the client, `false` once one exceeds the limits.
+    pub(crate) fn push_client(&mut self, chunk: &[u8]) -> bool {
+        match (&self.websocket, &self.open) {
+            (Some(limits), Some(_)) => self.client.push(chunk, limits),
+            _ => true,
+        }
+    }
+
+    /// Follows the frames sent by the upstream, `false` once one exceeds the limits.
+    pub(crate) fn push_upstream(&mut self, chunk: &[u8]) -> bool {
+        match (&self.websocket, &self.open) {
+            (Some(limits), Some(_)) => self.upstream.push(chunk, limits),
+            _ => true,
+        }
+    }
+}
+
+/// Frame boundaries in one direction of a WebSocket connection.
+#[derive(Debug, Default)]
+struct FrameMeter {
+    /// Header bytes of the next frame read so far.
+    header: Vec<u8>,
+    /// Payload bytes of the current frame still to come.
+    payload_left: u64,
+    /// Payload bytes of the data message in progress.
+    message: u64,
+}
+
+impl FrameMeter {
+    fn push(&mut self, mut chunk: &[u8], limits: &DestinationWebSocket) -> bool {
+        while !chunk.is_empty() {
+            if self.payload_left > 0 {
+                let skip = usize::try_from(self.payload_left)
+                    .map_or(chunk.len(), |left| left.min(chunk.len()));
+                chunk = &chunk[skip..];
+                self.payload_left -= skip as u64;
+                continue;
+            }
+
+            let take = (header_len(&self.header) - self.header.len()).min(chunk.len());
+            self.header.extend_from_slice(&chunk[..take]);
+            chunk = &chunk[take..];
+            if self.header.len() == header_len(&self.header) && !self.next_frame(limits) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn next_frame(&mut self, limits: &DestinationWebSocket) -> bool {
+        let fin = self.header[0] & 0x80 != 0;
+        let opcode = self.header[0] & 0x0f;
+        let len = match self.header[1] & 0x7f {
+            126 => u64::from(u16::from_be_bytes([self.header[2], self.header[3]])),
+            127 => u64::from_be_bytes(self.header[2..10].try_into().unwrap_or_default()),
+            len => u64::from(len),
+        };
+        self.header.clear();
+        self.payload_left = len;
+        if exceeds(limits.max_frame_bytes, len) {
+            return false;
+        }
+
+        // Control frames may come between the fragments of a message and are not part of it.
+        if opcode < 0x8 {
+            self.message += len;
+            if exceeds(limits.max_message_bytes, self.message) {
+                return false;
+            }
+            if fin {
+                self.message = 0;
+            }
+        }
+        true
+    }
+}
+
+/// Length of the frame header starting with `header`, as far as it is known.
+fn header_len(header: &[u8]) -> usize {
+    let Some(second) = header.get(1) else {
+        return 2;
+    };
+    let extended = match second & 0x7f {
+        126 => 2,
+        127 => 8,
+        _ => 0,
+    };
+    let mask = if second & 0x80 != 0 { 4 } else { 0 };
+    2 + extended + mask
+}
+
+fn exceeds(max: Option<usize>, len: u64) -> bool {
+    max.is_some_and(|max| len > max as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::Method;
+
+    fn frame(fin: bool, opcode: u8, len: usize, masked: bool) -> Vec<u8> {
+        let mut frame = vec![if fin { 0x80 } else { 0 } | opcode];
+        let mask = if masked { 0x80 } else { 0 };
+        match len {
+            0..=125 => frame.push(mask | len as u8),
+            126..=65535 => {
+                frame.push(mask | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ => {
+                frame.push(mask | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        if masked {
+            frame.extend_from_slice(&[1, 2, 3, 4]);
+        }
+        frame.resize(frame.len() + len, b'x');
+        frame
+    }
+
+    fn limits(
+        max_frame_bytes: Option<usize>,
+        max_message_bytes: Option<usize>,
+    ) -> DestinationWebSocket {
+        DestinationWebSocket {
+            idle_timeout_ms: None,
+            max_frame_bytes,
+            max_message_bytes,
+        }
+    }
+
+    #[test]
+    fn detects_upgrade_requests() {
+        let mut req = RequestHeader::build(Method::GET, b"/chat", None).unwrap();
+        assert!(UpgradeSession::requested(&req, None).is_none());
+
+        let websocket = limits(None, None);
+        req.insert_header("Upgrade", "WebSocket").unwrap();
+        let upgrade = UpgradeSession::requested(&req, Some(&websocket)).unwrap();
+        assert_eq!(upgrade.protocol, "websocket");
+        assert!(upgrade.websocket.is_some());
+
+        req.insert_header("Upgrade", "h2c").unwrap();
+        let upgrade = UpgradeSession::requested(&req, Some(&websocket)).unwrap();
+        assert_eq!(upgrade.protocol, "h2c");
+        assert!(upgrade.websocket.is_none());
+
+        req.set_version(Version::HTTP_10);
+        assert!(UpgradeSession::requested(&req, Some(&websocket)).is_none());
+    }
+
+    #[test]
+    fn follows_frames_split_across_chunks() {
+        let limits = limits(Some(200), None);
+        let mut meter = FrameMeter::default();
+        let mut stream = frame(true, 0x1, 150, true);
+        stream.extend(frame(true, 0x2, 70000, false));
+
+        assert!(meter.push(&stream[..3], &limits));
+        assert!(meter.push(&stream[3..160], &limits));
+        assert!(!meter.push(&stream[160..], &limits));
+    }
+
+    #[test]
+    fn sums_fragments_of_a_message() {
+        let limits = limits(None, Some(100));
+        let mut meter = FrameMeter::default();
+
+        assert!(meter.push(&frame(false, 0x1, 60, true), &limits));
+        assert!(meter.push(&frame(true, 0x9, 20, true), &limits));
+        assert!(meter.push(&frame(true, 0x0, 40, true), &limits));
+        assert!(meter.push(&frame(true, 0x1, 100, true), &limits));
+        assert!(meter.push(&frame(false, 0x2, 60, true), &limits));
+        assert!(!meter.push(&frame(true, 0x0, 41, true), &limits));
+    }
+}
@@ -1 +1,2 @@
+pub(crate) mod circuit_breaker;
 pub(crate) mod requests;
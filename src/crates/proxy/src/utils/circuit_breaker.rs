@@ -0,0 +1,109 @@
+use crate::req::ReqCtx;
+use cardinal_base::destinations::container::{DestinationContainer, DestinationWrapper};
+use pingora::prelude::*;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Applies the destination circuit breaker, returning the destination that should serve the
+/// request. Falls back to the configured alternate destination while the breaker is open and
+/// returns `None` when the request must be rejected.
+pub(crate) fn admit_backend(
+    container: &DestinationContainer,
+    backend: Arc<DestinationWrapper>,
+    ctx: &mut ReqCtx,
+) -> Option<Arc<DestinationWrapper>> {
+    let Some(breaker) = backend.circuit_breaker.clone() else {
+        return Some(backend);
+    };
+
+    if let Some(permit) = breaker.try_acquire() {
+        ctx.circuit_permit = Some(permit);
+        return Some(backend);
+    }
+
+    let fallback = breaker
+        .fallback_destination()
+        .and_then(|name| container.get_destination(name))?;
+    if let Some(fallback_breaker) = &fallback.circuit_breaker {
+        ctx.circuit_permit = Some(fallback_breaker.try_acquire()?);
+    }
+
+    info!(
+        backend_id = %backend.destination.name,
+        fallback = %fallback.destination.name,
+        "Circuit breaker open, routing to fallback destination"
+    );
+    Some(fallback)
+}
+
+/// Reports the upstream status to the circuit breaker admitted for this request, if any.
+pub(crate) fn record_upstream_status(ctx: &mut ReqCtx, status: u16) {
+    let Some(permit) = ctx.circuit_permit.take() else {
+        return;
+    };
+
+    if permit.breaker().is_failure_status(status) {
+        if permit.failure() {
+            let backend_id = &ctx.req_unsafe().backend.destination.name;
+            warn!(backend_id, status, "Circuit breaker opened");
+        }
+    } else {
+        permit.success();
+    }
+}
+
+/// Counts a request that ended in an upstream error (connect failure, timeout, reset) as a
+/// failure. Errors caused by the client, such as an oversized body or a disconnect, leave the
+/// breaker alone.
+pub(crate) fn record_upstream_error(ctx: &mut ReqCtx, error: &Error) {
+    let Some(permit) = ctx.circuit_permit.take() else {
+        return;
+    };
+    if error.esource() != &ErrorSource::Upstream {
+        return;
+    }
+
+    if permit.failure() {
+        let backend_id = ctx
+            .ctx_base
+            .resolved_request
+            .as_ref()
+            .map(|request| request.backend.destination.name.as_str());
+        warn!(backend_id, "Circuit breaker opened");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cardinal_base::destinations::circuit_breaker::{CircuitBreaker, CircuitState};
+    use cardinal_config::DestinationCircuitBreaker;
+
+    fn admitted(breaker: &Arc<CircuitBreaker>) -> ReqCtx {
+        ReqCtx {
+            circuit_permit: breaker.try_acquire(),
+            ..ReqCtx::default()
+        }
+    }
+
+    #[test]
+    fn only_upstream_errors_open_the_breaker() {
+        let breaker = Arc::new(CircuitBreaker::new(&DestinationCircuitBreaker {
+            failure_threshold: 1,
+            open_duration_ms: 60_000,
+            half_open_max_requests: None,
+            failure_status_codes: vec![502, 503, 504],
+            fallback_destination: None,
+        }));
+
+        let too_large = Error::explain(ErrorType::HTTPStatus(413), "request body too large");
+        record_upstream_error(&mut admitted(&breaker), &too_large);
+        let disconnected = Error::new_down(ErrorType::ConnectionClosed);
+        record_upstream_error(&mut admitted(&breaker), &disconnected);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        let refused = Error::new_up(ErrorType::ConnectRefused);
+        record_upstream_error(&mut admitted(&breaker), &refused);
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+}
@@ -1,4 +1,4 @@
-use cardinal_base::destinations::container::DestinationWrapper;
+use cardinal_config::Destination;
 use cardinal_errors::proxy::CardinalProxyError;
 use cardinal_errors::CardinalError;
 use cardinal_plugins::utils::parse_query_string_multi;
@@ -83,52 +83,81 @@ pub(crate) fn compose_upstream_url(
     format!("{scheme}://{hostport}{pq}")
 }
 
+pub(crate) fn upstream_host_header(origin: &str) -> Result<String, CardinalError> {
+    let (up_host, up_port, up_tls) = parse_origin(origin)?;
+    if (up_tls && up_port == 443) || (!up_tls && up_port == 80) {
+        Ok(up_host)
+    } else {
+        Ok(format!("{up_host}:{up_port}"))
+    }
+}
+
+/// `Host` sent to `origin` for `destination`, `None` when the client `Host` is preserved.
+pub(crate) fn destination_host_header(
+    destination: &Destination,
+    origin: &str,
+) -> Result<Option<String>, CardinalError> {
+    if destination.preserve_host {
+        return Ok(None);
+    }
+    match &destination.host_header {
+        Some(host) => Ok(Some(host.clone())),
+        None => upstream_host_header(origin).map(Some),
+    }
+}
+
 pub(crate) fn set_upstream_host_headers(
     session: &mut Session,
-    backend: &Arc<DestinationWrapper>,
+    destination: &Destination,
+    origin: &str,
 ) -> Result<(), CardinalError> {
-    let (up_host, up_port, up_tls) = parse_origin(&backend.destination.url)?;
-    let header_host = if (up_tls && up_port == 443) || (!up_tls && up_port == 80) {
-        up_host.clone()
-    } else {
-        format!("{up_host}:{up_port}")
+    apply_upstream_host_headers(session.req_header_mut(), destination, origin)
+}
+
+/// Points `Host` at `origin`, keeping the client value in `X-Forwarded-Host`.
+pub(crate) fn apply_upstream_host_headers(
+    req: &mut RequestHeader,
+    destination: &Destination,
+    origin: &str,
+) -> Result<(), CardinalError> {
+    let Some(header_host) = destination_host_header(destination, origin)? else {
+        return Ok(());
     };
 
     // Preserve original Host
-    let orig_host = session
-        .req_header()
+    let orig_host = req
         .headers
         .get("Host")
         .and_then(|h| h.to_str().ok())
         .map(|s| s.to_string());
 
     // Set Host to upstream host for virtual hosting and TLS SNI
-    session
-        .req_header_mut()
-        .insert_header("Host", header_host)
-        .unwrap();
+    req.insert_header("Host", header_host).unwrap();
 
     if let Some(h) = orig_host {
-        let _ = session
-            .req_header_mut()
-            .insert_header("X-Forwarded-Host", h);
+        let _ = req.insert_header("X-Forwarded-Host", h);
     }
 
     Ok(())
 }
 
-pub(crate) fn execution_context_from_request(session: &Session) -> ExecutionContext {
+pub(crate) fn execution_context_from_request(
+    session: &Session,
+    request_id: Option<String>,
+) -> ExecutionContext {
     let get_req_headers = session.req_header().headers.clone();
 
     let query = parse_query_string_multi(session.req_header().uri.query().unwrap_or(""));
 
-    ExecutionContext::from_parts(
+    let mut context = ExecutionContext::from_parts(
         get_req_headers,
         query,
         None,
         ResponseState::with_default_status(200),
         Arc::new(RwLock::new(HashMap::new())),
-    )
+    );
+    context.request_mut().set_request_id(request_id);
+    context
 }
 
 #[cfg(test)]
@@ -140,6 +169,40 @@ mod tests {
         RequestHeader::build(Method::GET, pq.as_bytes(), None).unwrap()
     }
 
+    #[test]
+    fn destination_host_header_follows_destination_settings() {
+        let destination = |extra: serde_json::Value| -> Destination {
+            let mut value = serde_json::json!({ "name": "posts" });
+            value
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            serde_json::from_value(value).unwrap()
+        };
+        let origin = "https://10.0.0.1:8443";
+
+        assert_eq!(
+            destination_host_header(&destination(serde_json::json!({})), origin).unwrap(),
+            Some("10.0.0.1:8443".to_string())
+        );
+        assert_eq!(
+            destination_host_header(
+                &destination(serde_json::json!({ "host_header": "posts.internal" })),
+                origin
+            )
+            .unwrap(),
+            Some("posts.internal".to_string())
+        );
+        assert_eq!(
+            destination_host_header(
+                &destination(serde_json::json!({ "preserve_host": true })),
+                origin
+            )
+            .unwrap(),
+            None
+        );
+    }
+
     #[test]
     fn force_path_strips_prefix_without_query() {
         let mut req = build_req("/api/foo");
@@ -233,6 +296,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn upstream_host_header_omits_default_port() {
+        assert_eq!(
+            upstream_host_header("https://example.com").unwrap(),
+            "example.com"
+        );
+        assert_eq!(
+            upstream_host_header("http://10.0.0.1:8080").unwrap(),
+            "10.0.0.1:8080"
+        );
+    }
+
     #[test]
     fn parse_origin_ipv6_host_with_port() {
         let (host, port, tls) = parse_origin("http://[::1]:8080").unwrap();
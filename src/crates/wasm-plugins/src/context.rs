@@ -102,6 +102,7 @@ pub struct RequestState {
     query: Arc<QueryStore>,
     body: Option<Bytes>,
     persistent_vars: Arc<RwLock<HashMap<String, String>>>,
+    request_id: Option<String>,
 }
 
 impl RequestState {
@@ -117,6 +118,7 @@ impl RequestState {
             query: Arc::new(query_store),
             body,
             persistent_vars,
+            request_id: None,
         }
     }
 
@@ -126,6 +128,7 @@ impl RequestState {
             query: Arc::new(QueryStore::new(HashMap::new())),
             body: None,
             persistent_vars: Arc::new(RwLock::new(HashMap::new())),
+            request_id: None,
         }
     }
 
@@ -163,6 +166,14 @@ impl RequestState {
     pub fn persistent_vars(&self) -> &Arc<RwLock<HashMap<String, String>>> {
         &self.persistent_vars
     }
+
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    pub fn set_request_id(&mut self, request_id: Option<String>) {
+        self.request_id = request_id;
+    }
 }
 
 impl Default for RequestState {
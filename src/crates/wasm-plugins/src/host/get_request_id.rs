@@ -0,0 +1,48 @@
+use crate::host::HostImport;
+use crate::utils::{with_mem_view, write_bytes};
+use crate::SharedExecutionContext;
+use wasmer::{Function, FunctionEnv, FunctionEnvMut, Store};
+
+pub(crate) struct GetRequestIdImport;
+
+impl HostImport for GetRequestIdImport {
+    fn namespace(&self) -> &str {
+        "env"
+    }
+
+    fn name(&self) -> &str {
+        "get_request_id"
+    }
+
+    fn build(&self, store: &mut Store, env: &FunctionEnv<SharedExecutionContext>) -> Function {
+        Function::new_typed_with_env(store, env, get_request_id_raw)
+    }
+}
+
+pub(crate) static GET_REQUEST_ID_IMPORT: GetRequestIdImport = GetRequestIdImport;
+
+/// Writes the request ID into guest memory, returning its full length or -1 when the request
+/// has none. When the ID is longer than `out_cap` only the first `out_cap` bytes are written,
+/// so a result above `out_cap` tells the guest to retry with a larger buffer.
+fn get_request_id_raw(
+    ctx: FunctionEnvMut<SharedExecutionContext>,
+    out_ptr: i32,
+    out_cap: i32,
+) -> i32 {
+    let view = match with_mem_view(&ctx) {
+        Ok(v) => v,
+        Err(_) => return -1,
+    };
+
+    let guard = ctx.data().read();
+    let Some(id) = guard.request().request_id() else {
+        return -1;
+    };
+
+    let write_len = id.len().min(out_cap.max(0) as usize);
+    if write_len > 0 && write_bytes(&view, out_ptr, &id.as_bytes()[..write_len]).is_err() {
+        return -1;
+    }
+
+    id.len() as i32
+}
@@ -10,6 +10,7 @@ mod abort;
 pub mod get_header;
 mod get_query_param;
 mod get_req_var;
+mod get_request_id;
 mod set_header;
 mod set_req_var;
 mod set_status;
@@ -18,6 +19,7 @@ use self::abort::ABORT_IMPORT;
 use self::get_header::GET_HEADER_IMPORT;
 use self::get_query_param::GET_QUERY_PARAM_IMPORT;
 use self::get_req_var::GET_REQ_VAR_IMPORT;
+use self::get_request_id::GET_REQUEST_ID_IMPORT;
 use self::set_header::SET_HEADER_IMPORT;
 use self::set_req_var::SET_REQ_VAR_IMPORT;
 use self::set_status::SET_STATUS_IMPORT;
@@ -118,6 +120,7 @@ static INBOUND_IMPORTS: &[&dyn HostImport] = &[
     &SET_STATUS_IMPORT,
     &SET_REQ_VAR_IMPORT,
     &GET_REQ_VAR_IMPORT,
+    &GET_REQUEST_ID_IMPORT,
 ];
 
 static OUTBOUND_IMPORTS: &[&dyn HostImport] = &[
@@ -128,6 +131,7 @@ static OUTBOUND_IMPORTS: &[&dyn HostImport] = &[
     &SET_STATUS_IMPORT,
     &SET_REQ_VAR_IMPORT,
     &GET_REQ_VAR_IMPORT,
+    &GET_REQUEST_ID_IMPORT,
 ];
 
 /// Read key from guest memory and write lookup result back into guest memory.
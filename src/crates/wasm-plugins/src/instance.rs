@@ -1,5 +1,6 @@
 use crate::context::ExecutionContext;
 use crate::host::{make_imports, HostImportHandle};
+use crate::metrics::{WASM_INSTANCES_CREATED, WASM_POOLED_INSTANCES};
 use crate::plugin::WasmPlugin;
 use crate::runner::ExecutionPhase;
 use crate::SharedExecutionContext;
@@ -37,8 +38,11 @@ impl InstancePool {
         let mut instance = pooled.pop();
         drop(pooled);
 
-        if instance.is_none() {
+        if instance.is_some() {
+            WASM_POOLED_INSTANCES.dec();
+        } else {
             instance = Some(self.instantiate()?);
+            WASM_INSTANCES_CREATED.inc();
         }
 
         let mut instance = instance.expect("instance must be present");
@@ -122,10 +126,17 @@ impl Drop for InstanceGuard<'_> {
         if let Some(instance) = self.instance.take() {
             let mut pooled = self.pool.instances.lock();
             pooled.push(instance);
+            WASM_POOLED_INSTANCES.inc();
         }
     }
 }
 
+impl Drop for InstancePool {
+    fn drop(&mut self) {
+        WASM_POOLED_INSTANCES.sub(self.instances.get_mut().len() as i64);
+    }
+}
+
 pub struct PreparedInstance {
     store: Store,
     _instance: Instance,
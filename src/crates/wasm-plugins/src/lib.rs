@@ -1,6 +1,7 @@
 mod context;
 pub mod host;
 pub mod instance;
+pub mod metrics;
 pub mod plugin;
 pub mod runner;
 pub mod utils;
@@ -52,6 +53,92 @@ mod tests {
         run_wasm_case("inbound-block", ScenarioKind::Request);
     }
 
+    /// Copies the request ID into the `x-seen-request-id` response header.
+    const REQUEST_ID_PLUGIN: &str = r#"
+        (module
+          (import "env" "get_request_id" (func $get_request_id (param i32 i32) (result i32)))
+          (import "env" "set_header" (func $set_header (param i32 i32 i32 i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "x-seen-request-id")
+          (func (export "__new") (param i32 i32) (result i32)
+            i32.const 1024)
+          (func (export "handle") (param i32 i32) (result i32)
+            (local $len i32)
+            (local.set $len (call $get_request_id (i32.const 64) (i32.const 128)))
+            (if (i32.ge_s (local.get $len) (i32.const 0))
+              (then
+                (call $set_header (i32.const 1) (i32.const 0) (i32.const 17)
+                  (i32.const 64) (local.get $len))))
+            i32.const 1))
+    "#;
+
+    #[test]
+    fn wasm_plugin_reads_request_id() {
+        let (engine, module) = WasmPlugin::initiate(REQUEST_ID_PLUGIN.as_bytes(), None).unwrap();
+        let wasm_plugin = Arc::new(WasmPlugin::new(engine, module, None, None).unwrap());
+        let runner = WasmRunner::new(&wasm_plugin, ExecutionPhase::Outbound, None);
+
+        let mut with_id = ExecutionContext::new();
+        with_id
+            .request_mut()
+            .set_request_id(Some("0190b6f4-6c4e-7b1a-9c3d-5f2e8a7b6c1d".into()));
+        let result = runner.run(Arc::new(RwLock::new(with_id))).unwrap();
+        let context = result.execution_context.read();
+        assert_eq!(
+            context.response().headers()["x-seen-request-id"],
+            "0190b6f4-6c4e-7b1a-9c3d-5f2e8a7b6c1d"
+        );
+
+        let result = runner
+            .run(Arc::new(RwLock::new(ExecutionContext::new())))
+            .unwrap();
+        let context = result.execution_context.read();
+        assert!(!context
+            .response()
+            .headers()
+            .contains_key("x-seen-request-id"));
+    }
+
+    /// Asks for the request ID with an 8-byte buffer and, when the host reports the full
+    /// 36-byte length, copies the buffer plus the untouched bytes after it into
+    /// `x-seen-request-id`.
+    const SHORT_BUFFER_REQUEST_ID_PLUGIN: &str = r#"
+        (module
+          (import "env" "get_request_id" (func $get_request_id (param i32 i32) (result i32)))
+          (import "env" "set_header" (func $set_header (param i32 i32 i32 i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "x-seen-request-id")
+          (data (i32.const 72) "/rest")
+          (func (export "__new") (param i32 i32) (result i32)
+            i32.const 1024)
+          (func (export "handle") (param i32 i32) (result i32)
+            (if (i32.ne (call $get_request_id (i32.const 64) (i32.const 8)) (i32.const 36))
+              (then (return (i32.const 0))))
+            (call $set_header (i32.const 1) (i32.const 0) (i32.const 17)
+              (i32.const 64) (i32.const 13))
+            i32.const 1))
+    "#;
+
+    #[test]
+    fn wasm_plugin_request_id_reports_full_length_for_small_buffer() {
+        let (engine, module) =
+            WasmPlugin::initiate(SHORT_BUFFER_REQUEST_ID_PLUGIN.as_bytes(), None).unwrap();
+        let wasm_plugin = Arc::new(WasmPlugin::new(engine, module, None, None).unwrap());
+        let runner = WasmRunner::new(&wasm_plugin, ExecutionPhase::Outbound, None);
+
+        let mut with_id = ExecutionContext::new();
+        with_id
+            .request_mut()
+            .set_request_id(Some("0190b6f4-6c4e-7b1a-9c3d-5f2e8a7b6c1d".into()));
+        let result = runner.run(Arc::new(RwLock::new(with_id))).unwrap();
+        assert!(result.should_continue);
+        let context = result.execution_context.read();
+        assert_eq!(
+            context.response().headers()["x-seen-request-id"],
+            "0190b6f4/rest"
+        );
+    }
+
     fn run_wasm_case(name: &str, expected_type: ScenarioKind) {
         let case_dir = case_path(name);
         let wasm_path = case_dir.join("plugin.wasm");
@@ -234,6 +321,7 @@ mod tests {
         lowered
     }
 
+    #[allow(dead_code)]
     fn response_state_from_value(value: &Value, default_status: u16) -> ResponseState {
         let headers = lowercase_string_map(json_string_map(value.get("resp_headers")));
         let override_status = value.get("status").and_then(Value::as_i64).and_then(|raw| {
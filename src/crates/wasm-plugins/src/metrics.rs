@@ -0,0 +1,20 @@
+use prometheus::{register_int_counter, register_int_gauge, IntCounter, IntGauge};
+use std::sync::LazyLock;
+
+/// Idle WASM instances parked in instance pools, ready to be reused.
+pub static WASM_POOLED_INSTANCES: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "cardinal_wasm_pooled_instances",
+        "Idle WASM instances held by instance pools"
+    )
+    .expect("cardinal_wasm_pooled_instances registers once")
+});
+
+/// WASM instances created because no pooled instance was available.
+pub static WASM_INSTANCES_CREATED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "cardinal_wasm_instances_created_total",
+        "WASM instances instantiated by instance pools"
+    )
+    .expect("cardinal_wasm_instances_created_total registers once")
+});